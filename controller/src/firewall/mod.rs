@@ -12,7 +12,7 @@ use message::Log;
 use message::{
     async_bincode::{tokio::AsyncBincodeStream, AsyncDestination},
     firewall::{self, LogKind, Status},
-    firewall_common::{StoredEventDecoded, StoredRuleDecoded},
+    firewall_common::{conntrack::Connection, StoredEventDecoded, StoredRuleDecoded},
     EventQuery, Message,
 };
 use tokio::net::UnixStream;
@@ -67,8 +67,12 @@ pub fn router() -> Router<AppState> {
         .route("/:idx", routing::get(get_rule).delete(delete))
         .route("/", routing::get(get_rules).post(add));
 
+    let connections =
+        Router::new().route("/", routing::get(get_connections).delete(flush_connections));
+
     Router::new()
         .nest("/rules", rules)
+        .nest("/connections", connections)
         .nest("/state", state)
         .nest("/events", events)
 }
@@ -205,6 +209,19 @@ pub async fn add(
     Json(socket.read().await)
 }
 
+pub async fn get_connections(State(s): State<AppState>) -> Json<Vec<Connection>> {
+    Json(s.firewall_pool.get().await.unwrap().get_connections().await)
+}
+
+pub async fn flush_connections(State(s): State<AppState>) {
+    s.firewall_pool
+        .get()
+        .await
+        .unwrap()
+        .flush_connections()
+        .await;
+}
+
 pub async fn start(State(s): State<AppState>) {
     s.firewall_pool.get().await.unwrap().start().await;
 }
@@ -343,6 +360,21 @@ impl Socket {
         }
     }
 
+    pub async fn get_connections(&mut self) -> Vec<Connection> {
+        self.send(Message::Firewall(firewall::Request::GetConnections))
+            .await;
+
+        match self.read().await {
+            firewall::Response::Connections(connections) => connections,
+            _ => unreachable!(),
+        }
+    }
+
+    pub async fn flush_connections(&mut self) {
+        self.send(Message::Firewall(firewall::Request::FlushConnections))
+            .await
+    }
+
     pub async fn start(&mut self) {
         self.send(Message::Start).await
     }
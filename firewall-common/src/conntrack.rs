@@ -0,0 +1,364 @@
+//! Connection tracking shared by the eBPF programs and the controller. Entries are keyed by the
+//! [`FlowKey`] of the packet which opened the connection, replies are found with the reversed
+//! key. The XDP programs track ingress packets and the egress classifier the ones sent by the host
+
+#[cfg(feature = "serde")]
+use core::net::SocketAddr;
+
+use netp::flow::FlowKey;
+use netp::network::InetProtocol;
+
+use crate::{Headers, TcpFlags};
+
+pub const MAX_CONNECTIONS: u32 = 16384;
+
+const SECOND: u64 = 1_000_000_000;
+
+/// Timeouts in nanoseconds since the last packet, as given by `bpf_ktime_get_ns`
+pub const TCP_HANDSHAKE_TIMEOUT: u64 = 120 * SECOND;
+pub const TCP_ESTABLISHED_TIMEOUT: u64 = 2 * 3600 * SECOND;
+pub const TCP_CLOSING_TIMEOUT: u64 = 10 * SECOND;
+/// UDP and ICMP echo
+pub const UNREPLIED_TIMEOUT: u64 = 30 * SECOND;
+pub const REPLIED_TIMEOUT: u64 = 120 * SECOND;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum ConnState {
+    /// Opens a connection, or belongs to one without reply yet or still in the TCP handshake
+    New,
+    /// Belongs to a connection which got a reply, or completed the TCP handshake
+    Established,
+    /// ICMP error about a tracked connection
+    Related,
+    /// TCP segment or ICMP reply outside of any connection or after a RST, or a non-first
+    /// fragment
+    Invalid,
+}
+
+/// Progress of a TCP connection, `None` for the other protocols
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum TcpState {
+    None,
+    SynSent,
+    SynReceived,
+    Established,
+    /// A FIN was seen
+    Closing,
+    /// A RST was seen
+    Closed,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Conn {
+    /// `bpf_ktime_get_ns` of the last accepted packet
+    pub last_seen: u64,
+    pub tcp: TcpState,
+    /// A packet was accepted from the destination
+    pub replied: bool,
+    /// Copied to user space with the rest of the entry, always zero
+    pub _padding: [u8; 6],
+}
+
+impl Conn {
+    /// Entry for the first packet of a connection. TCP connections only open with a SYN
+    pub fn open(protocol: InetProtocol, tcp_flags: Option<u8>, now: u64) -> Option<Self> {
+        let tcp = match (protocol, tcp_flags) {
+            (InetProtocol::TCP, Some(flags))
+                if flags & (TcpFlags::SYN | TcpFlags::ACK | TcpFlags::RST) == TcpFlags::SYN =>
+            {
+                TcpState::SynSent
+            }
+            (InetProtocol::TCP, _) => return None,
+            _ => TcpState::None,
+        };
+
+        Some(Self {
+            last_seen: now,
+            tcp,
+            replied: false,
+            _padding: [0; 6],
+        })
+    }
+
+    /// Advances the entry with a packet, `reply` when sent by the destination. Returns the state of
+    /// the packet, the one of the connection after it except for the RST closing it
+    pub fn update(&mut self, tcp_flags: Option<u8>, reply: bool, now: u64) -> ConnState {
+        let before = self.state();
+
+        if let Some(flags) = tcp_flags {
+            let syn_ack = flags & (TcpFlags::SYN | TcpFlags::ACK);
+
+            self.tcp = match self.tcp {
+                _ if flags & TcpFlags::RST != 0 => TcpState::Closed,
+                _ if flags & TcpFlags::FIN != 0 => TcpState::Closing,
+                TcpState::SynSent if reply && syn_ack == TcpFlags::SYN | TcpFlags::ACK => {
+                    TcpState::SynReceived
+                }
+                TcpState::SynReceived if !reply && syn_ack == TcpFlags::ACK => {
+                    TcpState::Established
+                }
+                state => state,
+            };
+        }
+
+        self.replied |= reply;
+        self.last_seen = now;
+
+        match self.tcp {
+            TcpState::Closed if before != ConnState::Invalid => before,
+            _ => self.state(),
+        }
+    }
+
+    pub fn timeout(&self) -> u64 {
+        match self.tcp {
+            TcpState::None if self.replied => REPLIED_TIMEOUT,
+            TcpState::None => UNREPLIED_TIMEOUT,
+            TcpState::SynSent | TcpState::SynReceived => TCP_HANDSHAKE_TIMEOUT,
+            TcpState::Established => TCP_ESTABLISHED_TIMEOUT,
+            TcpState::Closing | TcpState::Closed => TCP_CLOSING_TIMEOUT,
+        }
+    }
+
+    pub fn is_expired(&self, now: u64) -> bool {
+        now.saturating_sub(self.last_seen) > self.timeout()
+    }
+
+    pub fn state(&self) -> ConnState {
+        match self.tcp {
+            TcpState::None if self.replied => ConnState::Established,
+            TcpState::None | TcpState::SynSent | TcpState::SynReceived => ConnState::New,
+            TcpState::Established | TcpState::Closing => ConnState::Established,
+            TcpState::Closed => ConnState::Invalid,
+        }
+    }
+}
+
+/// Connection state of a packet of `key`, with the updated entry to store for it. `lookup` gives
+/// the stored entry of a key, expired ones are ignored. Only packets with `may_open` start new
+/// connections
+pub fn track(
+    headers: &Headers,
+    key: FlowKey,
+    may_open: bool,
+    now: u64,
+    lookup: impl Fn(&FlowKey) -> Option<Conn>,
+) -> (ConnState, Option<(FlowKey, Conn)>) {
+    let lookup = |key: &FlowKey| lookup(key).filter(|conn| !conn.is_expired(now));
+    let reversed = key.reversed();
+
+    let found = match lookup(&key) {
+        Some(conn) => Some((key, conn, false)),
+        None => lookup(&reversed).map(|conn| (reversed, conn, true)),
+    };
+
+    if let Some((key, mut conn, reply)) = found {
+        let state = conn.update(headers.tcp_flags, reply, now);
+        return (state, Some((key, conn)));
+    }
+
+    let protocol = headers.protocol().ok();
+
+    match protocol.and_then(|protocol| Conn::open(protocol, headers.tcp_flags, now)) {
+        Some(conn) if may_open => (ConnState::New, Some((key, conn))),
+        _ => (ConnState::Invalid, None),
+    }
+}
+
+#[cfg(feature = "aya")]
+unsafe impl aya::Pod for Conn {}
+
+/// Tracked connection, as listed by the controller
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Connection {
+    pub source: SocketAddr,
+    pub destination: SocketAddr,
+    pub protocol: InetProtocol,
+    pub state: ConnState,
+    pub tcp: TcpState,
+    /// Time since the last accepted packet
+    pub idle: core::time::Duration,
+}
+
+#[cfg(feature = "serde")]
+impl Connection {
+    /// `now` as given by `CLOCK_MONOTONIC`, the clock of `bpf_ktime_get_ns`
+    pub fn new(key: &FlowKey, conn: &Conn, now: u64) -> Option<Self> {
+        if conn.is_expired(now) {
+            return None;
+        }
+
+        Some(Self {
+            source: SocketAddr::new(key.source(), key.source_port()),
+            destination: SocketAddr::new(key.destination(), key.destination_port()),
+            protocol: key.protocol().ok()?,
+            state: conn.state(),
+            tcp: conn.tcp,
+            idle: core::time::Duration::from_nanos(now.saturating_sub(conn.last_seen)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+    use core::net::IpAddr;
+
+    use netp::flow::FlowKey;
+    use netp::network::InetProtocol;
+
+    use crate::conntrack::{
+        track, Conn, ConnState, TcpState, TCP_ESTABLISHED_TIMEOUT, TCP_HANDSHAKE_TIMEOUT,
+    };
+    use crate::{Headers, TcpFlags};
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn connection() {
+        use core::net::SocketAddr;
+
+        use crate::conntrack::{Connection, UNREPLIED_TIMEOUT};
+
+        let key = FlowKey::new(
+            IpAddr::from([10, 0, 0, 1]),
+            IpAddr::from([10, 0, 0, 2]),
+            40000,
+            53,
+            InetProtocol::UDP,
+        );
+        let conn = Conn::open(InetProtocol::UDP, None, 0).unwrap();
+
+        let connection = Connection::new(&key.reversed(), &conn, 1).unwrap();
+        assert_eq!(connection.source, SocketAddr::from(([10, 0, 0, 2], 53)));
+        assert_eq!(connection.protocol, InetProtocol::UDP);
+        assert_eq!(connection.state, ConnState::New);
+
+        assert!(Connection::new(&key, &conn, UNREPLIED_TIMEOUT + 1).is_none());
+    }
+
+    #[test]
+    fn tcp_handshake() {
+        let ack = Some(TcpFlags::ACK);
+
+        assert_eq!(Conn::open(InetProtocol::TCP, ack, 0), None);
+
+        let mut conn = Conn::open(InetProtocol::TCP, Some(TcpFlags::SYN), 0).unwrap();
+        assert_eq!(conn.state(), ConnState::New);
+
+        let state = conn.update(Some(TcpFlags::SYN | TcpFlags::ACK), true, 1);
+        assert_eq!(conn.tcp, TcpState::SynReceived);
+        assert_eq!(state, ConnState::New);
+
+        assert_eq!(conn.update(ack, false, 2), ConnState::Established);
+        assert_eq!(conn.tcp, TcpState::Established);
+        assert!(!conn.is_expired(TCP_HANDSHAKE_TIMEOUT * 2));
+
+        conn.update(Some(TcpFlags::FIN | TcpFlags::ACK), false, 3);
+        assert_eq!(conn.tcp, TcpState::Closing);
+        assert!(conn.is_expired(TCP_HANDSHAKE_TIMEOUT));
+    }
+
+    #[test]
+    fn tcp_reset() {
+        let ack = Some(TcpFlags::ACK);
+        let mut conn = Conn::open(InetProtocol::TCP, Some(TcpFlags::SYN), 0).unwrap();
+
+        // Reset before the handshake completed
+        assert_eq!(conn.update(Some(TcpFlags::RST), true, 1), ConnState::New);
+        assert_eq!(conn.state(), ConnState::Invalid);
+        assert_eq!(conn.update(ack, false, 2), ConnState::Invalid);
+
+        let mut conn = Conn::open(InetProtocol::TCP, Some(TcpFlags::SYN), 0).unwrap();
+        conn.update(Some(TcpFlags::SYN | TcpFlags::ACK), true, 1);
+        conn.update(ack, false, 2);
+
+        let rst = Some(TcpFlags::RST | TcpFlags::ACK);
+        assert_eq!(conn.update(rst, true, 3), ConnState::Established);
+        assert_eq!(conn.tcp, TcpState::Closed);
+        assert_eq!(conn.update(ack, false, 4), ConnState::Invalid);
+        assert_eq!(conn.update(rst, true, 5), ConnState::Invalid);
+    }
+
+    #[test]
+    fn host_initiated() {
+        let (host, remote) = (IpAddr::from([10, 0, 0, 1]), IpAddr::from([192, 0, 2, 1]));
+
+        // Single entry table, the egress classifier stores every tracked packet while the XDP
+        // programs store the accepted ones
+        let table = Cell::new(None::<(FlowKey, Conn)>);
+        let lookup = |key: &FlowKey| table.get().filter(|(k, _)| k == key).map(|(_, c)| c);
+        let packet = |outbound: bool, flags: u8, now: u64| {
+            let (headers, ports) = if outbound {
+                (
+                    Headers::new(host, remote, InetProtocol::TCP.into()),
+                    (40000, 443),
+                )
+            } else {
+                (
+                    Headers::new(remote, host, InetProtocol::TCP.into()),
+                    (443, 40000),
+                )
+            };
+            let headers = Headers {
+                ports: Some(ports),
+                tcp_flags: Some(flags),
+                ..headers
+            };
+            let key = FlowKey::new(
+                headers.source,
+                headers.destination,
+                ports.0,
+                ports.1,
+                InetProtocol::TCP,
+            );
+            let (state, tracked) = track(&headers, key, true, now, lookup);
+
+            table.set(tracked.or(table.get()));
+            state
+        };
+
+        let syn_ack = TcpFlags::SYN | TcpFlags::ACK;
+
+        // Without the egress SYN the reply is not part of any connection
+        assert_eq!(packet(false, syn_ack, 0), ConnState::Invalid);
+        assert_eq!(table.get(), None);
+
+        assert_eq!(packet(true, TcpFlags::SYN, 0), ConnState::New);
+        assert_eq!(packet(false, syn_ack, 1), ConnState::New);
+        assert_eq!(packet(true, TcpFlags::ACK, 2), ConnState::Established);
+        assert_eq!(packet(false, TcpFlags::ACK, 3), ConnState::Established);
+
+        let (key, conn) = table.get().unwrap();
+        assert_eq!((key.source(), key.source_port()), (host, 40000));
+        assert_eq!(conn.tcp, TcpState::Established);
+
+        let expired = 4 + TCP_ESTABLISHED_TIMEOUT;
+        assert_eq!(packet(false, TcpFlags::ACK, expired), ConnState::Invalid);
+    }
+
+    #[test]
+    fn layout() {
+        assert_eq!(core::mem::size_of::<Conn>(), 16);
+    }
+
+    #[test]
+    fn udp() {
+        let mut conn = Conn::open(InetProtocol::UDP, None, 0).unwrap();
+        assert_eq!(conn.tcp, TcpState::None);
+        assert_eq!(conn.state(), ConnState::New);
+
+        assert_eq!(conn.update(None, true, 1), ConnState::Established);
+        assert_eq!(conn.state(), ConnState::Established);
+    }
+}
@@ -2,12 +2,23 @@
 
 pub const MAX_RULES: u32 = 100;
 
+/// Indices of the tail-called programs in `PROCESSOR`
 pub mod processor {
     pub const IPV4_TCP: u32 = 0;
+    pub const IPV4_UDP: u32 = 1;
+    pub const IPV6_TCP: u32 = 2;
+    pub const IPV6_UDP: u32 = 3;
+    pub const IPV6_ICMP: u32 = 4;
+    pub const IPV4_ICMP: u32 = 5;
 }
 
+pub mod conntrack;
+
 pub use netp;
-use netp::network::InetProtocol;
+
+use conntrack::ConnState;
+use core::net::{IpAddr, SocketAddr};
+use netp::network::{InetProtocol, IpNetwork};
 
 #[derive(Debug, Clone, Copy)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
@@ -17,7 +28,9 @@ pub enum Event {
     Pass,
     Blocked {
         rule: u32,
-        addr: core::net::SocketAddr,
+        /// Side of the packet the rule applies to, IPv6 for packets of the IPv6 path. The port
+        /// is 0 without TCP or UDP header
+        addr: SocketAddr,
     },
 }
 
@@ -37,8 +50,93 @@ pub enum Action {
 pub enum Match {
     Match(core::net::IpAddr),
     Socket(core::net::SocketAddr),
+    /// TCP or UDP port
     Port(u16),
     Protocol(InetProtocol),
+    /// Every address of the block, e.g. `203.0.113.0/24`. Looked up in an LPM trie instead of
+    /// scanning the rules, the most specific network wins
+    Network(IpNetwork),
+    /// Inclusive range of TCP or UDP ports
+    PortRange {
+        start: u16,
+        end: u16,
+    },
+    PortSet(PortSet),
+    /// Every packet, for rules relying on [`Rule::conditions`] only
+    Any,
+    /// Connection tracking state, e.g. `Established` to let replies through
+    State(ConnState),
+}
+
+impl Match {
+    /// Whether a TCP or UDP port is matched, always false for non-port matches
+    pub fn matches_port(&self, port: u16) -> bool {
+        match *self {
+            Match::Port(p) => p == port,
+            Match::PortRange { start, end } => start <= port && port <= end,
+            Match::PortSet(set) => set.contains(port),
+            _ => false,
+        }
+    }
+
+    /// Whether the match can only be evaluated by the processors, once the transport header is
+    /// parsed and the connection tracked
+    pub fn needs_transport(&self) -> bool {
+        matches!(
+            self,
+            Match::Socket(_)
+                | Match::Port(_)
+                | Match::PortRange { .. }
+                | Match::PortSet(_)
+                | Match::State(_)
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct PortRange {
+    pub start: u16,
+    /// Inclusive
+    pub end: u16,
+}
+
+impl PortRange {
+    pub fn contains(&self, port: u16) -> bool {
+        self.start <= port && port <= self.end
+    }
+}
+
+/// Named group of ports, matched as a single rule
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum PortSet {
+    /// 80, 443 and 8080
+    Web,
+    /// 25, 465, 587, 993 and 995
+    Mail,
+    /// 53 and 853 for DNS over TLS
+    Dns,
+    /// 0 to 1023, reserved for system services
+    WellKnown,
+    /// 49152 to 65535, picked by clients for outgoing connections
+    Ephemeral,
+}
+
+impl PortSet {
+    pub fn contains(&self, port: u16) -> bool {
+        match self {
+            PortSet::Web => matches!(port, 80 | 443 | 8080),
+            PortSet::Mail => matches!(port, 25 | 465 | 587 | 993 | 995),
+            PortSet::Dns => matches!(port, 53 | 853),
+            PortSet::WellKnown => port <= 1023,
+            PortSet::Ephemeral => port >= 49152,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -50,6 +148,17 @@ pub enum Direction {
     Destination,
 }
 
+/// Rules of an earlier phase take precedence over the ones of later phases whatever their index
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// `Match::State` rules, so accepting established connections overrides the address rules
+    State,
+    /// Rules decided by the IP header, the scanned ones before the network tries
+    Ip,
+    /// Rules needing the transport header
+    Transport,
+}
+
 #[derive(Debug, Clone, Copy)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
@@ -67,6 +176,258 @@ pub struct Rule {
     /// All added rules are marked as initialized
     #[cfg_attr(feature = "serde", serde(default))]
     pub init: bool,
+    /// ANDed with `matches`. Rules stored before it existed are decoded as [`RuleV0`]
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub conditions: Conditions,
+}
+
+impl Rule {
+    /// Whether the rule can only be evaluated by the processors
+    pub fn needs_transport(&self) -> bool {
+        self.matches.needs_transport() || self.conditions.needs_transport()
+    }
+
+    pub fn phase(&self) -> Phase {
+        match self.matches {
+            Match::State(_) => Phase::State,
+            _ if self.needs_transport() => Phase::Transport,
+            _ => Phase::Ip,
+        }
+    }
+
+    /// Plain `Match::Network` rules are looked up in an LPM trie instead of being scanned
+    pub fn uses_network_trie(&self) -> bool {
+        matches!(self.matches, Match::Network(_)) && self.conditions.is_empty()
+    }
+
+    /// Whether both `matches` and `conditions` hold, `enabled` is not checked
+    pub fn applies(&self, headers: &Headers) -> bool {
+        let socket = headers.socket(self.applies_to);
+        let port = headers.ports.map(|_| socket.port());
+
+        let matched = match self.matches {
+            Match::Match(address) => address == socket.ip(),
+            Match::Socket(expected) => port.is_some() && expected == socket,
+            Match::Port(_) | Match::PortRange { .. } | Match::PortSet(_) => {
+                port.is_some_and(|port| self.matches.matches_port(port))
+            }
+            Match::Protocol(protocol) => headers.protocol() == Ok(protocol),
+            Match::Network(network) => network.contains(socket.ip()),
+            Match::Any => true,
+            Match::State(state) => headers.state == Some(state),
+        };
+
+        matched && self.conditions.hold(headers)
+    }
+}
+
+/// First enabled rule applying to `headers`, by [`Phase`] then index. `rule` gives the rule at an
+/// index and `network` the index stored in the network trie of a side for an address
+pub fn evaluate<'r>(
+    headers: &Headers,
+    rule: impl Fn(u32) -> Option<&'r Rule>,
+    network: impl Fn(Direction, IpAddr) -> Option<u32>,
+) -> Option<(u32, &'r Rule)> {
+    let enabled = |i: u32| {
+        rule(i)
+            .filter(|rule| rule.init && rule.enabled)
+            .map(|rule| (i, rule))
+    };
+    let mut ip = None;
+    let mut transport = None;
+
+    for i in 0..MAX_RULES {
+        let Some((i, rule)) = enabled(i) else {
+            continue;
+        };
+
+        if rule.uses_network_trie() || !rule.applies(headers) {
+            continue;
+        }
+
+        match rule.phase() {
+            Phase::State => return Some((i, rule)),
+            Phase::Ip => {
+                ip.get_or_insert((i, rule));
+            }
+            Phase::Transport => {
+                transport.get_or_insert((i, rule));
+            }
+        }
+    }
+
+    ip.or_else(|| network(Direction::Source, headers.source).and_then(enabled))
+        .or_else(|| network(Direction::Destination, headers.destination).and_then(enabled))
+        .or(transport)
+}
+
+/// Extra fields a rule must match, unset fields match every packet
+#[derive(Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Conditions {
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub source: Option<NetworkMatch>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub destination: Option<NetworkMatch>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub protocol: Option<InetProtocol>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub source_ports: Option<PortRange>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub destination_ports: Option<PortRange>,
+    /// Only TCP packets match once set
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub tcp_flags: Option<TcpFlags>,
+}
+
+impl Conditions {
+    pub fn is_empty(&self) -> bool {
+        self.source.is_none()
+            && self.destination.is_none()
+            && self.protocol.is_none()
+            && !self.needs_transport()
+    }
+
+    pub fn needs_transport(&self) -> bool {
+        self.source_ports.is_some() || self.destination_ports.is_some() || self.tcp_flags.is_some()
+    }
+
+    pub fn hold(&self, headers: &Headers) -> bool {
+        let ports = |range: Option<PortRange>, port: fn((u16, u16)) -> u16| {
+            range.is_none_or(|range| {
+                headers
+                    .ports
+                    .is_some_and(|ports| range.contains(port(ports)))
+            })
+        };
+
+        self.source.is_none_or(|n| n.matches(headers.source))
+            && self
+                .destination
+                .is_none_or(|n| n.matches(headers.destination))
+            && self.protocol.is_none_or(|p| headers.protocol() == Ok(p))
+            && ports(self.source_ports, |(source, _)| source)
+            && ports(self.destination_ports, |(_, destination)| destination)
+            && self
+                .tcp_flags
+                .is_none_or(|f| headers.tcp_flags.is_some_and(|flags| f.matches(flags)))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct NetworkMatch {
+    pub network: IpNetwork,
+    /// Match the addresses outside of `network` instead
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub negate: bool,
+}
+
+impl NetworkMatch {
+    pub fn matches(&self, address: IpAddr) -> bool {
+        self.network.contains(address) != self.negate
+    }
+}
+
+/// The flags selected by `mask` must be equal to `value`. A mask of `SYN | ACK` with a value of
+/// `SYN` matches connection openings only
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct TcpFlags {
+    pub mask: u8,
+    pub value: u8,
+}
+
+impl TcpFlags {
+    pub const FIN: u8 = 0x01;
+    pub const SYN: u8 = 0x02;
+    pub const RST: u8 = 0x04;
+    pub const PSH: u8 = 0x08;
+    pub const ACK: u8 = 0x10;
+    pub const URG: u8 = 0x20;
+    pub const ECE: u8 = 0x40;
+    pub const CWR: u8 = 0x80;
+
+    pub fn matches(&self, flags: u8) -> bool {
+        flags & self.mask == self.value & self.mask
+    }
+}
+
+/// Header fields rules are evaluated against. The transport fields are only set for TCP and UDP
+#[derive(Debug, Clone, Copy)]
+pub struct Headers {
+    pub source: IpAddr,
+    pub destination: IpAddr,
+    /// Number of the upper-layer protocol, which [`InetProtocol`] may not know
+    pub protocol: u8,
+    /// Source and destination ports
+    pub ports: Option<(u16, u16)>,
+    pub tcp_flags: Option<u8>,
+    /// Unset for untracked packets
+    pub state: Option<ConnState>,
+}
+
+impl Headers {
+    pub fn new(source: IpAddr, destination: IpAddr, protocol: u8) -> Self {
+        Self {
+            source,
+            destination,
+            protocol,
+            ports: None,
+            tcp_flags: None,
+            state: None,
+        }
+    }
+
+    #[allow(clippy::result_unit_err)]
+    pub fn protocol(&self) -> Result<InetProtocol, ()> {
+        InetProtocol::try_from(self.protocol)
+    }
+
+    /// Address and port on the `direction` side, the port is 0 without transport header
+    pub fn socket(&self, direction: Direction) -> SocketAddr {
+        let (source, destination) = self.ports.unwrap_or_default();
+
+        match direction {
+            Direction::Source => SocketAddr::new(self.source, source),
+            Direction::Destination => SocketAddr::new(self.destination, destination),
+        }
+    }
+}
+
+/// [`Rule`] as stored before `conditions` existed. Those rows were padded with zeros to the size
+/// of the rule back then, which leaves no room for `conditions` after the longest matches, so they
+/// are decoded as this once decoding a `Rule` fails
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy)]
+pub struct RuleV0 {
+    pub id: u32,
+    pub action: Action,
+    pub matches: Match,
+    pub applies_to: Direction,
+    pub enabled: bool,
+    pub init: bool,
+}
+
+#[cfg(feature = "serde")]
+impl From<RuleV0> for Rule {
+    fn from(rule: RuleV0) -> Self {
+        Self {
+            id: rule.id,
+            action: rule.action,
+            matches: rule.matches,
+            applies_to: rule.applies_to,
+            enabled: rule.enabled,
+            init: rule.init,
+            conditions: Conditions::default(),
+        }
+    }
 }
 
 #[cfg(feature = "serde")]
@@ -104,3 +465,272 @@ impl From<Action> for u32 {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use core::net::{IpAddr, SocketAddr};
+    use core::str::FromStr;
+
+    use netp::network::{InetProtocol, IpNetwork};
+
+    use crate::conntrack::ConnState;
+    use crate::{
+        evaluate, Action, Conditions, Direction, Headers, Match, NetworkMatch, PortRange, PortSet,
+        Rule, TcpFlags,
+    };
+
+    #[test]
+    fn ports() {
+        let range = Match::PortRange {
+            start: 8000,
+            end: 8999,
+        };
+        assert!(range.matches_port(8000));
+        assert!(range.matches_port(8999));
+        assert!(!range.matches_port(9000));
+
+        assert!(Match::PortSet(PortSet::Web).matches_port(8080));
+        assert!(!Match::PortSet(PortSet::Web).matches_port(8081));
+        assert!(Match::PortSet(PortSet::Ephemeral).matches_port(u16::MAX));
+        assert!(Match::Port(22).matches_port(22));
+        assert!(!Match::Protocol(netp::network::InetProtocol::TCP).matches_port(22));
+    }
+
+    #[test]
+    fn compound() {
+        // Drop TCP to 10.0.0.5 port 22 from outside 10.0.0.0/8
+        let rule = Rule {
+            id: 0,
+            action: Action::Drop,
+            matches: Match::Match(IpAddr::from([10, 0, 0, 5])),
+            applies_to: Direction::Destination,
+            enabled: true,
+            init: true,
+            conditions: Conditions {
+                source: Some(NetworkMatch {
+                    network: IpNetwork::from_str("10.0.0.0/8").unwrap(),
+                    negate: true,
+                }),
+                protocol: Some(InetProtocol::TCP),
+                destination_ports: Some(PortRange { start: 22, end: 22 }),
+                tcp_flags: Some(TcpFlags {
+                    mask: TcpFlags::SYN | TcpFlags::ACK,
+                    value: TcpFlags::SYN,
+                }),
+                ..Default::default()
+            },
+        };
+        assert!(rule.needs_transport());
+        assert!(!rule.uses_network_trie());
+
+        let mut headers = Headers::new(
+            IpAddr::from([198, 51, 100, 7]),
+            IpAddr::from([10, 0, 0, 5]),
+            InetProtocol::TCP.into(),
+        );
+        assert!(!rule.applies(&headers));
+
+        headers.ports = Some((40000, 22));
+        headers.tcp_flags = Some(TcpFlags::SYN);
+        assert!(rule.applies(&headers));
+
+        headers.tcp_flags = Some(TcpFlags::SYN | TcpFlags::ACK);
+        assert!(!rule.applies(&headers));
+
+        headers.tcp_flags = Some(TcpFlags::SYN);
+        headers.source = IpAddr::from([10, 1, 2, 3]);
+        assert!(!rule.applies(&headers));
+    }
+
+    #[test]
+    fn ipv6() {
+        let address = IpAddr::from_str("2001:db8::5").unwrap();
+        let rule = Rule {
+            id: 0,
+            action: Action::Drop,
+            matches: Match::Match(address),
+            applies_to: Direction::Destination,
+            enabled: true,
+            init: true,
+            conditions: Conditions::default(),
+        };
+
+        let mut headers = Headers::new(
+            IpAddr::from_str("fe80::1").unwrap(),
+            address,
+            InetProtocol::UDP.into(),
+        );
+        headers.ports = Some((5353, 53));
+        assert!(rule.applies(&headers));
+        assert_eq!(
+            headers.socket(Direction::Destination),
+            SocketAddr::new(address, 53)
+        );
+
+        headers.destination = IpAddr::from([10, 0, 0, 5]);
+        assert!(!rule.applies(&headers));
+    }
+
+    #[test]
+    fn unassigned_protocol() {
+        let rule = |matches| Rule {
+            id: 0,
+            action: Action::Drop,
+            matches,
+            applies_to: Direction::Source,
+            enabled: true,
+            init: true,
+            conditions: Conditions::default(),
+        };
+        let headers = Headers::new(
+            IpAddr::from([198, 51, 100, 7]),
+            IpAddr::from([10, 0, 0, 5]),
+            200,
+        );
+
+        assert!(headers.protocol().is_err());
+        assert!(!rule(Match::Protocol(InetProtocol::TCP)).applies(&headers));
+        assert!(rule(Match::Any).applies(&headers));
+    }
+
+    #[test]
+    fn state() {
+        let rule = Rule {
+            id: 0,
+            action: Action::Accept,
+            matches: Match::State(ConnState::Established),
+            applies_to: Direction::Source,
+            enabled: true,
+            init: true,
+            conditions: Conditions::default(),
+        };
+        assert!(rule.needs_transport());
+
+        let mut headers = Headers::new(
+            IpAddr::from([198, 51, 100, 7]),
+            IpAddr::from([10, 0, 0, 5]),
+            InetProtocol::UDP.into(),
+        );
+        assert!(!rule.applies(&headers));
+
+        headers.state = Some(ConnState::New);
+        assert!(!rule.applies(&headers));
+
+        headers.state = Some(ConnState::Established);
+        assert!(rule.applies(&headers));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn stored_before_conditions() {
+        use crate::RuleV0;
+
+        // Written into a zeroed buffer of the size the rule had then
+        let row = |matches| {
+            let rule = RuleV0 {
+                id: 3,
+                action: Action::Drop,
+                matches,
+                applies_to: Direction::Source,
+                enabled: true,
+                init: true,
+            };
+            let mut buffer = [0u8; 40];
+            bincode::serialize_into(&mut buffer[..], &rule).unwrap();
+            buffer
+        };
+
+        let rule: Rule = bincode::deserialize_from(&row(Match::Port(22))[..]).unwrap();
+        assert!(rule.matches.matches_port(22) && rule.conditions.is_empty());
+
+        let row = row(Match::Socket("[2001:db8::1]:22".parse().unwrap()));
+        assert!(bincode::deserialize_from::<_, Rule>(&row[..]).is_err());
+
+        let rule = Rule::from(bincode::deserialize_from::<_, RuleV0>(&row[..]).unwrap());
+        assert_eq!(rule.id, 3);
+        assert!(rule.enabled && rule.init && rule.conditions.is_empty());
+        assert!(matches!(rule.matches, Match::Socket(socket) if socket.port() == 22));
+    }
+
+    #[cfg(feature = "schema")]
+    #[test]
+    fn schema() {
+        let schema = serde_json::to_string(&schemars::schema_for!(crate::Rule)).unwrap();
+        assert!(schema.contains("port_range"));
+        assert!(schema.contains("ephemeral"));
+
+        let json = r#"{"port_range":{"start":6000,"end":6063}}"#;
+        let matches: Match = serde_json::from_str(json).unwrap();
+        assert!(matches.matches_port(6010));
+
+        let json = r#"{
+            "action": "drop",
+            "matches": "any",
+            "applies_to": "source",
+            "conditions": { "source": { "network": "203.0.113.0/24" } }
+        }"#;
+        let rule: Rule = serde_json::from_str(json).unwrap();
+        assert!(!rule.conditions.is_empty());
+        assert!(!rule.needs_transport());
+    }
+
+    #[test]
+    fn established_overrides_network() {
+        let rule = |action, matches, applies_to| Rule {
+            id: 0,
+            action,
+            matches,
+            applies_to,
+            enabled: true,
+            init: true,
+            conditions: Conditions::default(),
+        };
+        let outside = IpNetwork::from_str("0.0.0.0/0").unwrap();
+        let rules = [
+            rule(Action::Drop, Match::Network(outside), Direction::Source),
+            rule(Action::Accept, Match::Port(22), Direction::Destination),
+            rule(
+                Action::Accept,
+                Match::State(ConnState::Established),
+                Direction::Source,
+            ),
+        ];
+        let network = |direction, address| {
+            (direction == Direction::Source && outside.contains(address)).then_some(0)
+        };
+
+        let mut headers = Headers::new(
+            IpAddr::from([198, 51, 100, 7]),
+            IpAddr::from([10, 0, 0, 5]),
+            InetProtocol::TCP.into(),
+        );
+        headers.ports = Some((40000, 22));
+
+        headers.state = Some(ConnState::Established);
+        let found = evaluate(&headers, |i| rules.get(i as usize), network);
+        assert!(matches!(
+            found,
+            Some((
+                2,
+                Rule {
+                    action: Action::Accept,
+                    ..
+                }
+            ))
+        ));
+
+        // The network drop still comes before the port rule
+        headers.state = Some(ConnState::New);
+        let found = evaluate(&headers, |i| rules.get(i as usize), network);
+        assert!(matches!(
+            found,
+            Some((
+                0,
+                Rule {
+                    action: Action::Drop,
+                    ..
+                }
+            ))
+        ));
+    }
+}
@@ -2,23 +2,36 @@
 #![no_main]
 #![feature(let_chains)]
 
-use core::net::{IpAddr, Ipv4Addr, SocketAddr};
+use core::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 
 use aya_ebpf::{
-    bindings::xdp_action,
-    macros::{map, xdp},
-    maps::{Array, ProgramArray, RingBuf},
-    programs::XdpContext,
+    bindings::{xdp_action, TC_ACT_OK},
+    helpers::bpf_ktime_get_ns,
+    macros::{classifier, map, xdp},
+    maps::{lpm_trie::Key, Array, LpmTrie, LruHashMap, ProgramArray, RingBuf},
+    programs::{TcContext, XdpContext},
 };
 use aya_log_ebpf::error;
 
-use firewall_common::{processor, Action, Direction, Event, Match, Rule, MAX_RULES};
+use firewall_common::{
+    conntrack::{self, Conn, ConnState, MAX_CONNECTIONS},
+    processor, Action, Direction, Event, Headers, Rule, MAX_RULES,
+};
 use netp::{
     aya::XdpErr,
-    bounds,
+    bbounds, bounds,
+    flow::FlowKey,
     link::{EtherType, Ethernet},
-    network::IPv4,
-    transport::tcp::Tcp,
+    network::{
+        ipv6::{ExtensionHeader, MAX_EXTENSION_HEADERS},
+        IPv4, IPv6, InetProtocol, LpmKey,
+    },
+    transport::{
+        icmp::{Icmp, IcmpType},
+        icmpv6::{Icmpv6, Icmpv6Type},
+        tcp::{self, Tcp},
+        udp::Udp,
+    },
 };
 
 #[map]
@@ -30,6 +43,17 @@ static FIREWALL_EVENTS: RingBuf = RingBuf::with_byte_size(4096, 0);
 #[map]
 static FIREWALL_RULES: Array<Rule> = Array::with_max_entries(MAX_RULES, 0);
 
+/// Index of the `Match::Network` rule for each network, keyed as [`LpmKey`]
+#[map]
+static FIREWALL_SOURCE_NETWORKS: LpmTrie<[u8; 17], u32> = LpmTrie::with_max_entries(MAX_RULES, 0);
+
+#[map]
+static FIREWALL_DESTINATION_NETWORKS: LpmTrie<[u8; 17], u32> =
+    LpmTrie::with_max_entries(MAX_RULES, 0);
+
+#[map]
+static CONNTRACK: LruHashMap<FlowKey, Conn> = LruHashMap::with_max_entries(MAX_CONNECTIONS, 0);
+
 #[xdp]
 pub fn firewall(ctx: XdpContext) -> u32 {
     match try_firewall(ctx) {
@@ -46,49 +70,58 @@ fn try_firewall(ctx: XdpContext) -> Result<u32, u32> {
     bounds!(ctx, 38).or_pass()?;
     let (eth, rem) = Ethernet::new(packet).or_pass()?;
 
-    // TODO: Impl this
-    // if let EtherType::IPv6 = eth.ethertype() {}
+    match eth.ethertype() {
+        EtherType::IPv4 => {
+            let (ip4, _): (IPv4<&[u8]>, &[u8]) = IPv4::new(rem).or_drop()?;
 
-    if let EtherType::IPv4 = eth.ethertype() {
-        let (ip4, _): (IPv4<&[u8]>, &[u8]) = IPv4::new(rem).or_drop()?;
+            let headers = Headers::new(
+                IpAddr::V4(Ipv4Addr::from_bits(ip4.source_u32())),
+                IpAddr::V4(Ipv4Addr::from_bits(ip4.destination_u32())),
+                ip4.protocol_u8(),
+            );
 
-        for i in 0..MAX_RULES {
-            let Some(
-                rule @ Rule {
-                    init: true,
-                    enabled: true,
-                    ..
-                },
-            ) = FIREWALL_RULES.get(i)
-            else {
-                continue;
-            };
-
-            // info!(&ctx, "try_firewall: Verifying rule on {}", rule.id);
+            if ip4.fragment_offset_u16() != 0 {
+                return check_fragment(ctx, headers);
+            }
 
-            let matching_ip = if rule.applies_to == Direction::Source {
-                ip4.source_u32()
-            } else {
-                ip4.destination_u32()
+            let processor = match headers.protocol() {
+                Ok(InetProtocol::TCP) => processor::IPV4_TCP,
+                Ok(InetProtocol::UDP) => processor::IPV4_UDP,
+                Ok(InetProtocol::ICMP) => processor::IPV4_ICMP,
+                _ => return check_transport(ctx, &headers, None),
             };
-            let socket_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::from_bits(matching_ip)), 0);
-
-            if let Match::Protocol(p) = rule.matches {
-                let protocol = ip4.protocol().or_drop()?;
 
-                if protocol == p {
-                    return emit(ctx, rule.action, Some((i, socket_addr)));
-                }
+            unsafe { PROCESSOR.tail_call(&ctx, processor).or_drop()? };
+        }
+        EtherType::IPv6 => {
+            bounds!(ctx, eth.size_usize() + IPv6::MIN_LEN).or_drop()?;
+            let (ip6, payload): (IPv6<&[u8]>, &[u8]) = IPv6::new(rem).or_drop()?;
+            let (protocol, offset, _) = upper_layer(&ip6, payload)?;
+
+            let headers = Headers::new(
+                IpAddr::V6(Ipv6Addr::from(*ip6.source())),
+                IpAddr::V6(Ipv6Addr::from(*ip6.destination())),
+                protocol,
+            );
+
+            // Neighbor discovery is let through before any rule gets a chance to drop it
+            if offset.is_some() && headers.protocol() == Ok(InetProtocol::IPV6_ICMP) {
+                unsafe { PROCESSOR.tail_call(&ctx, processor::IPV6_ICMP).or_drop()? };
             }
 
-            if let Match::Match(core::net::IpAddr::V4(addr)) = rule.matches {
-                if addr.to_bits() == matching_ip {
-                    return emit(ctx, rule.action, Some((i, socket_addr)));
-                }
+            if offset.is_none() {
+                return check_fragment(ctx, headers);
             }
-        }
 
-        unsafe { PROCESSOR.tail_call(&ctx, processor::IPV4_TCP).or_drop()? };
+            let processor = match headers.protocol() {
+                Ok(InetProtocol::TCP) => processor::IPV6_TCP,
+                Ok(InetProtocol::UDP) => processor::IPV6_UDP,
+                _ => return check_transport(ctx, &headers, None),
+            };
+
+            unsafe { PROCESSOR.tail_call(&ctx, processor).or_drop()? };
+        }
+        _ => {}
     }
 
     emit(ctx, Action::Accept, None)
@@ -112,6 +145,16 @@ fn try_ipv4_tcp(ctx: XdpContext) -> Result<u32, u32> {
     let (eth, rem) = Ethernet::new(packet).or_pass()?;
     let (ip4, rem) = IPv4::new(rem).or_drop()?;
 
+    let len = (ip4.total_length() as usize).saturating_sub(ip4.size_usize());
+    if ip4.more_fragments() && short_tcp(&ctx, eth.size_usize() + ip4.size_usize(), rem, len) {
+        let headers = Headers::new(
+            IpAddr::V4(Ipv4Addr::from_bits(ip4.source_u32())),
+            IpAddr::V4(Ipv4Addr::from_bits(ip4.destination_u32())),
+            InetProtocol::TCP.into(),
+        );
+        return check_fragment(ctx, headers);
+    }
+
     bounds!(ctx, eth.size_usize() + ip4.size_usize() + Tcp::MIN_LEN).or_drop()?;
     let (tcp, _) = Tcp::new(rem).or_drop()?;
 
@@ -124,37 +167,623 @@ fn try_ipv4_tcp(ctx: XdpContext) -> Result<u32, u32> {
     bounds!(ctx, eth.size_usize() + ip4.size_usize() + Tcp::MIN_LEN).or_drop()?;
     let source = tcp.source();
     let dest = tcp.destination();
+    let flags = tcp.flags();
+
+    let headers = Headers {
+        ports: Some((source, dest)),
+        tcp_flags: Some(flags),
+        ..Headers::new(
+            IpAddr::V4(Ipv4Addr::from_bits(source_ip)),
+            IpAddr::V4(Ipv4Addr::from_bits(dest_ip)),
+            InetProtocol::TCP.into(),
+        )
+    };
+
+    check_flow(ctx, headers)
+}
+
+#[xdp]
+pub fn ipv4_udp(ctx: XdpContext) -> u32 {
+    match try_ipv4_udp(ctx) {
+        Ok(c) => c,
+        Err(c) => c,
+    }
+}
+
+/// This must be called only when IPV4 + Udp
+fn try_ipv4_udp(ctx: XdpContext) -> Result<u32, u32> {
+    let packet = unsafe {
+        core::slice::from_raw_parts_mut(ctx.data() as *mut u8, ctx.data_end() - ctx.data())
+    };
+
+    bounds!(ctx, 38).or_pass()?;
+    let (eth, rem) = Ethernet::new(packet).or_pass()?;
+    let (ip4, rem) = IPv4::new(rem).or_drop()?;
+
+    bounds!(ctx, eth.size_usize() + ip4.size_usize() + Udp::MIN_LEN).or_drop()?;
+    // The length field of a fragmented datagram goes past the end of the first fragment
+    let (udp, _) = if ip4.more_fragments() {
+        Udp::new_fragment(rem).or_drop()?
+    } else {
+        Udp::new(rem).or_drop()?
+    };
+
+    // Avoid branching as much as possible
+    bounds!(ctx, Ethernet::MIN_LEN + IPv4::MIN_LEN + Udp::MIN_LEN).or_drop()?;
+    let source_ip = ip4.source_u32();
+    let dest_ip = ip4.destination_u32();
+
+    // Avoid branching as much as possible
+    bounds!(ctx, eth.size_usize() + ip4.size_usize() + Udp::MIN_LEN).or_drop()?;
+    let headers = Headers {
+        ports: Some((udp.source(), udp.destination())),
+        ..Headers::new(
+            IpAddr::V4(Ipv4Addr::from_bits(source_ip)),
+            IpAddr::V4(Ipv4Addr::from_bits(dest_ip)),
+            InetProtocol::UDP.into(),
+        )
+    };
+
+    check_flow(ctx, headers)
+}
+
+#[xdp]
+pub fn ipv4_icmp(ctx: XdpContext) -> u32 {
+    match try_ipv4_icmp(ctx) {
+        Ok(c) => c,
+        Err(c) => c,
+    }
+}
+
+/// This must be called only when IPV4 + Icmp
+fn try_ipv4_icmp(ctx: XdpContext) -> Result<u32, u32> {
+    let packet = unsafe {
+        core::slice::from_raw_parts_mut(ctx.data() as *mut u8, ctx.data_end() - ctx.data())
+    };
+
+    bounds!(ctx, 38).or_pass()?;
+    let (eth, rem) = Ethernet::new(packet).or_pass()?;
+    let (ip4, rem) = IPv4::new(rem).or_drop()?;
 
-    for i in 0..MAX_RULES {
-        let Some(
-            rule @ Rule {
-                init: true,
-                enabled: true,
+    bounds!(ctx, eth.size_usize() + ip4.size_usize() + Icmp::MIN_LEN).or_drop()?;
+    let (icmp, rem) = Icmp::new(rem).or_drop()?;
+
+    let headers = Headers::new(
+        IpAddr::V4(Ipv4Addr::from_bits(ip4.source_u32())),
+        IpAddr::V4(Ipv4Addr::from_bits(ip4.destination_u32())),
+        InetProtocol::ICMP.into(),
+    );
+
+    let id = icmp.identifier();
+    let key = FlowKey::new(
+        headers.source,
+        headers.destination,
+        id,
+        id,
+        InetProtocol::ICMP,
+    );
+
+    let (state, tracked) = match icmp.icmp_type() {
+        IcmpType::EchoRequest => track(&headers, key, true),
+        IcmpType::EchoReply => track(&headers, key, false),
+        IcmpType::DestinationUnreachable | IcmpType::TimeExceeded | IcmpType::ParameterProblem => {
+            let offset = eth.size_usize() + ip4.size_usize() + Icmp::MIN_LEN;
+            (related(quoted_ipv4(&ctx, offset, rem)), None)
+        }
+        _ => return check_transport(ctx, &headers, None),
+    };
+
+    let headers = Headers {
+        state: Some(state),
+        ..headers
+    };
+    check_transport(ctx, &headers, tracked)
+}
+
+#[xdp]
+pub fn ipv6_tcp(ctx: XdpContext) -> u32 {
+    match try_ipv6_tcp(ctx) {
+        Ok(c) => c,
+        Err(c) => c,
+    }
+}
+
+/// This must be called only when IPV6 + Tcp
+fn try_ipv6_tcp(ctx: XdpContext) -> Result<u32, u32> {
+    let packet = unsafe {
+        core::slice::from_raw_parts_mut(ctx.data() as *mut u8, ctx.data_end() - ctx.data())
+    };
+
+    bounds!(ctx, Ethernet::MIN_LEN + IPv6::MIN_LEN).or_pass()?;
+    let (eth, rem) = Ethernet::new(packet).or_pass()?;
+
+    bounds!(ctx, eth.size_usize() + IPv6::MIN_LEN).or_drop()?;
+    let (ip6, payload) = IPv6::new(rem).or_drop()?;
+    let (_, offset, more_fragments) = upper_layer(&ip6, payload)?;
+    let offset = offset.ok_or(xdp_action::XDP_DROP)?;
+
+    let transport = eth.size_usize() + IPv6::MIN_LEN + offset;
+    let len = (ip6.payload_length() as usize).saturating_sub(offset);
+    if more_fragments && short_tcp(&ctx, transport, &payload[offset..], len) {
+        let headers = Headers::new(
+            IpAddr::V6(Ipv6Addr::from(*ip6.source())),
+            IpAddr::V6(Ipv6Addr::from(*ip6.destination())),
+            InetProtocol::TCP.into(),
+        );
+        return check_fragment(ctx, headers);
+    }
+
+    bounds!(ctx, transport + Tcp::MIN_LEN).or_drop()?;
+    let (tcp, _) = Tcp::new(&payload[offset..]).or_drop()?;
+
+    let headers = Headers {
+        ports: Some((tcp.source(), tcp.destination())),
+        tcp_flags: Some(tcp.flags()),
+        ..Headers::new(
+            IpAddr::V6(Ipv6Addr::from(*ip6.source())),
+            IpAddr::V6(Ipv6Addr::from(*ip6.destination())),
+            InetProtocol::TCP.into(),
+        )
+    };
+
+    check_flow(ctx, headers)
+}
+
+#[xdp]
+pub fn ipv6_udp(ctx: XdpContext) -> u32 {
+    match try_ipv6_udp(ctx) {
+        Ok(c) => c,
+        Err(c) => c,
+    }
+}
+
+/// This must be called only when IPV6 + Udp
+fn try_ipv6_udp(ctx: XdpContext) -> Result<u32, u32> {
+    let packet = unsafe {
+        core::slice::from_raw_parts_mut(ctx.data() as *mut u8, ctx.data_end() - ctx.data())
+    };
+
+    bounds!(ctx, Ethernet::MIN_LEN + IPv6::MIN_LEN).or_pass()?;
+    let (eth, rem) = Ethernet::new(packet).or_pass()?;
+
+    bounds!(ctx, eth.size_usize() + IPv6::MIN_LEN).or_drop()?;
+    let (ip6, payload) = IPv6::new(rem).or_drop()?;
+    let (_, offset, more_fragments) = upper_layer(&ip6, payload)?;
+    let offset = offset.ok_or(xdp_action::XDP_DROP)?;
+
+    bounds!(
+        ctx,
+        eth.size_usize() + IPv6::MIN_LEN + offset + Udp::MIN_LEN
+    )
+    .or_drop()?;
+    // The length field of a fragmented datagram goes past the end of the first fragment
+    let (udp, _) = if more_fragments {
+        Udp::new_fragment(&payload[offset..]).or_drop()?
+    } else {
+        Udp::new(&payload[offset..]).or_drop()?
+    };
+
+    let headers = Headers {
+        ports: Some((udp.source(), udp.destination())),
+        ..Headers::new(
+            IpAddr::V6(Ipv6Addr::from(*ip6.source())),
+            IpAddr::V6(Ipv6Addr::from(*ip6.destination())),
+            InetProtocol::UDP.into(),
+        )
+    };
+
+    check_flow(ctx, headers)
+}
+
+#[xdp]
+pub fn ipv6_icmp(ctx: XdpContext) -> u32 {
+    match try_ipv6_icmp(ctx) {
+        Ok(c) => c,
+        Err(c) => c,
+    }
+}
+
+/// This must be called only when IPV6 + Icmpv6, before any rule is evaluated
+fn try_ipv6_icmp(ctx: XdpContext) -> Result<u32, u32> {
+    let packet = unsafe {
+        core::slice::from_raw_parts_mut(ctx.data() as *mut u8, ctx.data_end() - ctx.data())
+    };
+
+    bounds!(ctx, Ethernet::MIN_LEN + IPv6::MIN_LEN).or_pass()?;
+    let (eth, rem) = Ethernet::new(packet).or_pass()?;
+
+    bounds!(ctx, eth.size_usize() + IPv6::MIN_LEN).or_drop()?;
+    let (ip6, payload) = IPv6::new(rem).or_drop()?;
+    let (_, offset, _) = upper_layer(&ip6, payload)?;
+    let offset = offset.ok_or(xdp_action::XDP_DROP)?;
+
+    let icmp_offset = eth.size_usize() + IPv6::MIN_LEN + offset;
+    bounds!(ctx, icmp_offset + Icmpv6::MIN_LEN).or_drop()?;
+    let (icmp, rem) = Icmpv6::new(&payload[offset..]).or_drop()?;
+
+    // IPv6 stops working without neighbor discovery, no rule may drop it
+    if matches!(
+        icmp.icmp_type(),
+        Icmpv6Type::RouterSolicitation
+            | Icmpv6Type::RouterAdvertisement
+            | Icmpv6Type::NeighborSolicitation
+            | Icmpv6Type::NeighborAdvertisement
+    ) {
+        return emit(ctx, Action::Accept, None);
+    }
+
+    let headers = Headers::new(
+        IpAddr::V6(Ipv6Addr::from(*ip6.source())),
+        IpAddr::V6(Ipv6Addr::from(*ip6.destination())),
+        InetProtocol::IPV6_ICMP.into(),
+    );
+
+    let id = icmp.identifier();
+    let key = FlowKey::new(
+        headers.source,
+        headers.destination,
+        id,
+        id,
+        InetProtocol::IPV6_ICMP,
+    );
+
+    let (state, tracked) = match icmp.icmp_type() {
+        Icmpv6Type::EchoRequest => track(&headers, key, true),
+        Icmpv6Type::EchoReply => track(&headers, key, false),
+        Icmpv6Type::DestinationUnreachable
+        | Icmpv6Type::PacketTooBig
+        | Icmpv6Type::TimeExceeded
+        | Icmpv6Type::ParameterProblem => {
+            let offset = icmp_offset + Icmpv6::MIN_LEN;
+            (related(quoted_ipv6(&ctx, offset, rem)), None)
+        }
+        _ => return check_transport(ctx, &headers, None),
+    };
+
+    let headers = Headers {
+        state: Some(state),
+        ..headers
+    };
+    check_transport(ctx, &headers, tracked)
+}
+
+#[classifier]
+pub fn egress(ctx: TcContext) -> i32 {
+    // Egress is never filtered, packets which cannot be tracked are let through as well
+    try_egress(&ctx).ok();
+    TC_ACT_OK
+}
+
+/// Tracks the packets sent by the host, so the XDP programs know the connections it opened when
+/// the replies come in
+fn try_egress(ctx: &TcContext) -> Result<(), u32> {
+    let packet = unsafe {
+        core::slice::from_raw_parts(ctx.data() as *const u8, ctx.data_end() - ctx.data())
+    };
+
+    bounds!(ctx, Ethernet::MIN_LEN).or_pass()?;
+    let (eth, rem) = Ethernet::new(packet).or_pass()?;
+    let offset = eth.size_usize();
+
+    let (headers, transport, offset) = match eth.ethertype() {
+        EtherType::IPv4 => {
+            bounds!(ctx, offset + IPv4::MIN_LEN).or_pass()?;
+            let (ip4, rem) = IPv4::new(rem).or_pass()?;
+
+            if ip4.fragment_offset_u16() != 0 {
+                return Ok(());
+            }
+
+            let headers = Headers::new(
+                IpAddr::V4(Ipv4Addr::from_bits(ip4.source_u32())),
+                IpAddr::V4(Ipv4Addr::from_bits(ip4.destination_u32())),
+                ip4.protocol_u8(),
+            );
+            (headers, rem, offset + ip4.size_usize())
+        }
+        EtherType::IPv6 => {
+            bounds!(ctx, offset + IPv6::MIN_LEN).or_pass()?;
+            let (ip6, payload) = IPv6::new(rem).or_pass()?;
+            let (protocol, extensions, _) = upper_layer(&ip6, payload)?;
+            let Some(extensions) = extensions else {
+                return Ok(());
+            };
+
+            let headers = Headers::new(
+                IpAddr::V6(Ipv6Addr::from(*ip6.source())),
+                IpAddr::V6(Ipv6Addr::from(*ip6.destination())),
+                protocol,
+            );
+            (
+                headers,
+                &payload[extensions..],
+                offset + IPv6::MIN_LEN + extensions,
+            )
+        }
+        _ => return Ok(()),
+    };
+
+    // Like on ingress, ICMP echo is keyed by its identifier and only requests open connections
+    let protocol = headers.protocol().or_pass()?;
+    let ((source_port, destination_port), tcp_flags, may_open) = match protocol {
+        InetProtocol::TCP => {
+            bounds!(ctx, offset + Tcp::MIN_LEN).or_pass()?;
+            let (tcp, _) = Tcp::new(transport).or_pass()?;
+            ((tcp.source(), tcp.destination()), Some(tcp.flags()), true)
+        }
+        InetProtocol::UDP => {
+            bounds!(ctx, offset + Udp::MIN_LEN).or_pass()?;
+            // Only the ports are needed, first fragments are tracked as well
+            let (udp, _) = Udp::new_fragment(transport).or_pass()?;
+            ((udp.source(), udp.destination()), None, true)
+        }
+        InetProtocol::ICMP => {
+            bounds!(ctx, offset + Icmp::MIN_LEN).or_pass()?;
+            let (icmp, _) = Icmp::new(transport).or_pass()?;
+            let id = icmp.identifier();
+
+            match icmp.icmp_type() {
+                IcmpType::EchoRequest => ((id, id), None, true),
+                IcmpType::EchoReply => ((id, id), None, false),
+                _ => return Ok(()),
+            }
+        }
+        InetProtocol::IPV6_ICMP => {
+            bounds!(ctx, offset + Icmpv6::MIN_LEN).or_pass()?;
+            let (icmp, _) = Icmpv6::new(transport).or_pass()?;
+            let id = icmp.identifier();
+
+            match icmp.icmp_type() {
+                Icmpv6Type::EchoRequest => ((id, id), None, true),
+                Icmpv6Type::EchoReply => ((id, id), None, false),
+                _ => return Ok(()),
+            }
+        }
+        _ => return Ok(()),
+    };
+
+    let headers = Headers {
+        tcp_flags,
+        ..headers
+    };
+    let key = FlowKey::new(
+        headers.source,
+        headers.destination,
+        source_port,
+        destination_port,
+        protocol,
+    );
+
+    if let (_, Some((key, conn))) = track(&headers, key, may_open) {
+        CONNTRACK.insert(&key, &conn, 0).ok();
+    }
+
+    Ok(())
+}
+
+/// Upper-layer protocol number of an IPv6 packet with the offset of its header in `payload`, and
+/// whether more fragments follow. Non-first fragments have no offset, their protocol is the one of
+/// the fragment header. Chains longer than `MAX_EXTENSION_HEADERS` or truncated are dropped
+fn upper_layer(ip6: &IPv6<&[u8]>, payload: &[u8]) -> Result<(u8, Option<usize>, bool), u32> {
+    let mut extensions = ip6.extensions(payload);
+    let mut more_fragments = false;
+
+    for _ in 0..=MAX_EXTENSION_HEADERS {
+        match extensions.next() {
+            Some(Ok(ext)) if ext.kind() == ExtensionHeader::Fragment => {
+                more_fragments = ext.more_fragments();
+
+                if ext.fragment_offset() != 0 {
+                    return Ok((ext.next_header_u8(), None, more_fragments));
+                }
+            }
+            Some(Ok(_)) => {}
+            Some(Err(_)) => return Err(xdp_action::XDP_DROP),
+            None => break,
+        }
+    }
+
+    Ok((
+        extensions.next_header_u8(),
+        Some(extensions.offset()),
+        more_fragments,
+    ))
+}
+
+/// Tracks a TCP or UDP packet, then evaluates the rules
+fn check_flow(ctx: XdpContext, headers: Headers) -> Result<u32, u32> {
+    let (source_port, destination_port) = headers.ports.unwrap_or_default();
+    let protocol = headers.protocol().or_drop()?;
+    let key = FlowKey::new(
+        headers.source,
+        headers.destination,
+        source_port,
+        destination_port,
+        protocol,
+    );
+    let (state, tracked) = track(&headers, key, true);
+
+    let headers = Headers {
+        state: Some(state),
+        ..headers
+    };
+    check_transport(ctx, &headers, tracked)
+}
+
+/// Whether a first fragment ends before its TCP header. `len` is the length of the fragment from
+/// the TCP header on, which Ethernet padding may hide, and `offset` where `transport` starts in
+/// the frame. Those are evaluated like the fragments following them
+fn short_tcp(ctx: &XdpContext, offset: usize, transport: &[u8], len: usize) -> bool {
+    if len < Tcp::MIN_LEN || !bbounds!(ctx, offset + Tcp::MIN_LEN) {
+        return true;
+    }
+
+    match Tcp::new(transport) {
+        Ok((tcp, _)) => len < tcp.size_usize(),
+        Err(e) => matches!(e, tcp::Error::InvalidSizeForOffset(..)),
+    }
+}
+
+/// Non-first fragments, and first ones cut before their TCP header, have no transport header.
+/// They are `Invalid` and only match rules without ports
+fn check_fragment(ctx: XdpContext, headers: Headers) -> Result<u32, u32> {
+    let headers = Headers {
+        state: Some(ConnState::Invalid),
+        ..headers
+    };
+    check_transport(ctx, &headers, None)
+}
+
+/// Evaluates every rule once the connection state is known. The connection entry is only stored
+/// for accepted packets
+fn check_transport(
+    ctx: XdpContext,
+    headers: &Headers,
+    tracked: Option<(FlowKey, Conn)>,
+) -> Result<u32, u32> {
+    let rule = find_rule(headers);
+
+    if let (
+        None
+        | Some((
+            _,
+            Rule {
+                action: Action::Accept,
                 ..
             },
-        ) = FIREWALL_RULES.get(i)
-        else {
-            continue;
-        };
+        )),
+        Some((key, conn)),
+    ) = (rule, tracked)
+    {
+        CONNTRACK.insert(&key, &conn, 0).ok();
+    }
 
-        // info!(&ctx, "try_ipv4_tcp: Verifying rule on {}", rule.id);
+    match rule {
+        Some((i, rule)) => emit(ctx, rule.action, Some((i, headers.socket(rule.applies_to)))),
+        None => emit(ctx, Action::Accept, None),
+    }
+}
 
-        let (ip, port) = if rule.applies_to == Direction::Source {
-            (source_ip, source)
-        } else {
-            (dest_ip, dest)
-        };
+/// Connection state of a packet, with the updated entry to store if it gets accepted. Only
+/// packets with `may_open` start new connections
+fn track(headers: &Headers, key: FlowKey, may_open: bool) -> (ConnState, Option<(FlowKey, Conn)>) {
+    let now = unsafe { bpf_ktime_get_ns() };
 
-        let socket_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::from_bits(ip)), port);
+    conntrack::track(headers, key, may_open, now, |key| unsafe {
+        CONNTRACK.get(key).copied()
+    })
+}
 
-        if let Match::Port(rule_port) = rule.matches {
-            if rule_port == port {
-                return emit(ctx, rule.action, Some((i, socket_addr)));
-            }
+/// State of an ICMP error quoting a packet of `key`, `Invalid` when too little was quoted to know
+/// the connection
+fn related(key: Option<FlowKey>) -> ConnState {
+    let now = unsafe { bpf_ktime_get_ns() };
+    let Some(key) = key else {
+        return ConnState::Invalid;
+    };
+
+    match lookup(&key, now).or_else(|| lookup(&key.reversed(), now)) {
+        Some(_) => ConnState::Related,
+        None => ConnState::Invalid,
+    }
+}
+
+/// Key of the IPv4 packet quoted by an ICMP error, `offset` being where `quoted` starts in the
+/// frame
+fn quoted_ipv4(ctx: &XdpContext, offset: usize, quoted: &[u8]) -> Option<FlowKey> {
+    bounds!(ctx, offset + IPv4::MIN_LEN).ok()?;
+    let (inner, rem) = IPv4::new(quoted).ok()?;
+
+    if inner.fragment_offset_u16() != 0 {
+        return None;
+    }
+
+    let protocol = inner.protocol().ok()?;
+    let (source_port, destination_port) =
+        quoted_ports(ctx, offset + inner.size_usize(), protocol, rem)?;
+
+    Some(FlowKey::new_v4(
+        *inner.source(),
+        *inner.destination(),
+        source_port,
+        destination_port,
+        protocol,
+    ))
+}
+
+/// Key of the IPv6 packet quoted by an ICMPv6 error, `offset` being where `quoted` starts in the
+/// frame
+fn quoted_ipv6(ctx: &XdpContext, offset: usize, quoted: &[u8]) -> Option<FlowKey> {
+    bounds!(ctx, offset + IPv6::MIN_LEN).ok()?;
+    let (inner, rem) = IPv6::new(quoted).ok()?;
+    let (protocol, extensions) = inner.extensions(rem).upper_layer().ok()?;
+    let transport = rem.get(extensions..)?;
+    let (source_port, destination_port) = quoted_ports(
+        ctx,
+        offset + IPv6::MIN_LEN + extensions,
+        protocol,
+        transport,
+    )?;
+
+    Some(FlowKey::new_v6(
+        *inner.source(),
+        *inner.destination(),
+        source_port,
+        destination_port,
+        protocol,
+    ))
+}
+
+/// Ports of a quoted transport header as tracked. Errors only have to quote 8 bytes of it, so the
+/// header is not parsed: TCP and UDP start with the ports, ICMP echo has the identifier after the
+/// checksum
+fn quoted_ports(
+    ctx: &XdpContext,
+    offset: usize,
+    protocol: InetProtocol,
+    transport: &[u8],
+) -> Option<(u16, u16)> {
+    match protocol {
+        InetProtocol::TCP | InetProtocol::UDP => {
+            bounds!(ctx, offset + 4).ok()?;
+            let ports = transport.first_chunk::<4>()?;
+
+            Some((
+                u16::from_be_bytes([ports[0], ports[1]]),
+                u16::from_be_bytes([ports[2], ports[3]]),
+            ))
+        }
+        InetProtocol::ICMP | InetProtocol::IPV6_ICMP => {
+            bounds!(ctx, offset + 6).ok()?;
+            let header = transport.first_chunk::<6>()?;
+            let id = u16::from_be_bytes([header[4], header[5]]);
+
+            Some((id, id))
         }
+        _ => None,
     }
+}
 
-    emit(ctx, Action::Accept, None)
+/// Entry of `key` unless expired
+fn lookup(key: &FlowKey, now: u64) -> Option<Conn> {
+    let conn = unsafe { CONNTRACK.get(key) }?;
+    (!conn.is_expired(now)).then_some(*conn)
+}
+
+/// First enabled rule applying to `headers`, see [`firewall_common::evaluate`]
+fn find_rule(headers: &Headers) -> Option<(u32, &'static Rule)> {
+    firewall_common::evaluate(
+        headers,
+        |i| FIREWALL_RULES.get(i),
+        |direction, addr| {
+            let networks = match direction {
+                Direction::Source => &FIREWALL_SOURCE_NETWORKS,
+                Direction::Destination => &FIREWALL_DESTINATION_NETWORKS,
+            };
+            let key = LpmKey::host(addr);
+            networks.get(&Key::new(key.prefix_len, key.data)).copied()
+        },
+    )
 }
 
 fn emit(ctx: XdpContext, action: Action, socket: Option<(u32, SocketAddr)>) -> Result<u32, u32> {
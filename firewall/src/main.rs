@@ -7,9 +7,11 @@ use std::ops::{ControlFlow, DerefMut};
 use std::sync::Arc;
 
 use anyhow::{Context, Result};
-use aya::maps::{Array, MapData, RingBuf};
+use aya::maps::lpm_trie::{Key, LpmTrie};
+use aya::maps::{Array, HashMap, MapData, RingBuf};
+use aya::programs::tc::SchedClassifierLinkId;
 use aya::programs::xdp::XdpLinkId;
-use aya::programs::{Xdp, XdpFlags};
+use aya::programs::{tc, SchedClassifier, TcAttachType, Xdp, XdpFlags};
 use aya::{include_bytes_aligned, Ebpf};
 use aya_log::EbpfLogger;
 use chrono::NaiveDateTime;
@@ -19,12 +21,17 @@ use diesel_async::async_connection_wrapper::AsyncConnectionWrapper;
 use diesel_async::sync_connection_wrapper::SyncConnectionWrapper;
 use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
 use dotenv::dotenv;
-use firewall_common::{processor, Event, Rule, StoredEventDecoded, StoredRuleDecoded, MAX_RULES};
+use firewall_common::conntrack::{Conn, Connection};
+use firewall_common::{
+    processor, Direction, Event, Match, Rule, RuleV0, StoredEventDecoded, StoredRuleDecoded,
+    MAX_RULES,
+};
 use futures::SinkExt;
 use log::{debug, info, warn};
 use message::async_bincode::tokio::AsyncBincodeStream;
 use message::firewall::*;
 use message::Message;
+use netp::flow::FlowKey;
 use serde::{Deserialize, Serialize};
 use std::io::ErrorKind;
 use tokio::io::unix::{AsyncFd, AsyncFdReadyMutGuard};
@@ -197,9 +204,7 @@ async fn main() -> Result<(), anyhow::Error> {
             register!($name);
             let prog: &Xdp = bpf.program($name).unwrap().try_into().unwrap();
 
-            programs
-                .set(processor::IPV4_TCP, prog.fd().unwrap(), 0)
-                .unwrap();
+            programs.set($at, prog.fd().unwrap(), 0).unwrap();
         }};
     }
 
@@ -211,14 +216,31 @@ async fn main() -> Result<(), anyhow::Error> {
         let mut db = db.lock().await;
 
         let rules: Vec<StoredRule> = rules::table.load(db.deref_mut()).await.unwrap();
-        for rule in rules {
-            let deserialize_from: Rule = bincode::deserialize_from(rule.rule.as_slice()).unwrap();
-            config.set(rule.id as u32, deserialize_from, 0).unwrap();
+        let rules: Vec<Rule> = rules
+            .into_iter()
+            .map(|rule| decode_rule(&rule.rule))
+            .collect();
+
+        for rule in &rules {
+            config.set(rule.id, rule, 0).unwrap();
+        }
+
+        for rule in &rules {
+            set_network(&mut bpf, rule);
         }
     };
 
     register!("firewall");
-    register!("ipv4_tcp", programs::IPV4_TCP);
+    register!("ipv4_tcp", processor::IPV4_TCP);
+    register!("ipv4_udp", processor::IPV4_UDP);
+    register!("ipv6_tcp", processor::IPV6_TCP);
+    register!("ipv6_udp", processor::IPV6_UDP);
+    register!("ipv6_icmp", processor::IPV6_ICMP);
+    register!("ipv4_icmp", processor::IPV4_ICMP);
+
+    info!("Loading egress");
+    let egress: &mut SchedClassifier = bpf.program_mut("egress").unwrap().try_into().unwrap();
+    egress.load()?;
 
     let bpf = Arc::new(Mutex::new(bpf));
     let opt = Arc::new(opt);
@@ -387,7 +409,7 @@ async fn handle_message(
     tx: &mut Sender<State>,
     opt: Arc<Opt>,
     bpf: Arc<Mutex<Ebpf>>,
-    link: Arc<Mutex<Option<XdpLinkId>>>,
+    link: Arc<Mutex<Option<(XdpLinkId, SchedClassifierLinkId)>>>,
     rx: Receiver<State>,
 ) -> Result<ControlFlow<(), Option<Response>>> {
     Ok(match msg {
@@ -414,9 +436,11 @@ async fn handle_message(
                             if let Ok(Rule { init: false, .. }) = config.get(&idx, 0) {
                                 rule.id = idx;
                                 config.set(idx, rule, 0).unwrap();
-                                let mut buffer = [0u8; std::mem::size_of::<Rule>()];
+                                set_network(&mut guard, &rule);
 
-                                bincode::serialize_into(&mut buffer[..], &rule).unwrap();
+                                // Networks serialize as text, so the encoding can be longer
+                                // than the in-memory rule
+                                let buffer = bincode::serialize(&rule).unwrap();
                                 diesel::insert_into(rules::table)
                                     .values(StoredRuleRef {
                                         id: idx as i32,
@@ -445,6 +469,7 @@ async fn handle_message(
                             .unwrap();
 
                         config.set(idx, rule, 0).unwrap();
+                        set_network(&mut guard, &rule);
                     }
                     None
                 }
@@ -496,6 +521,7 @@ async fn handle_message(
                                 .unwrap();
 
                             config.set(idx, rule, 0).unwrap();
+                            set_network(&mut guard, &rule);
 
                             Some(Response::RuleChange(RuleChange::Change(if rule.enabled {
                                 RuleStatus::Active
@@ -600,6 +626,31 @@ async fn handle_message(
 
                     Some(Response::Events(b))
                 }
+                Request::GetConnections => {
+                    let conntrack: HashMap<&mut MapData, FlowKey, Conn> =
+                        HashMap::try_from(guard.map_mut("CONNTRACK").unwrap()).unwrap();
+                    let now = monotonic_now();
+
+                    let connections = conntrack
+                        .iter()
+                        .flatten()
+                        .filter_map(|(key, conn)| Connection::new(&key, &conn, now))
+                        .collect();
+
+                    Some(Response::Connections(connections))
+                }
+                Request::FlushConnections => {
+                    let mut conntrack: HashMap<&mut MapData, FlowKey, Conn> =
+                        HashMap::try_from(guard.map_mut("CONNTRACK").unwrap()).unwrap();
+
+                    let keys = conntrack.keys().flatten().collect::<Vec<_>>();
+                    for key in keys {
+                        // Entries may have been evicted in the meantime
+                        conntrack.remove(&key).ok();
+                    }
+
+                    None
+                }
             })
         }
         Message::Halt => {
@@ -611,10 +662,12 @@ async fn handle_message(
 
             log::warn!("Got halt");
             let mut guard = bpf.lock().await;
-            let program: &mut Xdp = guard.program_mut("firewall").unwrap().try_into()?;
+            let (xdp, egress) = link.take().unwrap();
 
-            let val = link.take();
-            program.detach(val.unwrap()).unwrap();
+            let program: &mut Xdp = guard.program_mut("firewall").unwrap().try_into()?;
+            program.detach(xdp).unwrap();
+            let program: &mut SchedClassifier = guard.program_mut("egress").unwrap().try_into()?;
+            program.detach(egress).unwrap();
             log::warn!("State::Loaded");
             tx.send(State::Loaded).unwrap();
             ControlFlow::Continue(None)
@@ -623,11 +676,14 @@ async fn handle_message(
             let mut link = link.lock().await;
 
             log::warn!("Got terminate");
-            if let Some(val) = link.take() {
+            if let Some((xdp, egress)) = link.take() {
                 let mut guard = bpf.lock().await;
                 let program: &mut Xdp = guard.program_mut("firewall").unwrap().try_into()?;
+                program.detach(xdp).unwrap();
 
-                program.detach(val).unwrap();
+                let program: &mut SchedClassifier =
+                    guard.program_mut("egress").unwrap().try_into()?;
+                program.detach(egress).unwrap();
             }
 
             log::warn!("State::Terminated");
@@ -643,8 +699,27 @@ async fn handle_message(
 
             log::info!("Loading bpf program");
             let mut guard = bpf.lock().await;
+
+            // XDP only sees ingress, the connections opened by this host are tracked on egress.
+            // Adding the clsact qdisc fails when it already exists
+            let _ = tc::qdisc_add_clsact(&opt.iface);
+            let program: &mut SchedClassifier = guard.program_mut("egress").unwrap().try_into()?;
+            let egress = program
+                .attach(&opt.iface, TcAttachType::Egress)
+                .context("failed to attach the egress classifier")?;
+
             let program: &mut Xdp = guard.program_mut("firewall").unwrap().try_into()?;
-            *link = Some(program.attach(&opt.iface,XdpFlags::default()).context("failed to attach the XDP program with default flags - try changing XdpFlags::default() to XdpFlags::SKB_MODE")?);
+            let xdp = match program.attach(&opt.iface, XdpFlags::default()) {
+                Ok(xdp) => xdp,
+                Err(e) => {
+                    let program: &mut SchedClassifier =
+                        guard.program_mut("egress").unwrap().try_into()?;
+                    program.detach(egress).unwrap();
+
+                    return Err(e).context("failed to attach the XDP program with default flags - try changing XdpFlags::default() to XdpFlags::SKB_MODE");
+                }
+            };
+            *link = Some((xdp, egress));
 
             log::warn!("State::Started");
             tx.send(State::Started).unwrap();
@@ -653,13 +728,73 @@ async fn handle_message(
     })
 }
 
+/// Rows stored before `Rule::conditions` existed may be too short for it
+fn decode_rule(bytes: &[u8]) -> Rule {
+    bincode::deserialize_from(bytes)
+        .or_else(|_| bincode::deserialize_from(bytes).map(|rule: RuleV0| rule.into()))
+        .unwrap()
+}
+
+/// `CLOCK_MONOTONIC` in nanoseconds, the clock of `bpf_ktime_get_ns`
+fn monotonic_now() -> u64 {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts) };
+
+    ts.tv_sec as u64 * 1_000_000_000 + ts.tv_nsec as u64
+}
+
+/// Keeps the LPM trie of the rule direction in sync for `Match::Network` rules without extra
+/// conditions, the eBPF program looks these up instead of scanning `FIREWALL_RULES`. Rules sharing
+/// a network and direction leave the first enabled one in the trie, as the scan would pick it
+fn set_network(bpf: &mut Ebpf, rule: &Rule) {
+    let Match::Network(network) = rule.matches else {
+        return;
+    };
+    if !rule.uses_network_trie() {
+        return;
+    }
+
+    let config: Array<&MapData, Rule> =
+        Array::try_from(bpf.map("FIREWALL_RULES").unwrap()).unwrap();
+    let owner = config
+        .iter()
+        .flatten()
+        .filter(|other| {
+            other.init
+                && other.applies_to == rule.applies_to
+                && other.uses_network_trie()
+                && matches!(other.matches, Match::Network(n) if n == network)
+        })
+        .min_by_key(|other| (!other.enabled, other.id));
+
+    let name = match rule.applies_to {
+        Direction::Source => "FIREWALL_SOURCE_NETWORKS",
+        Direction::Destination => "FIREWALL_DESTINATION_NETWORKS",
+    };
+    let mut networks: LpmTrie<&mut MapData, [u8; 17], u32> =
+        LpmTrie::try_from(bpf.map_mut(name).unwrap()).unwrap();
+
+    let key = network.lpm_key();
+    let key = Key::new(key.prefix_len, key.data);
+
+    match owner {
+        Some(owner) => networks.insert(&key, owner.id, 0).unwrap(),
+        None => {
+            let _ = networks.remove(&key);
+        }
+    }
+}
+
 async fn handle_stream(
     (stream, _addr): (UnixStream, SocketAddr),
     mut rx: Receiver<State>,
     mut tx: Sender<State>,
     opt: Arc<Opt>,
     bpf: Arc<Mutex<Ebpf>>,
-    link: Arc<Mutex<Option<XdpLinkId>>>,
+    link: Arc<Mutex<Option<(XdpLinkId, SchedClassifierLinkId)>>>,
 ) -> Result<()> {
     use futures::{SinkExt, StreamExt};
 
@@ -13,6 +13,7 @@ pub enum Response {
     Status(Status),
     RuleChange(RuleChange),
     Events(Vec<firewall_common::StoredEventDecoded>),
+    Connections(Vec<firewall_common::conntrack::Connection>),
 }
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
@@ -60,4 +61,7 @@ pub enum Request {
     GetRules,
     Status,
     GetEvents(crate::EventQuery),
+    GetConnections,
+    /// Forget every tracked connection, their next packets are `New` or `Invalid`
+    FlushConnections,
 }
@@ -0,0 +1,685 @@
+use core::fmt;
+
+/// DNS message, as carried by UDP or, after its length prefix, by TCP.
+///
+/// Questions and records are parsed lazily from the whole message, since compressed names point
+/// anywhere before them
+pub struct Dns<'pkt> {
+    slice: &'pkt [u8],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    InvalidSize(usize),
+    /// Something at the given offset runs past the end of the message
+    Truncated(usize),
+    /// More than `MAX_POINTERS` compression pointers were followed for one name
+    TooManyPointers,
+    NameTooLong,
+    /// The two high bits of a label length are reserved
+    InvalidLabel(u8),
+}
+
+/// Upper bound on the compression pointers followed while reading a name. Legitimate messages
+/// need a few, loops need infinitely many
+pub const MAX_POINTERS: usize = 16;
+
+/// Longest name on the wire, length bytes included
+pub const MAX_NAME_LEN: usize = 255;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RecordType {
+    A,
+    Ns,
+    Cname,
+    Soa,
+    Ptr,
+    Mx,
+    Txt,
+    Aaaa,
+    Srv,
+    Opt,
+    Https,
+    Any,
+    Other(u16),
+}
+
+impl From<u16> for RecordType {
+    fn from(value: u16) -> Self {
+        match value {
+            1 => Self::A,
+            2 => Self::Ns,
+            5 => Self::Cname,
+            6 => Self::Soa,
+            12 => Self::Ptr,
+            15 => Self::Mx,
+            16 => Self::Txt,
+            28 => Self::Aaaa,
+            33 => Self::Srv,
+            41 => Self::Opt,
+            65 => Self::Https,
+            255 => Self::Any,
+            x => Self::Other(x),
+        }
+    }
+}
+
+impl From<RecordType> for u16 {
+    fn from(value: RecordType) -> Self {
+        match value {
+            RecordType::A => 1,
+            RecordType::Ns => 2,
+            RecordType::Cname => 5,
+            RecordType::Soa => 6,
+            RecordType::Ptr => 12,
+            RecordType::Mx => 15,
+            RecordType::Txt => 16,
+            RecordType::Aaaa => 28,
+            RecordType::Srv => 33,
+            RecordType::Opt => 41,
+            RecordType::Https => 65,
+            RecordType::Any => 255,
+            RecordType::Other(x) => x,
+        }
+    }
+}
+
+impl Dns<'_> {
+    pub const HEADER_LEN: usize = 12;
+    pub const PORT: u16 = 53;
+}
+
+impl<'pkt> Dns<'pkt> {
+    pub fn new(slice: &'pkt [u8]) -> Result<Self, Error> {
+        if slice.len() < Dns::HEADER_LEN {
+            return Err(Error::InvalidSize(slice.len()));
+        }
+
+        Ok(Self { slice })
+    }
+
+    /// Parses a message sent over TCP, which starts with its length. Returns the bytes after
+    /// the message, e.g. the next pipelined message
+    pub fn new_tcp(slice: &'pkt [u8]) -> Result<(Self, &'pkt [u8]), Error> {
+        let Some((len, rem)) = slice.split_first_chunk::<2>() else {
+            return Err(Error::InvalidSize(slice.len()));
+        };
+
+        let len = u16::from_be_bytes(*len) as usize;
+        if rem.len() < len {
+            return Err(Error::Truncated(0));
+        }
+
+        let (message, rem) = rem.split_at(len);
+        Ok((Self::new(message)?, rem))
+    }
+
+    pub fn slice(&self) -> &'pkt [u8] {
+        self.slice
+    }
+
+    fn u16_at(&self, offset: usize) -> u16 {
+        u16::from_be_bytes(*self.slice[offset..offset + 2].first_chunk::<2>().unwrap())
+    }
+
+    pub fn id(&self) -> u16 {
+        self.u16_at(0)
+    }
+
+    pub fn is_response(&self) -> bool {
+        self.slice[2] & 0x80 != 0
+    }
+
+    pub fn opcode(&self) -> u8 {
+        (self.slice[2] >> 3) & 0xF
+    }
+
+    pub fn authoritative(&self) -> bool {
+        self.slice[2] & 0x04 != 0
+    }
+
+    pub fn truncated(&self) -> bool {
+        self.slice[2] & 0x02 != 0
+    }
+
+    pub fn recursion_desired(&self) -> bool {
+        self.slice[2] & 0x01 != 0
+    }
+
+    pub fn recursion_available(&self) -> bool {
+        self.slice[3] & 0x80 != 0
+    }
+
+    /// 0: no error, 2: server failure, 3: non-existent domain, 5: refused
+    pub fn rcode(&self) -> u8 {
+        self.slice[3] & 0xF
+    }
+
+    pub fn question_count(&self) -> u16 {
+        self.u16_at(4)
+    }
+
+    pub fn answer_count(&self) -> u16 {
+        self.u16_at(6)
+    }
+
+    pub fn authority_count(&self) -> u16 {
+        self.u16_at(8)
+    }
+
+    pub fn additional_count(&self) -> u16 {
+        self.u16_at(10)
+    }
+
+    pub fn questions(&self) -> Questions<'pkt> {
+        Questions {
+            message: self.slice,
+            offset: Dns::HEADER_LEN,
+            remaining: self.question_count(),
+        }
+    }
+
+    /// Name asked by the first question, the only one in practice
+    pub fn query_name(&self) -> Option<Name<'pkt>> {
+        self.questions().next()?.ok().map(|q| q.name)
+    }
+
+    pub fn answers(&self) -> Records<'pkt> {
+        self.records(self.questions().end(), self.answer_count())
+    }
+
+    pub fn authorities(&self) -> Records<'pkt> {
+        let answers = self.answers();
+        let count = self.authority_count();
+        self.records(answers.end(), count)
+    }
+
+    pub fn additionals(&self) -> Records<'pkt> {
+        let authorities = self.authorities();
+        let count = self.additional_count();
+        self.records(authorities.end(), count)
+    }
+
+    fn records(&self, offset: Result<usize, Error>, count: u16) -> Records<'pkt> {
+        match offset {
+            Ok(offset) => Records {
+                message: self.slice,
+                offset,
+                remaining: count,
+                error: None,
+            },
+            Err(e) => Records {
+                message: self.slice,
+                offset: self.slice.len(),
+                remaining: count,
+                error: Some(e),
+            },
+        }
+    }
+}
+
+/// Offset right after the name that starts at `offset`, without following pointers
+fn skip_name(message: &[u8], mut offset: usize) -> Result<usize, Error> {
+    for _ in 0..MAX_NAME_LEN / 2 + 1 {
+        let len = *message.get(offset).ok_or(Error::Truncated(offset))?;
+
+        match len & 0xC0 {
+            0xC0 if offset + 2 <= message.len() => return Ok(offset + 2),
+            0xC0 => return Err(Error::Truncated(offset)),
+            0x00 if len == 0 => return Ok(offset + 1),
+            0x00 => offset += 1 + len as usize,
+            _ => return Err(Error::InvalidLabel(len)),
+        }
+    }
+
+    Err(Error::NameTooLong)
+}
+
+/// Possibly compressed domain name, decoded on demand
+#[derive(Debug, Clone, Copy)]
+pub struct Name<'pkt> {
+    message: &'pkt [u8],
+    offset: usize,
+}
+
+impl<'pkt> Name<'pkt> {
+    pub fn labels(&self) -> Labels<'pkt> {
+        Labels {
+            message: self.message,
+            offset: self.offset,
+            pointers: 0,
+            len: 0,
+            done: false,
+        }
+    }
+
+    /// Writes the name in dotted form, without the trailing dot, and returns its length. The
+    /// root is written as an empty name
+    pub fn write_dotted(&self, buffer: &mut [u8]) -> Result<usize, Error> {
+        let mut len = 0;
+
+        for label in self.labels() {
+            let label = label?;
+            let dot = (len != 0) as usize;
+
+            let Some(dest) = buffer.get_mut(len..len + dot + label.len()) else {
+                return Err(Error::NameTooLong);
+            };
+
+            if dot == 1 {
+                dest[0] = b'.';
+            }
+            dest[dot..].copy_from_slice(label);
+            len += dot + label.len();
+        }
+
+        Ok(len)
+    }
+
+    /// Case-insensitive comparison against a dotted name, a trailing dot is ignored
+    pub fn eq_ignore_case(&self, name: &str) -> bool {
+        let mut buffer = [0u8; MAX_NAME_LEN];
+        let Ok(len) = self.write_dotted(&mut buffer) else {
+            return false;
+        };
+
+        buffer[..len].eq_ignore_ascii_case(name.trim_end_matches('.').as_bytes())
+    }
+
+    /// Whether this is `domain` or one of its subdomains, ignoring case. Every name is a
+    /// subdomain of the root, written as an empty string or `.`
+    pub fn is_subdomain_of(&self, domain: &str) -> bool {
+        let domain = domain.trim_end_matches('.').as_bytes();
+        let mut buffer = [0u8; MAX_NAME_LEN];
+        let Ok(len) = self.write_dotted(&mut buffer) else {
+            return false;
+        };
+        let name = &buffer[..len];
+
+        if domain.is_empty() || name.eq_ignore_ascii_case(domain) {
+            return true;
+        }
+
+        name.len() > domain.len()
+            && name[name.len() - domain.len() - 1] == b'.'
+            && name[name.len() - domain.len()..].eq_ignore_ascii_case(domain)
+    }
+}
+
+impl fmt::Display for Name<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, label) in self.labels().enumerate() {
+            let label = label.map_err(|_| fmt::Error)?;
+
+            if i != 0 {
+                f.write_str(".")?;
+            }
+
+            for &c in label {
+                match c {
+                    b'.' | b'\\' => write!(f, "\\{}", c as char)?,
+                    0x21..=0x7E => write!(f, "{}", c as char)?,
+                    _ => write!(f, "\\{c:03}")?,
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Labels of a name, following at most `MAX_POINTERS` compression pointers
+pub struct Labels<'pkt> {
+    message: &'pkt [u8],
+    offset: usize,
+    pointers: usize,
+    len: usize,
+    done: bool,
+}
+
+impl<'pkt> Labels<'pkt> {
+    fn fail(&mut self, error: Error) -> Option<Result<&'pkt [u8], Error>> {
+        self.done = true;
+        Some(Err(error))
+    }
+}
+
+impl<'pkt> Iterator for Labels<'pkt> {
+    type Item = Result<&'pkt [u8], Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for _ in 0..=MAX_POINTERS {
+            if self.done {
+                return None;
+            }
+
+            let Some(&len) = self.message.get(self.offset) else {
+                return self.fail(Error::Truncated(self.offset));
+            };
+
+            match len & 0xC0 {
+                0xC0 => {
+                    let Some(&low) = self.message.get(self.offset + 1) else {
+                        return self.fail(Error::Truncated(self.offset));
+                    };
+
+                    self.pointers += 1;
+                    if self.pointers > MAX_POINTERS {
+                        return self.fail(Error::TooManyPointers);
+                    }
+
+                    self.offset = ((len as usize & 0x3F) << 8) | low as usize;
+                }
+                0x00 if len == 0 => {
+                    self.done = true;
+                    return None;
+                }
+                0x00 => {
+                    let start = self.offset + 1;
+                    let Some(label) = self.message.get(start..start + len as usize) else {
+                        return self.fail(Error::Truncated(self.offset));
+                    };
+
+                    self.len += 1 + label.len();
+                    if self.len >= MAX_NAME_LEN {
+                        return self.fail(Error::NameTooLong);
+                    }
+
+                    self.offset = start + label.len();
+                    return Some(Ok(label));
+                }
+                _ => return self.fail(Error::InvalidLabel(len)),
+            }
+        }
+
+        self.fail(Error::TooManyPointers)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Question<'pkt> {
+    pub name: Name<'pkt>,
+    pub qtype: u16,
+    pub qclass: u16,
+}
+
+impl Question<'_> {
+    pub fn record_type(&self) -> RecordType {
+        RecordType::from(self.qtype)
+    }
+}
+
+pub struct Questions<'pkt> {
+    message: &'pkt [u8],
+    offset: usize,
+    remaining: u16,
+}
+
+impl Questions<'_> {
+    /// Offset of the answer section
+    fn end(mut self) -> Result<usize, Error> {
+        for question in self.by_ref() {
+            question?;
+        }
+
+        Ok(self.offset)
+    }
+}
+
+impl<'pkt> Iterator for Questions<'pkt> {
+    type Item = Result<Question<'pkt>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        let start = self.offset;
+        let result = skip_name(self.message, start).and_then(|end| {
+            let fixed = self
+                .message
+                .get(end..end + 4)
+                .ok_or(Error::Truncated(end))?;
+
+            self.offset = end + 4;
+            Ok(Question {
+                name: Name {
+                    message: self.message,
+                    offset: start,
+                },
+                qtype: u16::from_be_bytes(*fixed[0..2].first_chunk::<2>().unwrap()),
+                qclass: u16::from_be_bytes(*fixed[2..4].first_chunk::<2>().unwrap()),
+            })
+        });
+
+        self.remaining = match result {
+            Ok(_) => self.remaining - 1,
+            Err(_) => 0,
+        };
+
+        Some(result)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Record<'pkt> {
+    pub name: Name<'pkt>,
+    pub rtype: u16,
+    pub class: u16,
+    pub ttl: u32,
+    message: &'pkt [u8],
+    data_offset: usize,
+    data_len: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum RecordData<'pkt> {
+    A([u8; 4]),
+    Aaaa([u8; 16]),
+    Cname(Name<'pkt>),
+    Ptr(Name<'pkt>),
+    Txt(TxtStrings<'pkt>),
+    Other(u16, &'pkt [u8]),
+}
+
+impl<'pkt> Record<'pkt> {
+    pub fn record_type(&self) -> RecordType {
+        RecordType::from(self.rtype)
+    }
+
+    pub fn raw_data(&self) -> &'pkt [u8] {
+        &self.message[self.data_offset..self.data_offset + self.data_len]
+    }
+
+    pub fn data(&self) -> Result<RecordData<'pkt>, Error> {
+        let raw = self.raw_data();
+        let name = Name {
+            message: self.message,
+            offset: self.data_offset,
+        };
+
+        Ok(match (self.record_type(), raw.len()) {
+            (RecordType::A, 4) => RecordData::A(*raw.first_chunk::<4>().unwrap()),
+            (RecordType::Aaaa, 16) => RecordData::Aaaa(*raw.first_chunk::<16>().unwrap()),
+            (RecordType::A | RecordType::Aaaa, _) => {
+                return Err(Error::Truncated(self.data_offset))
+            }
+            (RecordType::Cname, _) => RecordData::Cname(name),
+            (RecordType::Ptr, _) => RecordData::Ptr(name),
+            (RecordType::Txt, _) => RecordData::Txt(TxtStrings { slice: raw }),
+            _ => RecordData::Other(self.rtype, raw),
+        })
+    }
+}
+
+pub struct Records<'pkt> {
+    message: &'pkt [u8],
+    offset: usize,
+    remaining: u16,
+    error: Option<Error>,
+}
+
+impl Records<'_> {
+    /// Offset of the next section
+    fn end(mut self) -> Result<usize, Error> {
+        for record in self.by_ref() {
+            record?;
+        }
+
+        Ok(self.offset)
+    }
+}
+
+impl<'pkt> Iterator for Records<'pkt> {
+    type Item = Result<Record<'pkt>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(e) = self.error.take() {
+            self.remaining = 0;
+            return Some(Err(e));
+        }
+
+        if self.remaining == 0 {
+            return None;
+        }
+
+        let start = self.offset;
+        let result = skip_name(self.message, start).and_then(|end| {
+            let fixed = self
+                .message
+                .get(end..end + 10)
+                .ok_or(Error::Truncated(end))?;
+            let data_len = u16::from_be_bytes(*fixed[8..10].first_chunk::<2>().unwrap()) as usize;
+            let data_offset = end + 10;
+
+            if self.message.len() < data_offset + data_len {
+                return Err(Error::Truncated(data_offset));
+            }
+
+            self.offset = data_offset + data_len;
+            Ok(Record {
+                name: Name {
+                    message: self.message,
+                    offset: start,
+                },
+                rtype: u16::from_be_bytes(*fixed[0..2].first_chunk::<2>().unwrap()),
+                class: u16::from_be_bytes(*fixed[2..4].first_chunk::<2>().unwrap()),
+                ttl: u32::from_be_bytes(*fixed[4..8].first_chunk::<4>().unwrap()),
+                message: self.message,
+                data_offset,
+                data_len,
+            })
+        });
+
+        self.remaining = match result {
+            Ok(_) => self.remaining - 1,
+            Err(_) => 0,
+        };
+
+        Some(result)
+    }
+}
+
+/// Character strings of a TXT record, a truncated last string is left out
+#[derive(Debug, Clone, Copy)]
+pub struct TxtStrings<'pkt> {
+    slice: &'pkt [u8],
+}
+
+impl<'pkt> Iterator for TxtStrings<'pkt> {
+    type Item = &'pkt [u8];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (&len, rem) = self.slice.split_first()?;
+        let string = rem.get(..len as usize)?;
+
+        self.slice = &rem[len as usize..];
+        Some(string)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::dns::{Dns, Error, RecordData, RecordType};
+
+    #[rustfmt::skip]
+    const RESPONSE: [u8; 67] = [
+        // Header: response, RD, RA, 1 question, 2 answers
+        0x12, 0x34, 0x81, 0x80, 0x00, 0x01, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00,
+        // www.example.com A IN
+        0x03, b'w', b'w', b'w', 0x07, b'e', b'x', b'a', b'm', b'p', b'l', b'e', 0x03, b'c', b'o',
+        b'm', 0x00, 0x00, 0x01, 0x00, 0x01,
+        // www.example.com CNAME web.example.com, TTL 300
+        0xC0, 0x0C, 0x00, 0x05, 0x00, 0x01, 0x00, 0x00, 0x01, 0x2C, 0x00, 0x06, 0x03, b'w', b'e',
+        b'b', 0xC0, 0x10,
+        // web.example.com A 93.184.216.34, TTL 60
+        0xC0, 0x2D, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x3C, 0x00, 0x04, 93, 184, 216, 34,
+    ];
+
+    #[test]
+    fn response() {
+        let dns = Dns::new(&RESPONSE).unwrap();
+
+        assert_eq!(dns.id(), 0x1234);
+        assert!(dns.is_response());
+        assert!(dns.recursion_available());
+        assert_eq!(dns.rcode(), 0);
+
+        let question = dns.questions().next().unwrap().unwrap();
+        assert_eq!(question.record_type(), RecordType::A);
+        assert!(question.name.eq_ignore_case("WWW.example.com."));
+        assert!(question.name.is_subdomain_of("example.com"));
+        assert!(!question.name.is_subdomain_of("ample.com"));
+
+        let mut answers = dns.answers();
+        let cname = answers.next().unwrap().unwrap();
+        assert_eq!(cname.ttl, 300);
+        let Ok(RecordData::Cname(target)) = cname.data() else {
+            panic!("expected a CNAME record");
+        };
+        assert!(target.eq_ignore_case("web.example.com"));
+
+        let a = answers.next().unwrap().unwrap();
+        assert!(a.name.eq_ignore_case("web.example.com"));
+        assert!(matches!(a.data(), Ok(RecordData::A([93, 184, 216, 34]))));
+        assert!(answers.next().is_none());
+        assert_eq!(dns.additionals().count(), 0);
+    }
+
+    #[test]
+    fn tcp() {
+        let mut packet = [0u8; 69];
+        packet[0..2].copy_from_slice(&67u16.to_be_bytes());
+        packet[2..].copy_from_slice(&RESPONSE);
+
+        let (dns, rem) = Dns::new_tcp(&packet).unwrap();
+        assert_eq!(dns.answer_count(), 2);
+        assert!(rem.is_empty());
+
+        assert!(Dns::new_tcp(&packet[..40]).is_err());
+    }
+
+    #[test]
+    fn pointer_loop() {
+        let mut message = [0u8; 18];
+        message[5] = 1;
+        // Name pointing to itself
+        message[12..14].copy_from_slice(&[0xC0, 0x0C]);
+
+        let dns = Dns::new(&message).unwrap();
+        let name = dns.query_name().unwrap();
+
+        assert_eq!(name.labels().last(), Some(Err(Error::TooManyPointers)));
+        assert!(!name.eq_ignore_case(""));
+    }
+
+    #[test]
+    fn truncated() {
+        let dns = Dns::new(&RESPONSE[..60]).unwrap();
+        let mut answers = dns.answers();
+
+        assert!(answers.next().unwrap().is_ok());
+        assert!(matches!(answers.next(), Some(Err(Error::Truncated(_)))));
+        assert!(answers.next().is_none());
+    }
+}
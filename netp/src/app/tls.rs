@@ -0,0 +1,515 @@
+/// ClientHello message read from the start of a TLS stream, e.g. the payload of the first data
+/// segment of a connection.
+///
+/// Only a ClientHello fully contained in its first record is parsed, which is the case for all
+/// but the largest post-quantum key shares
+pub struct ClientHello<'pkt> {
+    record_version: u16,
+    version: u16,
+    random: &'pkt [u8; 32],
+    session_id: &'pkt [u8],
+    cipher_suites: &'pkt [u8],
+    compression_methods: &'pkt [u8],
+    extensions: &'pkt [u8],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    InvalidSize(usize),
+    /// Holds the content type of a record that is not a handshake
+    NotHandshake(u8),
+    /// Holds the type of a handshake message that is not a ClientHello
+    NotClientHello(u8),
+    /// A length field at the given offset runs past the end of its enclosing structure
+    Truncated(usize),
+}
+
+pub const CONTENT_TYPE_HANDSHAKE: u8 = 22;
+pub const HANDSHAKE_CLIENT_HELLO: u8 = 1;
+
+pub const EXTENSION_SERVER_NAME: u16 = 0;
+pub const EXTENSION_SUPPORTED_GROUPS: u16 = 10;
+pub const EXTENSION_EC_POINT_FORMATS: u16 = 11;
+pub const EXTENSION_SIGNATURE_ALGORITHMS: u16 = 13;
+pub const EXTENSION_ALPN: u16 = 16;
+pub const EXTENSION_SUPPORTED_VERSIONS: u16 = 43;
+
+/// GREASE values (RFC 8701) are random placeholders clients send to keep servers tolerant of
+/// unknown values, fingerprints leave them out
+pub fn is_grease(value: u16) -> bool {
+    value & 0x0F0F == 0x0A0A && value >> 8 == value & 0xFF
+}
+
+/// Big-endian reader that fails instead of panicking on short input
+struct Reader<'pkt> {
+    slice: &'pkt [u8],
+    offset: usize,
+}
+
+impl<'pkt> Reader<'pkt> {
+    fn new(slice: &'pkt [u8]) -> Self {
+        Self { slice, offset: 0 }
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'pkt [u8], Error> {
+        let bytes = self
+            .slice
+            .get(self.offset..self.offset + len)
+            .ok_or(Error::Truncated(self.offset))?;
+
+        self.offset += len;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, Error> {
+        Ok(u16::from_be_bytes(
+            *self.bytes(2)?.first_chunk::<2>().unwrap(),
+        ))
+    }
+
+    fn u24(&mut self) -> Result<usize, Error> {
+        let bytes = self.bytes(3)?;
+        Ok((bytes[0] as usize) << 16 | (bytes[1] as usize) << 8 | bytes[2] as usize)
+    }
+
+    fn vec8(&mut self) -> Result<&'pkt [u8], Error> {
+        let len = self.u8()? as usize;
+        self.bytes(len)
+    }
+
+    fn vec16(&mut self) -> Result<&'pkt [u8], Error> {
+        let len = self.u16()? as usize;
+        self.bytes(len)
+    }
+}
+
+impl ClientHello<'_> {
+    pub const RECORD_HEADER_LEN: usize = 5;
+}
+
+impl<'pkt> ClientHello<'pkt> {
+    pub fn new(slice: &'pkt [u8]) -> Result<Self, Error> {
+        if slice.len() < ClientHello::RECORD_HEADER_LEN + 4 {
+            return Err(Error::InvalidSize(slice.len()));
+        }
+
+        let mut record = Reader::new(slice);
+
+        let content_type = record.u8()?;
+        if content_type != CONTENT_TYPE_HANDSHAKE {
+            return Err(Error::NotHandshake(content_type));
+        }
+
+        let record_version = record.u16()?;
+        let fragment = record.vec16()?;
+
+        let mut handshake = Reader::new(fragment);
+
+        let handshake_type = handshake.u8()?;
+        if handshake_type != HANDSHAKE_CLIENT_HELLO {
+            return Err(Error::NotClientHello(handshake_type));
+        }
+
+        let len = handshake.u24()?;
+        let mut hello = Reader::new(handshake.bytes(len)?);
+
+        let version = hello.u16()?;
+        let random = hello.bytes(32)?.first_chunk::<32>().unwrap();
+        let session_id = hello.vec8()?;
+        let cipher_suites = hello.vec16()?;
+        let compression_methods = hello.vec8()?;
+
+        // Extensions are optional, a hello can end right after the compression methods
+        let extensions = if hello.offset == hello.slice.len() {
+            &[]
+        } else {
+            hello.vec16()?
+        };
+
+        Ok(Self {
+            record_version,
+            version,
+            random,
+            session_id,
+            cipher_suites,
+            compression_methods,
+            extensions,
+        })
+    }
+
+    pub fn record_version(&self) -> u16 {
+        self.record_version
+    }
+
+    /// Legacy version field, TLS 1.3 clients announce 1.2 here and their real versions in the
+    /// supported versions extension
+    pub fn version(&self) -> u16 {
+        self.version
+    }
+
+    /// Highest version offered, from the supported versions extension if present
+    pub fn max_version(&self) -> u16 {
+        self.supported_versions()
+            .filter(|v| !is_grease(*v))
+            .max()
+            .unwrap_or(self.version)
+    }
+
+    pub fn random(&self) -> &'pkt [u8; 32] {
+        self.random
+    }
+
+    pub fn session_id(&self) -> &'pkt [u8] {
+        self.session_id
+    }
+
+    pub fn cipher_suites(&self) -> impl Iterator<Item = u16> + 'pkt {
+        u16_list(self.cipher_suites)
+    }
+
+    pub fn compression_methods(&self) -> &'pkt [u8] {
+        self.compression_methods
+    }
+
+    pub fn extensions(&self) -> Extensions<'pkt> {
+        Extensions {
+            reader: Reader::new(self.extensions),
+        }
+    }
+
+    fn extension(&self, kind: u16) -> Option<&'pkt [u8]> {
+        self.extensions()
+            .map_while(Result::ok)
+            .find(|e| e.kind == kind)
+            .map(|e| e.data)
+    }
+
+    /// Host name from the server name indication extension
+    pub fn server_name(&self) -> Option<&'pkt str> {
+        let mut reader = Reader::new(self.extension(EXTENSION_SERVER_NAME)?);
+        let mut list = Reader::new(reader.vec16().ok()?);
+
+        while list.offset < list.slice.len() {
+            let name_type = list.u8().ok()?;
+            let name = list.vec16().ok()?;
+
+            if name_type == 0 {
+                return core::str::from_utf8(name).ok();
+            }
+        }
+
+        None
+    }
+
+    /// Protocols offered through ALPN, e.g. `h2` and `http/1.1`
+    pub fn alpn(&self) -> AlpnProtocols<'pkt> {
+        AlpnProtocols {
+            slice: self.vec16_extension(EXTENSION_ALPN),
+        }
+    }
+
+    pub fn supported_versions(&self) -> impl Iterator<Item = u16> + 'pkt {
+        u16_list(self.vec8_extension(EXTENSION_SUPPORTED_VERSIONS))
+    }
+
+    pub fn supported_groups(&self) -> impl Iterator<Item = u16> + 'pkt {
+        u16_list(self.vec16_extension(EXTENSION_SUPPORTED_GROUPS))
+    }
+
+    pub fn signature_algorithms(&self) -> impl Iterator<Item = u16> + 'pkt {
+        u16_list(self.vec16_extension(EXTENSION_SIGNATURE_ALGORITHMS))
+    }
+
+    pub fn ec_point_formats(&self) -> &'pkt [u8] {
+        self.vec8_extension(EXTENSION_EC_POINT_FORMATS)
+    }
+
+    /// Body of an extension made of a single list with a one byte length
+    fn vec8_extension(&self, kind: u16) -> &'pkt [u8] {
+        self.extension(kind)
+            .and_then(|data| Reader::new(data).vec8().ok())
+            .unwrap_or(&[])
+    }
+
+    /// Body of an extension made of a single list with a two byte length
+    fn vec16_extension(&self, kind: u16) -> &'pkt [u8] {
+        self.extension(kind)
+            .and_then(|data| Reader::new(data).vec16().ok())
+            .unwrap_or(&[])
+    }
+}
+
+fn u16_list(slice: &[u8]) -> impl Iterator<Item = u16> + '_ {
+    slice
+        .as_chunks::<2>()
+        .0
+        .iter()
+        .map(|c| u16::from_be_bytes(*c))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TlsExtension<'pkt> {
+    pub kind: u16,
+    pub data: &'pkt [u8],
+}
+
+pub struct Extensions<'pkt> {
+    reader: Reader<'pkt>,
+}
+
+impl<'pkt> Iterator for Extensions<'pkt> {
+    type Item = Result<TlsExtension<'pkt>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.reader.offset >= self.reader.slice.len() {
+            return None;
+        }
+
+        let extension = self.reader.u16().and_then(|kind| {
+            Ok(TlsExtension {
+                kind,
+                data: self.reader.vec16()?,
+            })
+        });
+
+        if extension.is_err() {
+            self.reader.offset = self.reader.slice.len();
+        }
+
+        Some(extension)
+    }
+}
+
+pub struct AlpnProtocols<'pkt> {
+    slice: &'pkt [u8],
+}
+
+impl<'pkt> Iterator for AlpnProtocols<'pkt> {
+    type Item = &'pkt [u8];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (&len, rem) = self.slice.split_first()?;
+        let protocol = rem.get(..len as usize)?;
+
+        self.slice = &rem[len as usize..];
+        Some(protocol)
+    }
+}
+
+#[cfg(feature = "fingerprint")]
+mod fingerprint {
+    use std::fmt::Write;
+
+    use md5::{Digest, Md5};
+    use sha2::Sha256;
+
+    use super::{is_grease, ClientHello, EXTENSION_ALPN, EXTENSION_SERVER_NAME};
+
+    fn join(values: impl Iterator<Item = impl ToString>, separator: &str) -> String {
+        values
+            .map(|v| v.to_string())
+            .collect::<Vec<_>>()
+            .join(separator)
+    }
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().fold(String::new(), |mut s, b| {
+            let _ = write!(s, "{b:02x}");
+            s
+        })
+    }
+
+    /// First 12 hex characters of the SHA-256 of `input`, or zeros for an empty list
+    fn truncated_sha256(input: &str) -> String {
+        if input.is_empty() {
+            return "000000000000".into();
+        }
+
+        hex(&Sha256::digest(input.as_bytes()))[..12].into()
+    }
+
+    fn ja4_version(version: u16) -> &'static str {
+        match version {
+            0x0304 => "13",
+            0x0303 => "12",
+            0x0302 => "11",
+            0x0301 => "10",
+            0x0300 => "s3",
+            0x0002 => "s2",
+            0xFEFF => "d1",
+            0xFEFD => "d2",
+            0xFEFC => "d3",
+            _ => "00",
+        }
+    }
+
+    /// First and last characters of the first ALPN value, their hex digits if not alphanumeric
+    fn ja4_alpn(alpn: Option<&[u8]>) -> String {
+        let Some(&[first, .., last] | &[first @ last]) = alpn else {
+            return "00".into();
+        };
+
+        if first.is_ascii_alphanumeric() && last.is_ascii_alphanumeric() {
+            return format!("{}{}", first as char, last as char);
+        }
+
+        let first = format!("{first:02x}");
+        let last = format!("{last:02x}");
+        format!("{}{}", &first[..1], &last[1..])
+    }
+
+    impl ClientHello<'_> {
+        /// JA3 string, `version,ciphers,extensions,groups,point formats` with GREASE left out
+        pub fn ja3(&self) -> String {
+            let extensions = self
+                .extensions()
+                .map_while(Result::ok)
+                .map(|e| e.kind)
+                .filter(|k| !is_grease(*k));
+
+            format!(
+                "{},{},{},{},{}",
+                self.version(),
+                join(self.cipher_suites().filter(|c| !is_grease(*c)), "-"),
+                join(extensions, "-"),
+                join(self.supported_groups().filter(|g| !is_grease(*g)), "-"),
+                join(self.ec_point_formats().iter(), "-"),
+            )
+        }
+
+        /// MD5 of the JA3 string, the form JA3 fingerprints are shared in
+        pub fn ja3_hash(&self) -> String {
+            hex(&Md5::digest(self.ja3().as_bytes()))
+        }
+
+        /// JA4 fingerprint for TLS over TCP, e.g. `t13d1516h2_8daaf6152771_e5627efa2ab1`
+        pub fn ja4(&self) -> String {
+            let mut ciphers: Vec<u16> = self.cipher_suites().filter(|c| !is_grease(*c)).collect();
+            let mut extensions: Vec<u16> = self
+                .extensions()
+                .map_while(Result::ok)
+                .map(|e| e.kind)
+                .filter(|k| !is_grease(*k))
+                .collect();
+
+            let a = format!(
+                "t{}{}{:02}{:02}{}",
+                ja4_version(self.max_version()),
+                if self.server_name().is_some() {
+                    'd'
+                } else {
+                    'i'
+                },
+                ciphers.len().min(99),
+                extensions.len().min(99),
+                ja4_alpn(self.alpn().next()),
+            );
+
+            ciphers.sort_unstable();
+            let b = truncated_sha256(&join(ciphers.iter().map(|c| format!("{c:04x}")), ","));
+
+            extensions.retain(|k| *k != EXTENSION_SERVER_NAME && *k != EXTENSION_ALPN);
+            extensions.sort_unstable();
+
+            let mut c = join(extensions.iter().map(|e| format!("{e:04x}")), ",");
+            let signatures = join(self.signature_algorithms().map(|s| format!("{s:04x}")), ",");
+            if !signatures.is_empty() {
+                c = format!("{c}_{signatures}");
+            }
+
+            format!("{a}_{b}_{}", truncated_sha256(&c))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::tls::{is_grease, ClientHello, Error};
+
+    #[rustfmt::skip]
+    const HELLO: [u8; 133] = [
+        // Record: handshake, TLS 1.0, 128 bytes
+        0x16, 0x03, 0x01, 0x00, 0x80,
+        // ClientHello, 124 bytes, TLS 1.2
+        0x01, 0x00, 0x00, 0x7C, 0x03, 0x03,
+        // Random
+        0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0A, 0x0B, 0x0C, 0x0D, 0x0E,
+        0x0F, 0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18, 0x19, 0x1A, 0x1B, 0x1C, 0x1D,
+        0x1E, 0x1F,
+        // Empty session id
+        0x00,
+        // Cipher suites: GREASE, TLS_AES_128_GCM_SHA256, TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256
+        0x00, 0x06, 0x0A, 0x0A, 0x13, 0x01, 0xC0, 0x2F,
+        // Null compression
+        0x01, 0x00,
+        // Extensions, 77 bytes
+        0x00, 0x4D,
+        // GREASE
+        0x1A, 0x1A, 0x00, 0x00,
+        // SNI example.com
+        0x00, 0x00, 0x00, 0x10, 0x00, 0x0E, 0x00, 0x00, 0x0B, b'e', b'x', b'a', b'm', b'p', b'l',
+        b'e', b'.', b'c', b'o', b'm',
+        // Supported groups: x25519, secp256r1
+        0x00, 0x0A, 0x00, 0x06, 0x00, 0x04, 0x00, 0x1D, 0x00, 0x17,
+        // EC point formats: uncompressed
+        0x00, 0x0B, 0x00, 0x02, 0x01, 0x00,
+        // Signature algorithms: ecdsa_secp256r1_sha256, rsa_pss_rsae_sha256
+        0x00, 0x0D, 0x00, 0x06, 0x00, 0x04, 0x04, 0x03, 0x08, 0x04,
+        // ALPN: h2, http/1.1
+        0x00, 0x10, 0x00, 0x0E, 0x00, 0x0C, 0x02, b'h', b'2', 0x08, b'h', b't', b't', b'p', b'/',
+        b'1', b'.', b'1',
+        // Supported versions: TLS 1.3, TLS 1.2
+        0x00, 0x2B, 0x00, 0x05, 0x04, 0x03, 0x04, 0x03, 0x03,
+    ];
+
+    #[test]
+    fn client_hello() {
+        let hello = ClientHello::new(&HELLO).unwrap();
+
+        assert_eq!(hello.version(), 0x0303);
+        assert_eq!(hello.max_version(), 0x0304);
+        assert_eq!(hello.server_name(), Some("example.com"));
+        assert_eq!(hello.cipher_suites().filter(|c| !is_grease(*c)).count(), 2);
+        assert_eq!(hello.extensions().count(), 7);
+
+        let mut alpn = hello.alpn();
+        assert_eq!(alpn.next(), Some(&b"h2"[..]));
+        assert_eq!(alpn.next(), Some(&b"http/1.1"[..]));
+        assert_eq!(alpn.next(), None);
+    }
+
+    #[test]
+    fn not_client_hello() {
+        let mut packet = HELLO;
+        packet[0] = 0x17;
+        assert!(matches!(
+            ClientHello::new(&packet),
+            Err(Error::NotHandshake(0x17))
+        ));
+
+        assert!(matches!(
+            ClientHello::new(&HELLO[..100]),
+            Err(Error::Truncated(_))
+        ));
+    }
+
+    #[cfg(feature = "fingerprint")]
+    #[test]
+    fn fingerprints() {
+        let hello = ClientHello::new(&HELLO).unwrap();
+
+        assert_eq!(hello.ja3(), "771,4865-49199,0-10-11-13-16-43,29-23,0");
+        assert_eq!(hello.ja3_hash(), JA3_HASH);
+        assert_eq!(hello.ja4(), JA4);
+    }
+
+    #[cfg(feature = "fingerprint")]
+    const JA3_HASH: &str = "97737df38853b88c4324af06e211c4a1";
+
+    #[cfg(feature = "fingerprint")]
+    const JA4: &str = "t13d0206h2_c1929292aa6b_fb71836bce29";
+}
@@ -30,6 +30,7 @@ pub fn csum_fold_helper(mut csum: u64) -> u16 {
     !(csum as u16)
 }
 
+#[cfg(target_arch = "bpf")]
 #[inline(always)]
 pub fn csum_diff<T: Copy>(mut old: T, mut new: T, seed: u32) -> u64 {
     unsafe {
@@ -43,6 +44,26 @@ pub fn csum_diff<T: Copy>(mut old: T, mut new: T, seed: u32) -> u64 {
     }
 }
 
+/// Same sum as `bpf_csum_diff`, so the incremental updates can be checked on the host
+#[cfg(not(target_arch = "bpf"))]
+pub fn csum_diff<T: Copy>(old: T, new: T, seed: u32) -> u64 {
+    fn words<T>(value: &T) -> impl Iterator<Item = u64> + '_ {
+        let bytes =
+            unsafe { core::slice::from_raw_parts(value as *const T as *const u8, size_of::<T>()) };
+
+        bytes
+            .as_chunks::<2>()
+            .0
+            .iter()
+            .map(|word| u16::from_ne_bytes(*word) as u64)
+    }
+
+    let removed: u64 = words(&old).map(|word| !(word as u16) as u64).sum();
+    let added: u64 = words(&new).sum();
+
+    seed as u64 + removed + added
+}
+
 pub trait XdpErr<T> {
     fn or_drop(self) -> Result<T, u32>
     where
@@ -0,0 +1,631 @@
+//! Writes headers and a payload into a caller-provided buffer, filling in lengths and checksums.
+//!
+//! ```
+//! use netp::builder::PacketBuilder;
+//!
+//! let mut buffer = [0u8; 128];
+//! let len = PacketBuilder::new()
+//!     .ethernet([0x02; 6], [0x04; 6])
+//!     .ipv4([10, 0, 0, 1], [10, 0, 0, 2], 64)
+//!     .udp(5353, 53)
+//!     .write(&mut buffer, b"payload")
+//!     .unwrap();
+//!
+//! assert_eq!(len, 14 + 20 + 8 + 7);
+//! ```
+
+use crate::link::{EtherType, Ethernet, VlanTag};
+use crate::network::{IPv4, IPv6, InetProtocol};
+use crate::packet;
+use crate::transport::icmp::{Icmp, IcmpType};
+use crate::transport::icmpv6::{Icmpv6, Icmpv6Type};
+use crate::transport::tcp::Tcp;
+use crate::transport::udp::Udp;
+
+#[derive(Debug)]
+pub enum Error {
+    /// Holds the number of bytes needed and the number of bytes available
+    BufferTooSmall(usize, usize),
+    /// The payload does not fit in the length field of a header
+    PayloadTooLarge(usize),
+    /// A transport header was requested without a network header
+    MissingNetwork,
+    /// No ethertype or IP protocol could be derived for a header
+    MissingNextHeader,
+    /// More than two VLAN tags were pushed
+    TooManyTags,
+    /// ICMP over IPv6 or ICMPv6 over IPv4
+    ProtocolMismatch,
+    /// A written header could not be parsed back, e.g. a VLAN ethertype without a tag
+    Parse(packet::Error),
+}
+
+#[derive(Debug, Clone, Copy)]
+struct EthernetSpec {
+    source: [u8; 6],
+    destination: [u8; 6],
+}
+
+#[derive(Debug, Clone, Copy)]
+enum NetworkSpec {
+    IPv4 {
+        source: [u8; 4],
+        destination: [u8; 4],
+        ttl: u8,
+        identification: u16,
+    },
+    IPv6 {
+        source: [u8; 16],
+        destination: [u8; 16],
+        hop_limit: u8,
+        flow_label: u32,
+    },
+}
+
+#[derive(Debug, Clone, Copy)]
+enum TransportSpec {
+    Tcp {
+        source: u16,
+        destination: u16,
+        sequence_num: u32,
+        ack_num: u32,
+        flags: u8,
+        window_size: u16,
+    },
+    Udp {
+        source: u16,
+        destination: u16,
+    },
+    Icmp {
+        icmp_type: IcmpType,
+        code: u8,
+        rest: [u8; 4],
+    },
+    Icmpv6 {
+        icmp_type: Icmpv6Type,
+        code: u8,
+        rest: [u8; 4],
+    },
+}
+
+impl TransportSpec {
+    fn size(&self) -> usize {
+        match self {
+            TransportSpec::Tcp { .. } => Tcp::MIN_LEN,
+            TransportSpec::Udp { .. } => Udp::MIN_LEN,
+            TransportSpec::Icmp { .. } => Icmp::MIN_LEN,
+            TransportSpec::Icmpv6 { .. } => Icmpv6::MIN_LEN,
+        }
+    }
+
+    fn protocol(&self) -> InetProtocol {
+        match self {
+            TransportSpec::Tcp { .. } => InetProtocol::TCP,
+            TransportSpec::Udp { .. } => InetProtocol::UDP,
+            TransportSpec::Icmp { .. } => InetProtocol::ICMP,
+            TransportSpec::Icmpv6 { .. } => InetProtocol::IPV6_ICMP,
+        }
+    }
+}
+
+/// Description of a packet, every layer is optional. The ethertype and IP protocol are derived
+/// from the next layer unless set with `ethertype` and `protocol`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PacketBuilder {
+    ethernet: Option<EthernetSpec>,
+    vlans: [Option<VlanTag>; 2],
+    ethertype: Option<EtherType>,
+    network: Option<NetworkSpec>,
+    protocol: Option<InetProtocol>,
+    transport: Option<TransportSpec>,
+    too_many_tags: bool,
+}
+
+impl PacketBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn ethernet(mut self, source: [u8; 6], destination: [u8; 6]) -> Self {
+        self.ethernet = Some(EthernetSpec {
+            source,
+            destination,
+        });
+        self
+    }
+
+    /// Tags are written in the order they are pushed, the first one is the outermost
+    pub fn vlan(mut self, tag: VlanTag) -> Self {
+        match self.vlans {
+            [None, _] => self.vlans[0] = Some(tag),
+            [Some(_), None] => self.vlans[1] = Some(tag),
+            _ => self.too_many_tags = true,
+        }
+        self
+    }
+
+    pub fn ethertype(mut self, ethertype: EtherType) -> Self {
+        self.ethertype = Some(ethertype);
+        self
+    }
+
+    pub fn ipv4(mut self, source: [u8; 4], destination: [u8; 4], ttl: u8) -> Self {
+        self.network = Some(NetworkSpec::IPv4 {
+            source,
+            destination,
+            ttl,
+            identification: 0,
+        });
+        self
+    }
+
+    pub fn ipv6(mut self, source: [u8; 16], destination: [u8; 16], hop_limit: u8) -> Self {
+        self.network = Some(NetworkSpec::IPv6 {
+            source,
+            destination,
+            hop_limit,
+            flow_label: 0,
+        });
+        self
+    }
+
+    /// Sets the IPv4 identification, ignored for IPv6
+    pub fn identification(mut self, id: u16) -> Self {
+        if let Some(NetworkSpec::IPv4 { identification, .. }) = &mut self.network {
+            *identification = id;
+        }
+        self
+    }
+
+    /// Sets the 20-bit IPv6 flow label, ignored for IPv4
+    pub fn flow_label(mut self, label: u32) -> Self {
+        if let Some(NetworkSpec::IPv6 { flow_label, .. }) = &mut self.network {
+            *flow_label = label;
+        }
+        self
+    }
+
+    pub fn protocol(mut self, protocol: InetProtocol) -> Self {
+        self.protocol = Some(protocol);
+        self
+    }
+
+    pub fn tcp(
+        mut self,
+        source: u16,
+        destination: u16,
+        sequence_num: u32,
+        ack_num: u32,
+        flags: u8,
+        window_size: u16,
+    ) -> Self {
+        self.transport = Some(TransportSpec::Tcp {
+            source,
+            destination,
+            sequence_num,
+            ack_num,
+            flags,
+            window_size,
+        });
+        self
+    }
+
+    pub fn udp(mut self, source: u16, destination: u16) -> Self {
+        self.transport = Some(TransportSpec::Udp {
+            source,
+            destination,
+        });
+        self
+    }
+
+    /// `rest` holds the last 4 bytes of the header, e.g. identifier and sequence for echo
+    pub fn icmp(mut self, icmp_type: IcmpType, code: u8, rest: [u8; 4]) -> Self {
+        self.transport = Some(TransportSpec::Icmp {
+            icmp_type,
+            code,
+            rest,
+        });
+        self
+    }
+
+    /// `rest` holds the last 4 bytes of the header, e.g. identifier and sequence for echo
+    pub fn icmpv6(mut self, icmp_type: Icmpv6Type, code: u8, rest: [u8; 4]) -> Self {
+        self.transport = Some(TransportSpec::Icmpv6 {
+            icmp_type,
+            code,
+            rest,
+        });
+        self
+    }
+
+    fn link_size(&self) -> usize {
+        match self.ethernet {
+            Some(_) => Ethernet::MIN_LEN + self.vlans.iter().flatten().count() * Ethernet::TAG_LEN,
+            None => 0,
+        }
+    }
+
+    fn network_size(&self) -> usize {
+        match self.network {
+            Some(NetworkSpec::IPv4 { .. }) => IPv4::MIN_LEN,
+            Some(NetworkSpec::IPv6 { .. }) => IPv6::MIN_LEN,
+            None => 0,
+        }
+    }
+
+    fn transport_size(&self) -> usize {
+        self.transport.map(|t| t.size()).unwrap_or(0)
+    }
+
+    /// Number of bytes `write` needs for a payload of `payload_len` bytes
+    pub fn size(&self, payload_len: usize) -> usize {
+        self.link_size() + self.network_size() + self.transport_size() + payload_len
+    }
+
+    /// Writes the packet at the start of `buffer` and returns its length
+    pub fn write(&self, buffer: &mut [u8], payload: &[u8]) -> Result<usize, Error> {
+        if self.too_many_tags {
+            return Err(Error::TooManyTags);
+        }
+
+        let size = self.size(payload.len());
+        if buffer.len() < size {
+            return Err(Error::BufferTooSmall(size, buffer.len()));
+        }
+
+        if self.transport.is_some() && self.network.is_none() {
+            return Err(Error::MissingNetwork);
+        }
+
+        let (link, rem) = buffer[..size].split_at_mut(self.link_size());
+        let (network, rem) = rem.split_at_mut(self.network_size());
+        let (transport, rem) = rem.split_at_mut(self.transport_size());
+        rem.copy_from_slice(payload);
+
+        if let Some(spec) = self.ethernet {
+            self.write_ethernet(spec, link)?;
+        }
+
+        if let Some(spec) = self.network {
+            self.write_network(spec, network, transport.len() + payload.len())?;
+        }
+
+        if let (Some(spec), Some(network)) = (self.transport, self.network) {
+            write_transport(spec, network, transport, payload)?;
+        }
+
+        Ok(size)
+    }
+
+    fn write_ethernet(&self, spec: EthernetSpec, slice: &mut [u8]) -> Result<(), Error> {
+        let ethertype = match (self.ethertype, self.network) {
+            (Some(ethertype), _) => ethertype,
+            (None, Some(NetworkSpec::IPv4 { .. })) => EtherType::IPv4,
+            (None, Some(NetworkSpec::IPv6 { .. })) => EtherType::IPv6,
+            (None, None) => return Err(Error::MissingNextHeader),
+        };
+
+        let mut offset = 12;
+        for tag in self.vlans.iter().flatten() {
+            slice[offset..offset + 2].copy_from_slice(&u16::from(tag.tpid).to_be_bytes());
+            slice[offset + 2..offset + 4].copy_from_slice(&tag.tci.to_be_bytes());
+            offset += Ethernet::TAG_LEN;
+        }
+        slice[offset..offset + 2].copy_from_slice(&u16::from(ethertype).to_be_bytes());
+
+        let (mut eth, _) =
+            Ethernet::new_mut(slice).map_err(|e| Error::Parse(packet::Error::Ethernet(e)))?;
+        eth.set_source(&spec.source);
+        eth.set_destination(&spec.destination);
+
+        Ok(())
+    }
+
+    fn write_network(
+        &self,
+        spec: NetworkSpec,
+        slice: &mut [u8],
+        payload_len: usize,
+    ) -> Result<(), Error> {
+        let protocol = match (self.protocol, self.transport) {
+            (Some(protocol), _) => protocol,
+            (None, Some(transport)) => transport.protocol(),
+            (None, None) => return Err(Error::MissingNextHeader),
+        };
+
+        match spec {
+            NetworkSpec::IPv4 {
+                source,
+                destination,
+                ttl,
+                identification,
+            } => {
+                let total_length = u16::try_from(IPv4::MIN_LEN + payload_len)
+                    .map_err(|_| Error::PayloadTooLarge(payload_len))?;
+
+                slice.fill(0);
+                slice[0] = (4 << 4) | 5;
+
+                let (mut ip4, _) =
+                    IPv4::new_mut(slice).map_err(|e| Error::Parse(packet::Error::IPv4(e)))?;
+                ip4.set_total_length_u16(total_length);
+                ip4.set_identification(identification);
+                ip4.set_dont_fragment(true);
+                ip4.set_ttl(ttl);
+                ip4.set_protocol(protocol);
+                ip4.set_source(&source);
+                ip4.set_destination(&destination);
+                ip4.update_csum();
+            }
+            NetworkSpec::IPv6 {
+                source,
+                destination,
+                hop_limit,
+                flow_label,
+            } => {
+                let payload_length =
+                    u16::try_from(payload_len).map_err(|_| Error::PayloadTooLarge(payload_len))?;
+
+                slice.fill(0);
+                slice[0] = 6 << 4;
+
+                let (mut ip6, _) =
+                    IPv6::new_mut(slice).map_err(|e| Error::Parse(packet::Error::IPv6(e)))?;
+                ip6.set_flow_label(flow_label);
+                ip6.set_payload_length_u16(payload_length);
+                ip6.set_next_header(protocol);
+                ip6.set_hop_limit(hop_limit);
+                ip6.set_source(&source);
+                ip6.set_destination(&destination);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn write_transport(
+    spec: TransportSpec,
+    network: NetworkSpec,
+    slice: &mut [u8],
+    payload: &[u8],
+) -> Result<(), Error> {
+    slice.fill(0);
+
+    match spec {
+        TransportSpec::Tcp {
+            source,
+            destination,
+            sequence_num,
+            ack_num,
+            flags,
+            window_size,
+        } => {
+            slice[12] = 5 << 4;
+
+            let (mut tcp, _) =
+                Tcp::new_mut(slice).map_err(|e| Error::Parse(packet::Error::Tcp(e)))?;
+            tcp.set_source(source);
+            tcp.set_destination(destination);
+            tcp.set_sequence_num(sequence_num);
+            tcp.set_ack_num(ack_num);
+            tcp.set_flags(flags);
+            tcp.set_window_size(window_size);
+
+            match network {
+                NetworkSpec::IPv4 {
+                    source,
+                    destination,
+                    ..
+                } => tcp.update_csum_ipv4(&source, &destination, payload),
+                NetworkSpec::IPv6 {
+                    source,
+                    destination,
+                    ..
+                } => tcp.update_csum_ipv6(&source, &destination, payload),
+            }
+        }
+        TransportSpec::Udp {
+            source,
+            destination,
+        } => {
+            let length = u16::try_from(Udp::MIN_LEN + payload.len())
+                .map_err(|_| Error::PayloadTooLarge(payload.len()))?;
+            // The transport slice holds only the header, the payload is written afterwards
+            slice[4..6].copy_from_slice(&(Udp::MIN_LEN as u16).to_be_bytes());
+
+            let (mut udp, _) =
+                Udp::new_mut(slice).map_err(|e| Error::Parse(packet::Error::Udp(e)))?;
+            udp.set_length(length);
+            udp.set_source(source);
+            udp.set_destination(destination);
+
+            match network {
+                NetworkSpec::IPv4 {
+                    source,
+                    destination,
+                    ..
+                } => udp.update_csum_ipv4(&source, &destination, payload),
+                NetworkSpec::IPv6 {
+                    source,
+                    destination,
+                    ..
+                } => udp.update_csum_ipv6(&source, &destination, payload),
+            }
+        }
+        TransportSpec::Icmp {
+            icmp_type,
+            code,
+            rest,
+        } => {
+            let NetworkSpec::IPv4 { .. } = network else {
+                return Err(Error::ProtocolMismatch);
+            };
+
+            let (mut icmp, _) =
+                Icmp::new_mut(slice).map_err(|e| Error::Parse(packet::Error::Icmp(e)))?;
+            icmp.set_icmp_type(icmp_type);
+            icmp.set_code(code);
+            icmp.set_rest_of_header(&rest);
+            icmp.update_csum(payload);
+        }
+        TransportSpec::Icmpv6 {
+            icmp_type,
+            code,
+            rest,
+        } => {
+            let NetworkSpec::IPv6 {
+                source,
+                destination,
+                ..
+            } = network
+            else {
+                return Err(Error::ProtocolMismatch);
+            };
+
+            let (mut icmp, _) =
+                Icmpv6::new_mut(slice).map_err(|e| Error::Parse(packet::Error::Icmpv6(e)))?;
+            icmp.set_icmp_type(icmp_type);
+            icmp.set_code(code);
+            icmp.set_rest_of_header(&rest);
+            icmp.update_csum(&source, &destination, payload);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::{Error, PacketBuilder};
+    use crate::link::{eth, EtherType, Ethernet, VlanTag};
+    use crate::network::{IPv4, IPv6, InetProtocol};
+    use crate::packet;
+    use crate::transport::icmpv6::Icmpv6;
+    use crate::transport::tcp::Tcp;
+    use crate::transport::udp::Udp;
+
+    #[test]
+    fn ipv4_tcp() {
+        let mut buffer = [0u8; 64];
+        let len = PacketBuilder::new()
+            .ethernet([0x01; 6], [0x02; 6])
+            .vlan(VlanTag::new(EtherType::VlanTaggedFrame, 0, false, 7))
+            .ipv4([10, 0, 0, 1], [10, 0, 0, 2], 64)
+            .identification(0xBEEF)
+            .tcp(12345, 80, 1, 0, 0x02, 1024)
+            .write(&mut buffer, b"abc")
+            .unwrap();
+
+        assert_eq!(len, 18 + 20 + 20 + 3);
+
+        let (eth, rem) = Ethernet::new(&buffer[..len]).unwrap();
+        assert_eq!(eth.source(), &[0x01; 6]);
+        assert_eq!(eth.ethertype(), EtherType::IPv4);
+        assert_eq!(eth.vlan_tags()[0].map(|t| t.vid()), Some(7));
+
+        let (ip4, rem) = IPv4::new(rem).unwrap();
+        assert_eq!(ip4.total_length(), 43);
+        assert_eq!(ip4.identification(), 0xBEEF);
+        assert_eq!(ip4.protocol(), Ok(InetProtocol::TCP));
+        assert_eq!(ip4.csum(), ip4.calc_csum());
+
+        let (tcp, payload) = Tcp::new(rem).unwrap();
+        assert_eq!(tcp.destination(), 80);
+        assert!(tcp.syn());
+        assert_eq!(payload, b"abc");
+        assert_eq!(
+            tcp.csum(),
+            tcp.calc_csum_ipv4(ip4.source(), ip4.destination(), payload)
+        );
+    }
+
+    /// The IPv4 header is the header checksum example from Wikipedia, the UDP checksum was
+    /// computed outside of this crate
+    #[test]
+    fn ipv4_udp_reference() {
+        let reference = [
+            0x45, 0x00, 0x00, 0x73, 0x00, 0x00, 0x40, 0x00, 0x40, 0x11, 0xB8, 0x61, 0xC0, 0xA8,
+            0x00, 0x01, 0xC0, 0xA8, 0x00, 0xC7, 0x9C, 0x40, 0x00, 0x35, 0x00, 0x5F, 0xE0, 0xA1,
+        ];
+        let mut buffer = [0u8; 128];
+        let len = PacketBuilder::new()
+            .ipv4([192, 168, 0, 1], [192, 168, 0, 199], 64)
+            .udp(40000, 53)
+            .write(&mut buffer, &[0; 87])
+            .unwrap();
+
+        assert_eq!(len, 0x73);
+        assert_eq!(buffer[..28], reference);
+    }
+
+    #[test]
+    fn ipv6_udp() {
+        let mut buffer = [0u8; 64];
+        let len = PacketBuilder::new()
+            .ipv6([0x01; 16], [0x02; 16], 255)
+            .flow_label(0x12345)
+            .udp(5353, 53)
+            .write(&mut buffer, b"query")
+            .unwrap();
+
+        let (ip6, rem) = IPv6::new(&buffer[..len]).unwrap();
+        assert_eq!(ip6.payload_length(), 13);
+        assert_eq!(ip6.flow_label(), 0x12345);
+        assert_eq!(ip6.next_header(), Ok(InetProtocol::UDP));
+
+        let (udp, payload) = Udp::new(rem).unwrap();
+        assert_eq!(udp.length(), 13);
+        assert_eq!(payload, b"query");
+        assert_eq!(
+            u16::from_be_bytes(*udp.checksum()),
+            udp.calc_csum_ipv6(ip6.source(), ip6.destination(), payload)
+        );
+    }
+
+    #[test]
+    fn icmpv6_echo() {
+        let mut buffer = [0u8; 64];
+        let len = PacketBuilder::new()
+            .ipv6([0x01; 16], [0x02; 16], 64)
+            .icmpv6(
+                crate::transport::icmpv6::Icmpv6Type::EchoRequest,
+                0,
+                [0, 1, 0, 1],
+            )
+            .write(&mut buffer, &[])
+            .unwrap();
+
+        let (ip6, rem) = IPv6::new(&buffer[..len]).unwrap();
+        let (icmp, payload) = Icmpv6::new(rem).unwrap();
+        assert_eq!(icmp.identifier(), 1);
+        assert_eq!(
+            icmp.csum(),
+            icmp.calc_csum(ip6.source(), ip6.destination(), payload)
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let mut buffer = [0u8; 16];
+        let builder = PacketBuilder::new().ipv4([0; 4], [0; 4], 64).udp(1, 2);
+
+        assert!(matches!(
+            builder.write(&mut buffer, &[]),
+            Err(Error::BufferTooSmall(28, 16))
+        ));
+        assert!(matches!(
+            PacketBuilder::new().udp(1, 2).write(&mut buffer, &[]),
+            Err(Error::MissingNetwork)
+        ));
+        assert!(matches!(
+            PacketBuilder::new()
+                .ethernet([0; 6], [0; 6])
+                .ethertype(EtherType::VlanTaggedFrame)
+                .write(&mut buffer, &[]),
+            Err(Error::Parse(packet::Error::Ethernet(
+                eth::Error::WrongSizeForType(_, 14)
+            )))
+        ));
+    }
+}
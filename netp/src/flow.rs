@@ -0,0 +1,348 @@
+use core::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use crate::network::InetProtocol;
+use crate::packet::{Network, Packet, Transport};
+use crate::transport::icmp::IcmpType;
+use crate::transport::icmpv6::Icmpv6Type;
+
+/// Key used by Microsoft's RSS verification suite and by most NIC drivers as their default
+pub const DEFAULT_RSS_KEY: [u8; 40] = [
+    0x6d, 0x5a, 0x56, 0xda, 0x25, 0x5b, 0x0e, 0xc2, 0x41, 0x67, 0x25, 0x3d, 0x43, 0xa3, 0x8f, 0xb0,
+    0xd0, 0xca, 0x2b, 0xcb, 0xae, 0x7b, 0x30, 0xb4, 0x77, 0xcb, 0x2d, 0xa3, 0x80, 0x30, 0xf2, 0x0c,
+    0x6a, 0x42, 0xb7, 0x3b, 0xbe, 0xac, 0x01, 0xfa,
+];
+
+/// 5-tuple identifying a flow. The layout has no implicit padding so it can be used as the key
+/// of BPF hash maps.
+///
+/// IPv4 addresses are stored as IPv4-mapped IPv6 addresses. For ICMP echo messages both ports
+/// hold the echo identifier, so requests and replies share a key once normalized
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct FlowKey {
+    source: [u8; 16],
+    destination: [u8; 16],
+    source_port: u16,
+    destination_port: u16,
+    protocol: u8,
+    version: u8,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    _reserved: [u8; 2],
+}
+
+#[cfg(feature = "pod")]
+unsafe impl aya::Pod for FlowKey {}
+
+impl FlowKey {
+    /// Key of addresses of either family. A pair mixing families, which no single packet has,
+    /// is stored as IPv6
+    pub fn new(
+        source: IpAddr,
+        destination: IpAddr,
+        source_port: u16,
+        destination_port: u16,
+        protocol: InetProtocol,
+    ) -> Self {
+        match (source, destination) {
+            (IpAddr::V4(source), IpAddr::V4(destination)) => Self::new_v4(
+                source.octets(),
+                destination.octets(),
+                source_port,
+                destination_port,
+                protocol,
+            ),
+            (source, destination) => Self::new_v6(
+                ipv6_octets(source),
+                ipv6_octets(destination),
+                source_port,
+                destination_port,
+                protocol,
+            ),
+        }
+    }
+
+    pub fn new_v4(
+        source: [u8; 4],
+        destination: [u8; 4],
+        source_port: u16,
+        destination_port: u16,
+        protocol: InetProtocol,
+    ) -> Self {
+        Self {
+            source: Ipv4Addr::from(source).to_ipv6_mapped().octets(),
+            destination: Ipv4Addr::from(destination).to_ipv6_mapped().octets(),
+            source_port,
+            destination_port,
+            protocol: protocol.into(),
+            version: 4,
+            _reserved: [0; 2],
+        }
+    }
+
+    pub fn new_v6(
+        source: [u8; 16],
+        destination: [u8; 16],
+        source_port: u16,
+        destination_port: u16,
+        protocol: InetProtocol,
+    ) -> Self {
+        Self {
+            source,
+            destination,
+            source_port,
+            destination_port,
+            protocol: protocol.into(),
+            version: 6,
+            _reserved: [0; 2],
+        }
+    }
+
+    /// `None` for packets without an IP header or with an unknown upper-layer protocol
+    pub fn from_packet(packet: &Packet) -> Option<Self> {
+        let protocol = packet.protocol?;
+
+        let (source_port, destination_port) = match &packet.transport {
+            Some(Transport::Tcp(tcp)) => (tcp.source(), tcp.destination()),
+            Some(Transport::Udp(udp)) => (udp.source(), udp.destination()),
+            Some(Transport::Sctp(sctp)) => (sctp.source(), sctp.destination()),
+            Some(Transport::Icmp(icmp))
+                if matches!(
+                    icmp.icmp_type(),
+                    IcmpType::EchoRequest | IcmpType::EchoReply
+                ) =>
+            {
+                (icmp.identifier(), icmp.identifier())
+            }
+            Some(Transport::Icmpv6(icmp))
+                if matches!(
+                    icmp.icmp_type(),
+                    Icmpv6Type::EchoRequest | Icmpv6Type::EchoReply
+                ) =>
+            {
+                (icmp.identifier(), icmp.identifier())
+            }
+            _ => (0, 0),
+        };
+
+        match &packet.network {
+            Some(Network::IPv4(ip4)) => Some(Self::new_v4(
+                *ip4.source(),
+                *ip4.destination(),
+                source_port,
+                destination_port,
+                protocol,
+            )),
+            Some(Network::IPv6(ip6)) => Some(Self::new_v6(
+                *ip6.source(),
+                *ip6.destination(),
+                source_port,
+                destination_port,
+                protocol,
+            )),
+            _ => None,
+        }
+    }
+
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    pub fn source(&self) -> IpAddr {
+        self.address(&self.source)
+    }
+
+    pub fn destination(&self) -> IpAddr {
+        self.address(&self.destination)
+    }
+
+    fn address(&self, octets: &[u8; 16]) -> IpAddr {
+        let address = Ipv6Addr::from(*octets);
+
+        match (self.version, address.to_ipv4_mapped()) {
+            (4, Some(address)) => IpAddr::V4(address),
+            _ => IpAddr::V6(address),
+        }
+    }
+
+    pub fn source_port(&self) -> u16 {
+        self.source_port
+    }
+
+    pub fn destination_port(&self) -> u16 {
+        self.destination_port
+    }
+
+    #[allow(clippy::result_unit_err)]
+    pub fn protocol(&self) -> Result<InetProtocol, ()> {
+        InetProtocol::try_from(self.protocol)
+    }
+
+    pub fn protocol_u8(&self) -> u8 {
+        self.protocol
+    }
+
+    /// The same flow seen from the other end
+    pub fn reversed(&self) -> Self {
+        Self {
+            source: self.destination,
+            destination: self.source,
+            source_port: self.destination_port,
+            destination_port: self.source_port,
+            ..*self
+        }
+    }
+
+    /// Whether the lower endpoint, comparing addresses then ports, is the source
+    pub fn is_normalized(&self) -> bool {
+        (self.source, self.source_port) <= (self.destination, self.destination_port)
+    }
+
+    /// Both directions of a flow map to the same normalized key
+    pub fn normalized(&self) -> Self {
+        if self.is_normalized() {
+            *self
+        } else {
+            self.reversed()
+        }
+    }
+
+    /// Toeplitz hash as computed by NICs doing receive side scaling. Ports are only hashed for
+    /// TCP and UDP, like the RSS hash types NICs support. Hash `normalized()` for a hash that is
+    /// the same in both directions
+    pub fn toeplitz_hash(&self, key: &[u8; 40]) -> u32 {
+        let mut input = [0u8; 36];
+        let addr_len = if self.version == 4 { 4 } else { 16 };
+        let addr_start = 16 - addr_len;
+
+        input[..addr_len].copy_from_slice(&self.source[addr_start..]);
+        input[addr_len..addr_len * 2].copy_from_slice(&self.destination[addr_start..]);
+
+        let mut len = addr_len * 2;
+        if self.protocol == 6 || self.protocol == 17 {
+            input[len..len + 2].copy_from_slice(&self.source_port.to_be_bytes());
+            input[len + 2..len + 4].copy_from_slice(&self.destination_port.to_be_bytes());
+            len += 4;
+        }
+
+        toeplitz(key, &input[..len])
+    }
+}
+
+fn ipv6_octets(address: IpAddr) -> [u8; 16] {
+    match address {
+        IpAddr::V4(address) => address.to_ipv6_mapped().octets(),
+        IpAddr::V6(address) => address.octets(),
+    }
+}
+
+/// The input is at most 36 bytes long, so the 40 byte key always covers the sliding window
+fn toeplitz(key: &[u8; 40], input: &[u8]) -> u32 {
+    let mut result = 0;
+    let mut window = u32::from_be_bytes(*key.first_chunk::<4>().unwrap());
+
+    for (i, byte) in input.iter().enumerate() {
+        let next = key.get(i + 4).copied().unwrap_or(0);
+
+        for bit in 0..8 {
+            if byte & (0x80 >> bit) != 0 {
+                result ^= window;
+            }
+
+            window = (window << 1) | ((next >> (7 - bit)) & 1) as u32;
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::PacketBuilder;
+    use crate::flow::{FlowKey, DEFAULT_RSS_KEY};
+    use crate::network::InetProtocol;
+    use crate::packet::Packet;
+
+    #[test]
+    fn rss_verification_suite() {
+        let flow = FlowKey::new_v4(
+            [66, 9, 149, 187],
+            [161, 142, 100, 80],
+            2794,
+            1766,
+            InetProtocol::TCP,
+        );
+        assert_eq!(flow.toeplitz_hash(&DEFAULT_RSS_KEY), 0x51ccc178);
+
+        let flow = FlowKey::new_v4(
+            [66, 9, 149, 187],
+            [161, 142, 100, 80],
+            0,
+            0,
+            InetProtocol::ICMP,
+        );
+        assert_eq!(flow.toeplitz_hash(&DEFAULT_RSS_KEY), 0x323e8fc2);
+
+        let flow = FlowKey::new_v4(
+            [199, 92, 111, 2],
+            [65, 69, 140, 83],
+            14230,
+            4739,
+            InetProtocol::UDP,
+        );
+        assert_eq!(flow.toeplitz_hash(&DEFAULT_RSS_KEY), 0xc626b0ea);
+
+        let flow = FlowKey::new_v6(
+            [
+                0x3f, 0xfe, 0x25, 0x01, 0x02, 0x00, 0x1f, 0xff, 0, 0, 0, 0, 0, 0, 0, 7,
+            ],
+            [
+                0x3f, 0xfe, 0x25, 0x01, 0x02, 0x00, 0x00, 0x03, 0, 0, 0, 0, 0, 0, 0, 1,
+            ],
+            2794,
+            1766,
+            InetProtocol::TCP,
+        );
+        assert_eq!(flow.toeplitz_hash(&DEFAULT_RSS_KEY), 0x40207d3d);
+    }
+
+    #[test]
+    fn normalized() {
+        let flow = FlowKey::new_v4([10, 0, 0, 2], [10, 0, 0, 1], 80, 12345, InetProtocol::TCP);
+
+        assert!(!flow.is_normalized());
+        assert_eq!(flow.normalized(), flow.reversed().normalized());
+        assert_eq!(flow.normalized().source_port(), 12345);
+        assert_eq!(flow.reversed().reversed(), flow);
+    }
+
+    #[test]
+    fn from_packet() {
+        let mut buffer = [0u8; 64];
+        let len = PacketBuilder::new()
+            .ethernet([0x01; 6], [0x02; 6])
+            .ipv4([10, 0, 0, 1], [10, 0, 0, 2], 64)
+            .udp(5353, 53)
+            .write(&mut buffer, &[])
+            .unwrap();
+
+        let packet = Packet::new(&buffer[..len]).unwrap();
+        let flow = FlowKey::from_packet(&packet).unwrap();
+
+        assert_eq!(flow.source(), core::net::IpAddr::from([10, 0, 0, 1]));
+        assert_eq!(flow.destination_port(), 53);
+        assert_eq!(flow.protocol(), Ok(InetProtocol::UDP));
+        assert_eq!(flow.version(), 4);
+        assert_eq!(
+            FlowKey::new(
+                flow.source(),
+                flow.destination(),
+                5353,
+                53,
+                InetProtocol::UDP
+            ),
+            flow
+        );
+    }
+}
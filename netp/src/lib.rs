@@ -1,8 +1,19 @@
-#![cfg_attr(not(feature = "schema"), no_std)]
+#![cfg_attr(not(feature = "std"), no_std)]
 
+pub mod app;
+pub mod builder;
+pub mod flow;
 pub mod link;
 pub mod network;
+pub mod packet;
+pub mod tlv;
 pub mod transport;
+pub mod tunnel;
 
 #[cfg(feature = "aya")]
 pub mod aya;
+
+#[cfg(feature = "std")]
+pub mod pcap;
+
+pub use packet::Packet;
@@ -0,0 +1,282 @@
+use core::fmt;
+use core::net::Ipv4Addr;
+
+use super::{EtherType, MacAddr};
+
+/// ARP packet for Ethernet hardware addresses and IPv4 protocol addresses, the only
+/// combination seen in practice
+#[derive(Debug)]
+pub struct Arp<P = ()> {
+    slice: P,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    InvalidSize(usize),
+    UnsupportedHardware(u16),
+    UnsupportedProtocol(EtherType),
+    InvalidAddressLength(u8, u8),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(u16)]
+pub enum ArpOperation {
+    Request = 1,
+    Reply = 2,
+    Other(u16),
+}
+
+impl From<u16> for ArpOperation {
+    fn from(value: u16) -> Self {
+        match value {
+            1 => Self::Request,
+            2 => Self::Reply,
+            x => Self::Other(x),
+        }
+    }
+}
+
+impl From<ArpOperation> for u16 {
+    fn from(value: ArpOperation) -> Self {
+        match value {
+            ArpOperation::Request => 1,
+            ArpOperation::Reply => 2,
+            ArpOperation::Other(x) => x,
+        }
+    }
+}
+
+impl Arp<()> {
+    pub const LEN: usize = 28;
+    pub const HARDWARE_ETHERNET: u16 = 1;
+}
+
+fn validate(slice: &[u8]) -> Result<(), Error> {
+    if slice.len() < Arp::LEN {
+        return Err(Error::InvalidSize(slice.len()));
+    }
+
+    let hardware = u16::from_be_bytes(*slice[0..2].first_chunk::<2>().unwrap());
+    if hardware != Arp::HARDWARE_ETHERNET {
+        return Err(Error::UnsupportedHardware(hardware));
+    }
+
+    let protocol = EtherType::from(*slice[2..4].first_chunk::<2>().unwrap());
+    if protocol != EtherType::IPv4 {
+        return Err(Error::UnsupportedProtocol(protocol));
+    }
+
+    if slice[4] != 6 || slice[5] != 4 {
+        return Err(Error::InvalidAddressLength(slice[4], slice[5]));
+    }
+
+    Ok(())
+}
+
+impl<'pkt> Arp<&'pkt [u8]> {
+    pub fn new(slice: &'pkt [u8]) -> Result<(Self, &'pkt [u8]), Error> {
+        validate(slice)?;
+
+        let (slice, rem) = slice.split_at(Arp::LEN);
+        Ok((Self { slice }, rem))
+    }
+}
+
+impl<'pkt> Arp<&'pkt mut [u8]> {
+    pub fn new_mut(slice: &'pkt mut [u8]) -> Result<(Self, &'pkt mut [u8]), Error> {
+        validate(slice)?;
+
+        let (slice, rem) = slice.split_at_mut(Arp::LEN);
+        Ok((Self { slice }, rem))
+    }
+}
+
+impl<P: AsMut<[u8]> + AsRef<[u8]>> Arp<P> {
+    pub fn slice_mut(&mut self) -> &mut [u8] {
+        self.slice.as_mut()
+    }
+
+    pub fn set_operation(&mut self, operation: ArpOperation) {
+        self.slice.as_mut()[6..8].copy_from_slice(&u16::from(operation).to_be_bytes());
+    }
+
+    pub fn set_sender_hardware(&mut self, address: &[u8; 6]) {
+        self.slice.as_mut()[8..14].copy_from_slice(address);
+    }
+
+    pub fn set_sender_protocol(&mut self, address: &[u8; 4]) {
+        self.slice.as_mut()[14..18].copy_from_slice(address);
+    }
+
+    pub fn set_sender_protocol_u32(&mut self, address: u32) {
+        self.slice.as_mut()[14..18].copy_from_slice(&address.to_be_bytes());
+    }
+
+    pub fn set_target_hardware(&mut self, address: &[u8; 6]) {
+        self.slice.as_mut()[18..24].copy_from_slice(address);
+    }
+
+    pub fn set_target_protocol(&mut self, address: &[u8; 4]) {
+        self.slice.as_mut()[24..28].copy_from_slice(address);
+    }
+
+    pub fn set_target_protocol_u32(&mut self, address: u32) {
+        self.slice.as_mut()[24..28].copy_from_slice(&address.to_be_bytes());
+    }
+
+    /// Turns a request into the reply announcing that `hardware` owns the requested address.
+    /// The Ethernet header is left untouched
+    pub fn reply_with(&mut self, hardware: &[u8; 6]) {
+        let sender_hardware = *self.sender_hardware();
+        let sender_protocol = *self.sender_protocol();
+        let target_protocol = *self.target_protocol();
+
+        self.set_operation(ArpOperation::Reply);
+        self.set_target_hardware(&sender_hardware);
+        self.set_target_protocol(&sender_protocol);
+        self.set_sender_hardware(hardware);
+        self.set_sender_protocol(&target_protocol);
+    }
+}
+
+impl<P: AsRef<[u8]>> Arp<P> {
+    pub fn slice(&self) -> &[u8] {
+        self.slice.as_ref()
+    }
+
+    pub fn hardware_type(&self) -> u16 {
+        u16::from_be_bytes(*self.slice.as_ref()[0..2].first_chunk::<2>().unwrap())
+    }
+
+    pub fn protocol_type(&self) -> EtherType {
+        EtherType::from(*self.slice.as_ref()[2..4].first_chunk::<2>().unwrap())
+    }
+
+    pub fn hardware_len(&self) -> u8 {
+        self.slice.as_ref()[4]
+    }
+
+    pub fn protocol_len(&self) -> u8 {
+        self.slice.as_ref()[5]
+    }
+
+    pub fn operation(&self) -> ArpOperation {
+        ArpOperation::from(self.operation_u16())
+    }
+
+    pub fn operation_u16(&self) -> u16 {
+        u16::from_be_bytes(*self.slice.as_ref()[6..8].first_chunk::<2>().unwrap())
+    }
+
+    pub fn sender_hardware(&self) -> &[u8; 6] {
+        self.slice.as_ref()[8..14].first_chunk::<6>().unwrap()
+    }
+
+    pub fn sender_protocol(&self) -> &[u8; 4] {
+        self.slice.as_ref()[14..18].first_chunk::<4>().unwrap()
+    }
+
+    pub fn sender_protocol_u32(&self) -> u32 {
+        u32::from_be_bytes(*self.sender_protocol())
+    }
+
+    pub fn target_hardware(&self) -> &[u8; 6] {
+        self.slice.as_ref()[18..24].first_chunk::<6>().unwrap()
+    }
+
+    pub fn target_protocol(&self) -> &[u8; 4] {
+        self.slice.as_ref()[24..28].first_chunk::<4>().unwrap()
+    }
+
+    pub fn target_protocol_u32(&self) -> u32 {
+        u32::from_be_bytes(*self.target_protocol())
+    }
+
+    /// Gratuitous ARPs announce the sender's own address, they are the usual vehicle for
+    /// cache poisoning
+    pub fn is_gratuitous(&self) -> bool {
+        self.sender_protocol() == self.target_protocol()
+    }
+}
+
+/// `ARP, Request who-has 10.0.0.2 tell 10.0.0.1` or `ARP, Reply 10.0.0.2 is-at aa:aa:aa:aa:aa:aa`
+impl<P: AsRef<[u8]>> fmt::Display for Arp<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sender = Ipv4Addr::from(*self.sender_protocol());
+        let target = Ipv4Addr::from(*self.target_protocol());
+
+        match self.operation() {
+            ArpOperation::Request => write!(f, "ARP, Request who-has {target} tell {sender}"),
+            ArpOperation::Reply => write!(
+                f,
+                "ARP, Reply {sender} is-at {}",
+                MacAddr(*self.sender_hardware())
+            ),
+            ArpOperation::Other(operation) => {
+                write!(f, "ARP, unknown operation {operation}, {sender} > {target}")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<P: AsRef<[u8]>> serde::Serialize for Arp<P> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("Arp", 5)?;
+        state.serialize_field("operation", &self.operation())?;
+        state.serialize_field("sender_hardware", &MacAddr(*self.sender_hardware()))?;
+        state.serialize_field("sender_protocol", &Ipv4Addr::from(*self.sender_protocol()))?;
+        state.serialize_field("target_hardware", &MacAddr(*self.target_hardware()))?;
+        state.serialize_field("target_protocol", &Ipv4Addr::from(*self.target_protocol()))?;
+        state.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::link::arp::{Arp, ArpOperation};
+    use crate::link::EtherType;
+
+    const REQUEST: [u8; 28] = [
+        0x00, 0x01, 0x08, 0x00, 0x06, 0x04, 0x00, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0x0A,
+        0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0A, 0x00, 0x00, 0x02,
+    ];
+
+    #[test]
+    fn create_ref() {
+        let (arp, rem) = Arp::new(&REQUEST).unwrap();
+
+        assert_eq!(rem.len(), 0);
+        assert_eq!(arp.protocol_type(), EtherType::IPv4);
+        assert_eq!(arp.operation(), ArpOperation::Request);
+        assert_eq!(arp.sender_hardware(), &[0x01; 6]);
+        assert_eq!(arp.sender_protocol(), &[10, 0, 0, 1]);
+        assert_eq!(arp.target_protocol_u32(), 0x0A000002);
+        assert!(!arp.is_gratuitous());
+    }
+
+    #[test]
+    fn reply() {
+        let mut packet = REQUEST;
+        let (mut arp, _) = Arp::new_mut(&mut packet).unwrap();
+
+        arp.reply_with(&[0x02; 6]);
+
+        assert_eq!(arp.operation(), ArpOperation::Reply);
+        assert_eq!(arp.sender_hardware(), &[0x02; 6]);
+        assert_eq!(arp.sender_protocol(), &[10, 0, 0, 2]);
+        assert_eq!(arp.target_hardware(), &[0x01; 6]);
+        assert_eq!(arp.target_protocol(), &[10, 0, 0, 1]);
+    }
+
+    #[test]
+    fn unsupported() {
+        let mut packet = REQUEST;
+        packet[5] = 16;
+
+        assert!(Arp::new(&packet).is_err());
+    }
+}
@@ -1,9 +1,15 @@
+use core::fmt;
+
+use super::MacAddr;
+
+#[derive(Debug)]
 pub struct Ethernet<P = ()> {
     slice: P,
     size: EtherSize,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u16)]
 pub enum EtherType {
     IPv4 = 0x0800,
@@ -13,17 +19,69 @@ pub enum EtherType {
     VlanTaggedFrame = 0x8100,
     ProviderBridging = 0x88A8,
     VlanDoubleTaggedFrame = 0x9100,
+    /// Ethernet frames carried by GRE and Geneve
+    TransparentEthernetBridging = 0x6558,
     Other(u16),
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 #[repr(u8)]
 pub enum EtherSize {
+    S22 = 22,
     S18 = 18,
-    S16 = 16,
     S14 = 14,
 }
 
+impl EtherType {
+    /// Whether this is one of the TPIDs used by 802.1Q and 802.1ad tags
+    pub fn is_vlan(&self) -> bool {
+        matches!(
+            self,
+            EtherType::VlanTaggedFrame
+                | EtherType::ProviderBridging
+                | EtherType::VlanDoubleTaggedFrame
+        )
+    }
+}
+
+/// 802.1Q tag, the TPID followed by the tag control information
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct VlanTag {
+    pub tpid: EtherType,
+    pub tci: u16,
+}
+
+impl VlanTag {
+    pub fn new(tpid: EtherType, pcp: u8, dei: bool, vid: u16) -> Self {
+        Self {
+            tpid,
+            tci: ((pcp as u16 & 0b111) << 13) | ((dei as u16) << 12) | (vid & 0xFFF),
+        }
+    }
+
+    fn from_slice(slice: &[u8]) -> Self {
+        Self {
+            tpid: EtherType::from(*slice[0..2].first_chunk::<2>().unwrap()),
+            tci: u16::from_be_bytes(*slice[2..4].first_chunk::<2>().unwrap()),
+        }
+    }
+
+    /// Priority code point
+    pub fn pcp(&self) -> u8 {
+        (self.tci >> 13) as u8
+    }
+
+    /// Drop eligible indicator
+    pub fn dei(&self) -> bool {
+        (self.tci >> 12) & 1 == 1
+    }
+
+    /// VLAN identifier
+    pub fn vid(&self) -> u16 {
+        self.tci & 0xFFF
+    }
+}
+
 impl From<EtherType> for u16 {
     fn from(value: EtherType) -> Self {
         match value {
@@ -34,6 +92,7 @@ impl From<EtherType> for u16 {
             EtherType::VlanTaggedFrame => 0x8100,
             EtherType::ProviderBridging => 0x88A8,
             EtherType::VlanDoubleTaggedFrame => 0x9100,
+            EtherType::TransparentEthernetBridging => 0x6558,
             EtherType::Other(v) => v,
         }
     }
@@ -68,6 +127,7 @@ impl From<u16> for EtherType {
             0x8100 => Self::VlanTaggedFrame,
             0x88A8 => Self::ProviderBridging,
             0x9100 => Self::VlanDoubleTaggedFrame,
+            0x6558 => Self::TransparentEthernetBridging,
             x => Self::Other(x),
         }
     }
@@ -77,6 +137,34 @@ impl From<u16> for EtherType {
 pub enum Error {
     WrongSize(usize),
     WrongSizeForType(EtherType, usize),
+    NotTagged,
+    TooManyTags,
+}
+
+fn size_for(slice: &[u8]) -> Result<EtherSize, Error> {
+    if slice.len() < Ethernet::MIN_LEN {
+        return Err(Error::WrongSize(slice.len()));
+    }
+
+    let outer = EtherType::from(*slice[12..14].first_chunk::<2>().unwrap());
+    if !outer.is_vlan() {
+        return Ok(EtherSize::S14);
+    }
+
+    if slice.len() < Ethernet::MIN_LEN + Ethernet::TAG_LEN {
+        return Err(Error::WrongSizeForType(outer, slice.len()));
+    }
+
+    let inner = EtherType::from(*slice[16..18].first_chunk::<2>().unwrap());
+    if !inner.is_vlan() {
+        return Ok(EtherSize::S18);
+    }
+
+    if slice.len() < Ethernet::MAX_LEN {
+        return Err(Error::WrongSizeForType(inner, slice.len()));
+    }
+
+    Ok(EtherSize::S22)
 }
 
 impl<P: AsMut<[u8]> + AsRef<[u8]>> Ethernet<P> {
@@ -100,22 +188,13 @@ impl<P: AsMut<[u8]> + AsRef<[u8]>> Ethernet<P> {
 
 impl Ethernet<()> {
     pub const MIN_LEN: usize = 14;
-    pub const MAX_LEN: usize = 18;
+    pub const MAX_LEN: usize = 22;
+    pub const TAG_LEN: usize = 4;
 }
 
 impl<'pkt> Ethernet<&'pkt [u8]> {
     pub fn new(slice: &'pkt [u8]) -> Result<(Ethernet<&'pkt [u8]>, &'pkt [u8]), Error> {
-        if slice.len() < Ethernet::MIN_LEN {
-            return Err(Error::WrongSize(slice.len()));
-        }
-
-        let size = match EtherType::from(*slice[12..14].first_chunk::<2>().unwrap()) {
-            EtherType::VlanDoubleTaggedFrame if slice.len() >= Ethernet::MAX_LEN => EtherSize::S18,
-            EtherType::VlanTaggedFrame if slice.len() >= Ethernet::MIN_LEN + 2 => EtherSize::S16,
-            EtherType::Other(_) if slice.len() >= Ethernet::MIN_LEN => EtherSize::S14,
-            _ if slice.len() >= Ethernet::MIN_LEN => EtherSize::S14,
-            x => return Err(Error::WrongSizeForType(x, slice.len())),
-        };
+        let size = size_for(slice)?;
 
         let (parsed, rem) = slice.split_at(size as usize);
         Ok((
@@ -132,17 +211,7 @@ impl<'pkt> Ethernet<&'pkt mut [u8]> {
     pub fn new_mut(
         slice: &'pkt mut [u8],
     ) -> Result<(Ethernet<&'pkt mut [u8]>, &'pkt mut [u8]), Error> {
-        if slice.len() < Ethernet::MIN_LEN {
-            return Err(Error::WrongSize(slice.len()));
-        }
-
-        let size = match EtherType::from(*slice[12..14].first_chunk::<2>().unwrap()) {
-            EtherType::VlanDoubleTaggedFrame if slice.len() >= Ethernet::MAX_LEN => EtherSize::S18,
-            EtherType::VlanTaggedFrame if slice.len() >= Ethernet::MIN_LEN + 2 => EtherSize::S16,
-            EtherType::Other(_) if slice.len() >= Ethernet::MIN_LEN => EtherSize::S14,
-            _ if slice.len() >= Ethernet::MIN_LEN => EtherSize::S14,
-            x => return Err(Error::WrongSizeForType(x, slice.len())),
-        };
+        let size = size_for(slice)?;
 
         let (parsed, rem) = slice.split_at_mut(size as usize);
         Ok((
@@ -153,17 +222,53 @@ impl<'pkt> Ethernet<&'pkt mut [u8]> {
             rem,
         ))
     }
+
+    /// Inserts `tag` as the outermost tag of the frame that starts 4 bytes into `slice`. Those 4
+    /// leading bytes are headroom, e.g. what `bpf_xdp_adjust_head(ctx, -4)` leaves in front of
+    /// the packet, once done the frame starts at the beginning of `slice`
+    pub fn push_vlan(
+        slice: &'pkt mut [u8],
+        tag: VlanTag,
+    ) -> Result<(Ethernet<&'pkt mut [u8]>, &'pkt mut [u8]), Error> {
+        if slice.len() < Ethernet::TAG_LEN {
+            return Err(Error::WrongSize(slice.len()));
+        }
+
+        if size_for(&slice[Ethernet::TAG_LEN..])? == EtherSize::S22 {
+            return Err(Error::TooManyTags);
+        }
+
+        slice.copy_within(Ethernet::TAG_LEN..Ethernet::TAG_LEN + 12, 0);
+        slice[12..14].copy_from_slice(&u16::from(tag.tpid).to_be_bytes());
+        slice[14..16].copy_from_slice(&tag.tci.to_be_bytes());
+
+        Ethernet::new_mut(slice)
+    }
+
+    /// Removes the outermost tag of the frame in `slice`. The frame then starts 4 bytes into
+    /// `slice`, which is what `bpf_xdp_adjust_head(ctx, 4)` expects afterwards
+    pub fn pop_vlan(slice: &'pkt mut [u8]) -> Result<(VlanTag, Self, &'pkt mut [u8]), Error> {
+        if size_for(slice)? == EtherSize::S14 {
+            return Err(Error::NotTagged);
+        }
+
+        let tag = VlanTag::from_slice(&slice[12..16]);
+        slice.copy_within(0..12, Ethernet::TAG_LEN);
+
+        let (eth, rem) = Ethernet::new_mut(&mut slice[Ethernet::TAG_LEN..])?;
+        Ok((tag, eth, rem))
+    }
 }
 
 impl<P: AsRef<[u8]>> Ethernet<P> {
     pub fn ethertype(&self) -> EtherType {
         match self.size {
+            EtherSize::S22 => {
+                EtherType::from(*self.slice.as_ref()[20..22].first_chunk::<2>().unwrap())
+            }
             EtherSize::S18 => {
                 EtherType::from(*self.slice.as_ref()[16..18].first_chunk::<2>().unwrap())
             }
-            EtherSize::S16 => {
-                EtherType::from(*self.slice.as_ref()[14..16].first_chunk::<2>().unwrap())
-            }
             EtherSize::S14 => {
                 EtherType::from(*self.slice.as_ref()[12..14].first_chunk::<2>().unwrap())
             }
@@ -180,12 +285,26 @@ impl<P: AsRef<[u8]>> Ethernet<P> {
 
     pub fn size_usize(&self) -> usize {
         match self.size {
+            EtherSize::S22 => 22,
             EtherSize::S18 => 18,
-            EtherSize::S16 => 16,
             EtherSize::S14 => 14,
         }
     }
 
+    /// Outer and inner VLAN tags, in the order they appear on the wire
+    pub fn vlan_tags(&self) -> [Option<VlanTag>; 2] {
+        let slice = self.slice.as_ref();
+
+        match self.size {
+            EtherSize::S22 => [
+                Some(VlanTag::from_slice(&slice[12..16])),
+                Some(VlanTag::from_slice(&slice[16..20])),
+            ],
+            EtherSize::S18 => [Some(VlanTag::from_slice(&slice[12..16])), None],
+            EtherSize::S14 => [None, None],
+        }
+    }
+
     pub fn size(&self) -> EtherSize {
         self.size
     }
@@ -193,11 +312,89 @@ impl<P: AsRef<[u8]>> Ethernet<P> {
     pub fn source(&self) -> &[u8; 6] {
         self.slice.as_ref()[6..12].try_into().unwrap()
     }
+
+    pub fn source_mac(&self) -> MacAddr {
+        MacAddr(*self.source())
+    }
+
+    pub fn destination_mac(&self) -> MacAddr {
+        MacAddr(*self.destination())
+    }
+}
+
+impl fmt::Display for EtherType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            EtherType::IPv4 => "IPv4",
+            EtherType::IPv6 => "IPv6",
+            EtherType::Arp => "ARP",
+            EtherType::WakeOnLan => "Wake-on-LAN",
+            EtherType::VlanTaggedFrame => "802.1Q",
+            EtherType::ProviderBridging => "802.1Q-QinQ",
+            EtherType::VlanDoubleTaggedFrame => "802.1Q-9100",
+            EtherType::TransparentEthernetBridging => "TEB",
+            EtherType::Other(_) => "Unknown",
+        };
+
+        write!(f, "{name} ({:#06x})", u16::from(*self))
+    }
+}
+
+/// `aa:aa:aa:aa:aa:aa > bb:bb:bb:bb:bb:bb, vlan 7, p 0, ethertype IPv4 (0x0800)`
+impl<P: AsRef<[u8]>> fmt::Display for Ethernet<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} > {}", self.source_mac(), self.destination_mac())?;
+
+        for tag in self.vlan_tags().into_iter().flatten() {
+            write!(f, ", vlan {}, p {}", tag.vid(), tag.pcp())?;
+        }
+
+        write!(f, ", ethertype {}", self.ethertype())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for VlanTag {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("VlanTag", 4)?;
+        state.serialize_field("tpid", &self.tpid)?;
+        state.serialize_field("pcp", &self.pcp())?;
+        state.serialize_field("dei", &self.dei())?;
+        state.serialize_field("vid", &self.vid())?;
+        state.end()
+    }
+}
+
+/// Serialized as a sequence of the tags that are present
+#[cfg(feature = "serde")]
+struct VlanTags([Option<VlanTag>; 2]);
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for VlanTags {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.0.iter().flatten())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<P: AsRef<[u8]>> serde::Serialize for Ethernet<P> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("Ethernet", 4)?;
+        state.serialize_field("source", &self.source_mac())?;
+        state.serialize_field("destination", &self.destination_mac())?;
+        state.serialize_field("vlans", &VlanTags(self.vlan_tags()))?;
+        state.serialize_field("ethertype", &self.ethertype())?;
+        state.end()
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::link::eth::EtherSize;
+    use crate::link::eth::{EtherSize, VlanTag};
     use crate::link::{EtherType, Ethernet};
 
     #[test]
@@ -217,6 +414,7 @@ mod tests {
 
         assert_eq!(eth.source(), &[0x02, 0x02, 0x02, 0x02, 0x02, 0x02]);
         assert_eq!(eth.destination(), &[0x01, 0x02, 0x03, 0x04, 0x05, 0x06]);
+        assert!(eth.destination_mac().is_multicast());
     }
 
     #[test]
@@ -237,49 +435,106 @@ mod tests {
     fn vlan_tagged() {
         let packet = [
             0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0x81, 0x00,
-            0x08, 0x00,
+            0xB0, 0x64, 0x08, 0x00,
         ];
         let (eth, rem) = Ethernet::new(&packet).unwrap();
 
         assert_eq!(rem.len(), 0);
-        assert_eq!(eth.size, EtherSize::S16);
+        assert_eq!(eth.size, EtherSize::S18);
         assert_eq!(eth.destination(), &[0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
         assert_eq!(eth.source(), &[0x01, 0x01, 0x01, 0x01, 0x01, 0x01]);
         assert_eq!(eth.ethertype(), EtherType::IPv4);
+
+        let [Some(tag), None] = eth.vlan_tags() else {
+            panic!("expected a single tag");
+        };
+        assert_eq!(tag.tpid, EtherType::VlanTaggedFrame);
+        assert_eq!(tag.pcp(), 5);
+        assert!(tag.dei());
+        assert_eq!(tag.vid(), 100);
     }
 
     #[test]
     fn double_vlan_tagged() {
         let packet = [
             0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0x91, 0x00,
-            0x81, 0x00, 0x08, 0x00,
+            0x00, 0x0A, 0x81, 0x00, 0x00, 0x14, 0x08, 0x00,
         ];
         let (eth, rem) = Ethernet::new(&packet).unwrap();
 
         assert_eq!(rem.len(), 0);
-        assert_eq!(eth.size, EtherSize::S18);
+        assert_eq!(eth.size, EtherSize::S22);
         assert_eq!(eth.destination(), &[0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
         assert_eq!(eth.source(), &[0x01, 0x01, 0x01, 0x01, 0x01, 0x01]);
         assert_eq!(eth.ethertype(), EtherType::IPv4);
+
+        let [Some(outer), Some(inner)] = eth.vlan_tags() else {
+            panic!("expected two tags");
+        };
+        assert_eq!(outer.vid(), 10);
+        assert_eq!(inner.vid(), 20);
+    }
+
+    #[test]
+    fn provider_bridging() {
+        let packet = [
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0x88, 0xA8,
+            0x00, 0x0A, 0x81, 0x00, 0x00, 0x14, 0x86, 0xDD,
+        ];
+        let (eth, rem) = Ethernet::new(&packet).unwrap();
+
+        assert_eq!(rem.len(), 0);
+        assert_eq!(eth.size, EtherSize::S22);
+        assert_eq!(eth.ethertype(), EtherType::IPv6);
+        assert_eq!(
+            eth.vlan_tags()[0].map(|t| t.tpid),
+            Some(EtherType::ProviderBridging)
+        );
+    }
+
+    #[test]
+    fn push_pop_vlan() {
+        let mut packet = [
+            0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x01, 0x01, 0x01,
+            0x01, 0x01, 0x08, 0x00,
+        ];
+
+        let tag = VlanTag::new(EtherType::VlanTaggedFrame, 3, false, 42);
+        let (eth, _) = Ethernet::push_vlan(&mut packet, tag).unwrap();
+
+        assert_eq!(eth.size, EtherSize::S18);
+        assert_eq!(eth.destination(), &[0x00; 6]);
+        assert_eq!(eth.source(), &[0x01; 6]);
+        assert_eq!(eth.ethertype(), EtherType::IPv4);
+        assert_eq!(eth.vlan_tags(), [Some(tag), None]);
+
+        let (popped, eth, _) = Ethernet::pop_vlan(&mut packet).unwrap();
+
+        assert_eq!(popped, tag);
+        assert_eq!(eth.size, EtherSize::S14);
+        assert_eq!(eth.destination(), &[0x00; 6]);
+        assert_eq!(eth.source(), &[0x01; 6]);
+        assert_eq!(eth.ethertype(), EtherType::IPv4);
+        assert!(Ethernet::pop_vlan(&mut packet[4..]).is_err());
     }
 
     #[test]
     fn change_ehertype() {
         let mut packet = [
             0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0x81, 0x00,
-            0x08, 0x00,
+            0x00, 0x01, 0x08, 0x00,
         ];
         let (mut eth, rem) = Ethernet::new_mut(&mut packet).unwrap();
 
         assert_eq!(rem.len(), 0);
-        assert_eq!(eth.size, EtherSize::S16);
+        assert_eq!(eth.size, EtherSize::S18);
         assert_eq!(eth.destination(), &[0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
         assert_eq!(eth.source(), &[0x01, 0x01, 0x01, 0x01, 0x01, 0x01]);
         assert_eq!(eth.ethertype(), EtherType::IPv4);
 
         eth.set_ethertype(EtherType::Arp);
 
-        assert_eq!(eth.size, EtherSize::S16);
+        assert_eq!(eth.size, EtherSize::S18);
         assert_eq!(eth.destination(), &[0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
         assert_eq!(eth.source(), &[0x01, 0x01, 0x01, 0x01, 0x01, 0x01]);
         assert_eq!(eth.ethertype(), EtherType::Arp);
@@ -0,0 +1,240 @@
+use core::fmt;
+use core::str::FromStr;
+
+/// Hardware address, formatted as `aa:bb:cc:dd:ee:ff`. Parsing also accepts the
+/// `aa-bb-cc-dd-ee-ff` and `aabb.ccdd.eeff` forms
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct MacAddr(pub [u8; 6]);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    InvalidLength(usize),
+    InvalidSeparator,
+    InvalidDigit,
+}
+
+impl MacAddr {
+    pub const BROADCAST: MacAddr = MacAddr([0xFF; 6]);
+
+    pub fn octets(&self) -> [u8; 6] {
+        self.0
+    }
+
+    /// Organizationally unique identifier, the first three octets
+    pub fn oui(&self) -> [u8; 3] {
+        [self.0[0], self.0[1], self.0[2]]
+    }
+
+    pub fn is_broadcast(&self) -> bool {
+        *self == Self::BROADCAST
+    }
+
+    /// Broadcast is a multicast address too
+    pub fn is_multicast(&self) -> bool {
+        self.0[0] & 0x01 != 0
+    }
+
+    pub fn is_unicast(&self) -> bool {
+        !self.is_multicast()
+    }
+
+    /// Set by hypervisors, containers and randomized Wi-Fi addresses. These addresses carry no
+    /// vendor information
+    pub fn is_locally_administered(&self) -> bool {
+        self.0[0] & 0x02 != 0
+    }
+
+    pub fn is_universal(&self) -> bool {
+        !self.is_locally_administered()
+    }
+
+    /// Vendor registered for the OUI, from the embedded table
+    #[cfg(feature = "oui")]
+    pub fn vendor(&self) -> Option<&'static str> {
+        if self.is_locally_administered() {
+            return None;
+        }
+
+        super::oui::lookup(self.oui())
+    }
+}
+
+impl From<[u8; 6]> for MacAddr {
+    fn from(value: [u8; 6]) -> Self {
+        Self(value)
+    }
+}
+
+impl From<MacAddr> for [u8; 6] {
+    fn from(value: MacAddr) -> Self {
+        value.0
+    }
+}
+
+impl AsRef<[u8; 6]> for MacAddr {
+    fn as_ref(&self) -> &[u8; 6] {
+        &self.0
+    }
+}
+
+fn hex_digit(c: u8) -> Result<u8, Error> {
+    match c {
+        b'0'..=b'9' => Ok(c - b'0'),
+        b'a'..=b'f' => Ok(c - b'a' + 10),
+        b'A'..=b'F' => Ok(c - b'A' + 10),
+        _ => Err(Error::InvalidDigit),
+    }
+}
+
+impl FromStr for MacAddr {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.as_bytes();
+
+        // Number of digits between separators and the separator itself
+        let (group, separator) = match (s.len(), s.get(2), s.get(4)) {
+            (17, Some(b':'), _) => (2, b':'),
+            (17, Some(b'-'), _) => (2, b'-'),
+            (14, _, Some(b'.')) => (4, b'.'),
+            (17 | 14, ..) => return Err(Error::InvalidSeparator),
+            (len, ..) => return Err(Error::InvalidLength(len)),
+        };
+
+        let mut digits = [0u8; 12];
+        let mut n = 0;
+
+        for (i, &c) in s.iter().enumerate() {
+            if (i + 1) % (group + 1) == 0 {
+                if c != separator {
+                    return Err(Error::InvalidSeparator);
+                }
+                continue;
+            }
+
+            digits[n] = hex_digit(c)?;
+            n += 1;
+        }
+
+        let mut octets = [0u8; 6];
+        for (i, octet) in octets.iter_mut().enumerate() {
+            *octet = (digits[i * 2] << 4) | digits[i * 2 + 1];
+        }
+
+        Ok(Self(octets))
+    }
+}
+
+impl fmt::Display for MacAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [a, b, c, d, e, g] = self.0;
+        write!(f, "{a:02x}:{b:02x}:{c:02x}:{d:02x}:{e:02x}:{g:02x}")
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidLength(len) => write!(f, "invalid MAC address length {len}"),
+            Error::InvalidSeparator => write!(f, "invalid MAC address separator"),
+            Error::InvalidDigit => write!(f, "invalid hexadecimal digit in MAC address"),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for MacAddr {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for MacAddr {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl serde::de::Visitor<'_> for Visitor {
+            type Value = MacAddr;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a MAC address")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                MacAddr::from_str(v).map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_str(Visitor)
+    }
+}
+
+#[cfg(feature = "schema")]
+impl schemars::JsonSchema for MacAddr {
+    fn schema_name() -> String {
+        "MacAddr".into()
+    }
+
+    fn json_schema(_: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        schemars::schema::SchemaObject {
+            instance_type: Some(schemars::schema::InstanceType::String.into()),
+            format: Some("mac".into()),
+            ..Default::default()
+        }
+        .into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::str::FromStr;
+
+    use crate::link::mac::{Error, MacAddr};
+
+    #[test]
+    fn parse() {
+        let expected = MacAddr([0x00, 0x1B, 0x21, 0xAB, 0xCD, 0xEF]);
+
+        assert_eq!(MacAddr::from_str("00:1b:21:ab:cd:ef"), Ok(expected));
+        assert_eq!(MacAddr::from_str("00-1B-21-AB-CD-EF"), Ok(expected));
+        assert_eq!(MacAddr::from_str("001b.21ab.cdef"), Ok(expected));
+
+        assert_eq!(
+            MacAddr::from_str("00:1b-21:ab:cd:ef"),
+            Err(Error::InvalidSeparator)
+        );
+        assert_eq!(
+            MacAddr::from_str("00:1b:21:ab:cd:eg"),
+            Err(Error::InvalidDigit)
+        );
+        assert_eq!(MacAddr::from_str("00:1b"), Err(Error::InvalidLength(5)));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn display() {
+        let mac = MacAddr([0x00, 0x1B, 0x21, 0xAB, 0xCD, 0xEF]);
+        assert_eq!(mac.to_string(), "00:1b:21:ab:cd:ef");
+    }
+
+    #[test]
+    fn flags() {
+        assert!(MacAddr::BROADCAST.is_broadcast());
+        assert!(MacAddr::BROADCAST.is_multicast());
+        assert!(MacAddr([0x01, 0x00, 0x5E, 0, 0, 1]).is_multicast());
+        assert!(MacAddr([0x52, 0x54, 0x00, 0, 0, 1]).is_locally_administered());
+        assert!(MacAddr([0x00, 0x1B, 0x21, 0, 0, 1]).is_universal());
+    }
+
+    #[cfg(feature = "oui")]
+    #[test]
+    fn vendor() {
+        assert_eq!(
+            MacAddr([0x00, 0x50, 0x56, 0, 0, 1]).vendor(),
+            Some("VMware")
+        );
+        assert_eq!(MacAddr([0x52, 0x54, 0x00, 0, 0, 1]).vendor(), None);
+    }
+}
@@ -1,2 +1,8 @@
+pub mod arp;
 pub mod eth;
-pub use eth::{Ethernet, EtherType};
\ No newline at end of file
+pub mod mac;
+#[cfg(feature = "oui")]
+pub mod oui;
+pub use arp::Arp;
+pub use eth::{EtherType, Ethernet, VlanTag};
+pub use mac::MacAddr;
@@ -0,0 +1,99 @@
+/// Vendors most commonly seen on LANs and in data centers, a small subset of the IEEE MA-L
+/// registry. Sorted by OUI
+static VENDORS: &[([u8; 3], &str)] = &[
+    ([0x00, 0x00, 0x0C], "Cisco Systems"),
+    ([0x00, 0x02, 0xB3], "Intel"),
+    ([0x00, 0x02, 0xC9], "Mellanox Technologies"),
+    ([0x00, 0x03, 0x93], "Apple"),
+    ([0x00, 0x03, 0xFF], "Microsoft"),
+    ([0x00, 0x04, 0x4B], "NVIDIA"),
+    ([0x00, 0x05, 0x5D], "D-Link"),
+    ([0x00, 0x05, 0x69], "VMware"),
+    ([0x00, 0x05, 0x85], "Juniper Networks"),
+    ([0x00, 0x09, 0x0F], "Fortinet"),
+    ([0x00, 0x09, 0x5B], "Netgear"),
+    ([0x00, 0x0A, 0x95], "Apple"),
+    ([0x00, 0x0A, 0xF7], "Broadcom"),
+    ([0x00, 0x0B, 0x86], "Aruba Networks"),
+    ([0x00, 0x0C, 0x29], "VMware"),
+    ([0x00, 0x0C, 0x42], "MikroTik"),
+    ([0x00, 0x0D, 0x3A], "Microsoft"),
+    ([0x00, 0x0D, 0xB9], "PC Engines"),
+    ([0x00, 0x0E, 0x0C], "Intel"),
+    ([0x00, 0x10, 0x18], "Broadcom"),
+    ([0x00, 0x11, 0x32], "Synology"),
+    ([0x00, 0x14, 0x22], "Dell"),
+    ([0x00, 0x14, 0x6C], "Netgear"),
+    ([0x00, 0x15, 0x5D], "Microsoft"),
+    ([0x00, 0x15, 0x6D], "Ubiquiti"),
+    ([0x00, 0x16, 0x3E], "Xensource"),
+    ([0x00, 0x16, 0xCB], "Apple"),
+    ([0x00, 0x17, 0x88], "Philips Lighting"),
+    ([0x00, 0x17, 0xF2], "Apple"),
+    ([0x00, 0x18, 0x0A], "Cisco Meraki"),
+    ([0x00, 0x18, 0x8B], "Dell"),
+    ([0x00, 0x1A, 0x11], "Google"),
+    ([0x00, 0x1A, 0x1E], "Aruba Networks"),
+    ([0x00, 0x1B, 0x17], "Palo Alto Networks"),
+    ([0x00, 0x1B, 0x21], "Intel"),
+    ([0x00, 0x1B, 0x63], "Apple"),
+    ([0x00, 0x1C, 0x14], "VMware"),
+    ([0x00, 0x1C, 0x42], "Parallels"),
+    ([0x00, 0x1C, 0x73], "Arista Networks"),
+    ([0x00, 0x1C, 0x7F], "Check Point Software"),
+    ([0x00, 0x1D, 0x09], "Dell"),
+    ([0x00, 0x1E, 0x58], "D-Link"),
+    ([0x00, 0x1F, 0x5B], "Apple"),
+    ([0x00, 0x23, 0xDF], "Apple"),
+    ([0x00, 0x25, 0x00], "Apple"),
+    ([0x00, 0x25, 0x90], "Super Micro Computer"),
+    ([0x00, 0x25, 0xB5], "Cisco Systems"),
+    ([0x00, 0x26, 0xB9], "Dell"),
+    ([0x00, 0x26, 0xBB], "Apple"),
+    ([0x00, 0x27, 0x22], "Ubiquiti"),
+    ([0x00, 0x30, 0x48], "Super Micro Computer"),
+    ([0x00, 0x50, 0x56], "VMware"),
+    ([0x00, 0x50, 0xF2], "Microsoft"),
+    ([0x00, 0x90, 0x27], "Intel"),
+    ([0x00, 0xA0, 0x98], "NetApp"),
+    ([0x00, 0xA0, 0xC9], "Intel"),
+    ([0x00, 0xE0, 0x4C], "Realtek Semiconductor"),
+    ([0x08, 0x00, 0x27], "PCS Systemtechnik (VirtualBox)"),
+    ([0x14, 0xCC, 0x20], "TP-Link"),
+    ([0x18, 0xB4, 0x30], "Nest Labs"),
+    ([0x18, 0xFE, 0x34], "Espressif"),
+    ([0x24, 0x0A, 0xC4], "Espressif"),
+    ([0x24, 0x8A, 0x07], "Mellanox Technologies"),
+    ([0x24, 0xA4, 0x3C], "Ubiquiti"),
+    ([0x28, 0xCD, 0xC1], "Raspberry Pi"),
+    ([0x3C, 0x5A, 0xB4], "Google"),
+    ([0x3C, 0xFD, 0xFE], "Intel"),
+    ([0x4C, 0x5E, 0x0C], "MikroTik"),
+    ([0x50, 0xC7, 0xBF], "TP-Link"),
+    ([0x5C, 0xCF, 0x7F], "Espressif"),
+    ([0xAC, 0x1F, 0x6B], "Super Micro Computer"),
+    ([0xB8, 0x27, 0xEB], "Raspberry Pi"),
+    ([0xB8, 0xAC, 0x6F], "Dell"),
+    ([0xDC, 0xA6, 0x32], "Raspberry Pi"),
+    ([0xE4, 0x5F, 0x01], "Raspberry Pi"),
+    ([0xF4, 0xF5, 0xD8], "Google"),
+];
+
+pub fn lookup(oui: [u8; 3]) -> Option<&'static str> {
+    VENDORS
+        .binary_search_by(|(entry, _)| entry.cmp(&oui))
+        .ok()
+        .map(|i| VENDORS[i].1)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::link::oui::{lookup, VENDORS};
+
+    #[test]
+    fn sorted() {
+        assert!(VENDORS.windows(2).all(|w| w[0].0 < w[1].0));
+        assert_eq!(lookup([0x00, 0x00, 0x0C]), Some("Cisco Systems"));
+        assert_eq!(lookup([0x00, 0x00, 0x00]), None);
+    }
+}
@@ -0,0 +1,302 @@
+use core::fmt;
+use core::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use core::str::FromStr;
+
+/// Block of addresses in CIDR notation, e.g. `10.0.0.0/8` or `2001:db8::/32`. Host bits are
+/// cleared on creation, so `10.1.2.3/8` is stored as `10.0.0.0/8`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct IpNetwork {
+    address: IpAddr,
+    prefix: u8,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    InvalidAddress,
+    InvalidPrefix,
+    /// Holds the prefix length, which is longer than the address
+    PrefixTooLong(u8),
+}
+
+/// Key layout of `BPF_MAP_TYPE_LPM_TRIE` maps, the prefix length in bits followed by the IP
+/// version and the address in network order. The version byte is part of every prefix, so a
+/// single trie holds both families and `::/0` never matches IPv4 addresses
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LpmKey {
+    pub prefix_len: u32,
+    pub data: [u8; 17],
+}
+
+#[cfg(feature = "pod")]
+unsafe impl aya::Pod for LpmKey {}
+
+impl LpmKey {
+    /// Key to look up the most specific network containing `address`
+    pub fn host(address: IpAddr) -> Self {
+        IpNetwork::host(address).lpm_key()
+    }
+}
+
+fn mask_v4(prefix: u8) -> u32 {
+    u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0)
+}
+
+fn mask_v6(prefix: u8) -> u128 {
+    u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0)
+}
+
+impl IpNetwork {
+    pub fn new(address: IpAddr, prefix: u8) -> Result<Self, Error> {
+        let address = match address {
+            IpAddr::V4(address) if prefix <= 32 => {
+                IpAddr::V4(Ipv4Addr::from_bits(address.to_bits() & mask_v4(prefix)))
+            }
+            IpAddr::V6(address) if prefix <= 128 => {
+                IpAddr::V6(Ipv6Addr::from_bits(address.to_bits() & mask_v6(prefix)))
+            }
+            _ => return Err(Error::PrefixTooLong(prefix)),
+        };
+
+        Ok(Self { address, prefix })
+    }
+
+    /// Network holding only `address`
+    pub fn host(address: IpAddr) -> Self {
+        let prefix = match address {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+
+        Self { address, prefix }
+    }
+
+    pub fn address(&self) -> IpAddr {
+        self.address
+    }
+
+    pub fn prefix(&self) -> u8 {
+        self.prefix
+    }
+
+    pub fn is_ipv4(&self) -> bool {
+        self.address.is_ipv4()
+    }
+
+    pub fn is_ipv6(&self) -> bool {
+        self.address.is_ipv6()
+    }
+
+    pub fn netmask(&self) -> IpAddr {
+        match self.address {
+            IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::from_bits(mask_v4(self.prefix))),
+            IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::from_bits(mask_v6(self.prefix))),
+        }
+    }
+
+    /// Addresses of the other family are never contained
+    pub fn contains(&self, address: IpAddr) -> bool {
+        match (self.address, address) {
+            (IpAddr::V4(network), IpAddr::V4(address)) => {
+                address.to_bits() & mask_v4(self.prefix) == network.to_bits()
+            }
+            (IpAddr::V6(network), IpAddr::V6(address)) => {
+                address.to_bits() & mask_v6(self.prefix) == network.to_bits()
+            }
+            _ => false,
+        }
+    }
+
+    pub fn contains_u32(&self, address: u32) -> bool {
+        self.contains(IpAddr::V4(Ipv4Addr::from_bits(address)))
+    }
+
+    pub fn contains_u128(&self, address: u128) -> bool {
+        self.contains(IpAddr::V6(Ipv6Addr::from_bits(address)))
+    }
+
+    /// Whether every address of `other` is in this network
+    pub fn contains_network(&self, other: &IpNetwork) -> bool {
+        other.prefix >= self.prefix && self.contains(other.address)
+    }
+
+    pub fn lpm_key(&self) -> LpmKey {
+        let mut data = [0; 17];
+
+        match self.address {
+            IpAddr::V4(address) => {
+                data[0] = 4;
+                data[1..5].copy_from_slice(&address.octets());
+            }
+            IpAddr::V6(address) => {
+                data[0] = 6;
+                data[1..].copy_from_slice(&address.octets());
+            }
+        }
+
+        LpmKey {
+            prefix_len: 8 + self.prefix as u32,
+            data,
+        }
+    }
+}
+
+impl From<IpAddr> for IpNetwork {
+    fn from(address: IpAddr) -> Self {
+        Self::host(address)
+    }
+}
+
+impl FromStr for IpNetwork {
+    type Err = Error;
+
+    /// A bare address is parsed as a host network
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (address, prefix) = match s.split_once('/') {
+            Some((address, prefix)) => (address, Some(prefix)),
+            None => (s, None),
+        };
+
+        let address = IpAddr::from_str(address).map_err(|_| Error::InvalidAddress)?;
+
+        match prefix {
+            Some(prefix) => {
+                let prefix = u8::from_str(prefix).map_err(|_| Error::InvalidPrefix)?;
+                Self::new(address, prefix)
+            }
+            None => Ok(Self::host(address)),
+        }
+    }
+}
+
+impl fmt::Display for IpNetwork {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.address, self.prefix)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidAddress => write!(f, "invalid network address"),
+            Error::InvalidPrefix => write!(f, "invalid prefix length"),
+            Error::PrefixTooLong(prefix) => write!(f, "prefix length {prefix} is too long"),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for IpNetwork {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for IpNetwork {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl serde::de::Visitor<'_> for Visitor {
+            type Value = IpNetwork;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a network in CIDR notation")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                IpNetwork::from_str(v).map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_str(Visitor)
+    }
+}
+
+#[cfg(feature = "schema")]
+impl schemars::JsonSchema for IpNetwork {
+    fn schema_name() -> String {
+        "IpNetwork".into()
+    }
+
+    fn json_schema(_: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        schemars::schema::SchemaObject {
+            instance_type: Some(schemars::schema::InstanceType::String.into()),
+            format: Some("cidr".into()),
+            ..Default::default()
+        }
+        .into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::net::IpAddr;
+    use core::str::FromStr;
+
+    use crate::network::cidr::{Error, IpNetwork, LpmKey};
+
+    #[test]
+    fn parse() {
+        let network = IpNetwork::from_str("10.1.2.3/8").unwrap();
+
+        assert_eq!(network.address(), IpAddr::from([10, 0, 0, 0]));
+        assert_eq!(network.prefix(), 8);
+        assert_eq!(network.netmask(), IpAddr::from([255, 0, 0, 0]));
+
+        let network = IpNetwork::from_str("2001:db8::1").unwrap();
+        assert_eq!(network.prefix(), 128);
+
+        assert_eq!(
+            IpNetwork::from_str("10.0.0.0/33"),
+            Err(Error::PrefixTooLong(33))
+        );
+        assert_eq!(IpNetwork::from_str("10.0.0/8"), Err(Error::InvalidAddress));
+        assert_eq!(IpNetwork::from_str("10.0.0.0/x"), Err(Error::InvalidPrefix));
+    }
+
+    #[test]
+    fn contains() {
+        let network = IpNetwork::from_str("192.168.0.0/16").unwrap();
+
+        assert!(network.contains(IpAddr::from([192, 168, 10, 1])));
+        assert!(!network.contains(IpAddr::from([192, 169, 0, 1])));
+        assert!(network.contains_u32(0xC0A80001));
+        assert!(!network.contains(IpAddr::from([0u16; 8])));
+        assert!(network.contains_network(&IpNetwork::from_str("192.168.1.0/24").unwrap()));
+
+        let any = IpNetwork::from_str("::/0").unwrap();
+        assert!(any.contains_u128(u128::MAX));
+
+        let network = IpNetwork::from_str("2001:db8::/32").unwrap();
+        assert!(network.contains(IpAddr::from_str("2001:db8:ffff::1").unwrap()));
+        assert!(!network.contains(IpAddr::from_str("2001:db9::1").unwrap()));
+    }
+
+    /// Whether a trie holding `network` returns it when looking up `host`
+    fn lpm_matches(network: &str, host: [u8; 4]) -> bool {
+        let network = IpNetwork::from_str(network).unwrap().lpm_key();
+        let host = LpmKey::host(IpAddr::from(host));
+        let (bytes, bits) = (network.prefix_len as usize / 8, network.prefix_len % 8);
+
+        network.data[..bytes] == host.data[..bytes]
+            && (bits == 0 || (network.data[bytes] ^ host.data[bytes]) >> (8 - bits) == 0)
+    }
+
+    #[test]
+    fn lpm_key() {
+        let key = IpNetwork::from_str("10.0.0.0/8").unwrap().lpm_key();
+
+        assert_eq!(key.prefix_len, 16);
+        assert_eq!(&key.data[..5], &[4, 10, 0, 0, 0]);
+        assert_eq!(LpmKey::host(IpAddr::from([10, 0, 0, 1])).prefix_len, 40);
+        assert_eq!(LpmKey::host(IpAddr::from([0u16; 8])).prefix_len, 136);
+
+        assert!(lpm_matches("10.0.0.0/8", [10, 1, 2, 3]));
+        assert!(lpm_matches("10.0.0.0/9", [10, 127, 0, 1]));
+        assert!(!lpm_matches("10.0.0.0/9", [10, 128, 0, 1]));
+        assert!(lpm_matches("0.0.0.0/0", [192, 168, 0, 1]));
+        assert!(!lpm_matches("::/0", [192, 168, 0, 1]));
+        assert!(!lpm_matches("::ffff:0:0/96", [192, 168, 0, 1]));
+    }
+}
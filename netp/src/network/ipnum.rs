@@ -294,6 +294,28 @@ pub enum InetProtocol {
     EXPERIMENTAL_AND_TESTING_1 = 254,
 }
 
+impl InetProtocol {
+    /// Whether the upper-layer header starts with 16-bit source and destination ports
+    pub fn has_ports(&self) -> bool {
+        matches!(
+            self,
+            InetProtocol::TCP
+                | InetProtocol::UDP
+                | InetProtocol::SCTP
+                | InetProtocol::UDP_LITE
+                | InetProtocol::DCCP
+        )
+    }
+
+    /// Whether the payload is another network layer packet, as with GRE or IP-in-IP
+    pub fn is_tunnel(&self) -> bool {
+        matches!(
+            self,
+            InetProtocol::GRE | InetProtocol::IPV4 | InetProtocol::IPV6
+        )
+    }
+}
+
 impl TryFrom<u8> for InetProtocol {
     type Error = ();
 
@@ -598,3 +620,15 @@ impl From<InetProtocol> for u8 {
         }
     }
 }
+
+/// Protocol number as printed by tcpdump, e.g. `TCP (6)`
+pub(crate) struct ProtocolName(pub u8);
+
+impl core::fmt::Display for ProtocolName {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match InetProtocol::try_from(self.0) {
+            Ok(protocol) => write!(f, "{protocol:?} ({})", self.0),
+            Err(()) => write!(f, "unknown ({})", self.0),
+        }
+    }
+}
@@ -1,5 +1,9 @@
-use super::ipnum::InetProtocol;
+use core::fmt;
+use core::net::Ipv4Addr;
 
+use super::ipnum::{InetProtocol, ProtocolName};
+
+#[derive(Debug)]
 pub struct IPv4<P = ()> {
     slice: P,
     size: IPv4Size,
@@ -52,6 +56,7 @@ pub enum IhlError {
     InvalidIhl(u8),
 }
 
+#[derive(Debug)]
 pub enum Error {
     InvalidIhl(IhlError),
     InvalidSize(usize),
@@ -99,6 +104,22 @@ impl<P: AsMut<[u8]> + AsRef<[u8]>> IPv4<P> {
     pub fn set_protocol(&mut self, protocol: InetProtocol) {
         self.slice.as_mut()[9] = u8::from(protocol);
     }
+
+    pub fn set_ttl(&mut self, ttl: u8) {
+        self.slice.as_mut()[8] = ttl;
+    }
+
+    pub fn set_identification(&mut self, identification: u16) {
+        self.slice.as_mut()[4..6].copy_from_slice(&identification.to_be_bytes())
+    }
+
+    pub fn set_dont_fragment(&mut self, value: bool) {
+        if value {
+            self.slice.as_mut()[6] |= 1 << 6;
+        } else {
+            self.slice.as_mut()[6] &= !(1 << 6);
+        }
+    }
 }
 
 impl IPv4<()> {
@@ -197,6 +218,20 @@ impl<P: AsRef<[u8]>> IPv4<P> {
         res
     }
 
+    /// Offset of this fragment in 8-byte units
+    pub fn fragment_offset_u16(&self) -> u16 {
+        u16::from_be_bytes(self.fragment_offset())
+    }
+
+    pub fn is_fragment(&self) -> bool {
+        self.more_fragments() || self.fragment_offset_u16() != 0
+    }
+
+    /// Only the first fragment carries the transport header
+    pub fn is_first_fragment(&self) -> bool {
+        self.more_fragments() && self.fragment_offset_u16() == 0
+    }
+
     pub fn dont_fragment(&self) -> bool {
         (self.slice.as_ref()[6] >> 6) & 0b01 == 1
     }
@@ -283,3 +318,50 @@ impl IPv4Size {
         })
     }
 }
+
+/// `IP (tos 0x0, ttl 64, id 1, offset 0, flags [DF], proto TCP (6), length 40) 10.0.0.1 > 10.0.0.2`
+impl<P: AsRef<[u8]>> fmt::Display for IPv4<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let flags = match (self.dont_fragment(), self.more_fragments()) {
+            (true, true) => "DF+",
+            (true, false) => "DF",
+            (false, true) => "+",
+            (false, false) => "none",
+        };
+
+        write!(
+            f,
+            "IP (tos {:#x}, ttl {}, id {}, offset {}, flags [{flags}], proto {}, length {}) {} > {}",
+            self.slice.as_ref()[1],
+            self.ttl(),
+            self.identification(),
+            self.fragment_offset_u16() as u32 * 8,
+            ProtocolName(self.protocol_u8()),
+            self.total_length(),
+            Ipv4Addr::from(*self.source()),
+            Ipv4Addr::from(*self.destination()),
+        )
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<P: AsRef<[u8]>> serde::Serialize for IPv4<P> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("IPv4", 12)?;
+        state.serialize_field("source", &Ipv4Addr::from(*self.source()))?;
+        state.serialize_field("destination", &Ipv4Addr::from(*self.destination()))?;
+        state.serialize_field("dscp", &self.dscp())?;
+        state.serialize_field("ecn", &self.ecn())?;
+        state.serialize_field("total_length", &self.total_length())?;
+        state.serialize_field("identification", &self.identification())?;
+        state.serialize_field("dont_fragment", &self.dont_fragment())?;
+        state.serialize_field("more_fragments", &self.more_fragments())?;
+        state.serialize_field("fragment_offset", &self.fragment_offset_u16())?;
+        state.serialize_field("ttl", &self.ttl())?;
+        state.serialize_field("protocol", &self.protocol_u8())?;
+        state.serialize_field("checksum", &self.csum())?;
+        state.end()
+    }
+}
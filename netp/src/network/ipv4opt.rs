@@ -0,0 +1,211 @@
+use super::ipv4::IPv4;
+use crate::tlv::{OptionWalker, RawOption};
+
+pub use crate::tlv::OptionError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IPv4Option<'pkt> {
+    Eol,
+    Nop,
+    RecordRoute(Route<'pkt>),
+    LooseSourceRoute(Route<'pkt>),
+    StrictSourceRoute(Route<'pkt>),
+    Timestamp(Timestamp<'pkt>),
+    RouterAlert(u16),
+    Unknown(u8, &'pkt [u8]),
+}
+
+/// Route data shared by the record route and source route options
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Route<'pkt> {
+    pointer: u8,
+    slice: &'pkt [u8],
+}
+
+impl<'pkt> Route<'pkt> {
+    /// One-based offset, from the start of the option, of the next address slot
+    pub fn pointer(&self) -> u8 {
+        self.pointer
+    }
+
+    pub fn addresses(&self) -> impl Iterator<Item = &'pkt [u8; 4]> {
+        self.slice.as_chunks::<4>().0.iter()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timestamp<'pkt> {
+    pointer: u8,
+    overflow_flags: u8,
+    slice: &'pkt [u8],
+}
+
+impl<'pkt> Timestamp<'pkt> {
+    pub fn pointer(&self) -> u8 {
+        self.pointer
+    }
+
+    pub fn overflow(&self) -> u8 {
+        self.overflow_flags >> 4
+    }
+
+    /// 0: timestamps only, 1: address and timestamp pairs, 3: prespecified addresses
+    pub fn flags(&self) -> u8 {
+        self.overflow_flags & 0xF
+    }
+
+    pub fn data(&self) -> &'pkt [u8] {
+        self.slice
+    }
+}
+
+/// Iterator over the options of an IPv4 header, decoding the route recording, source routing,
+/// timestamp and router alert options. A known option with a malformed length ends the walk
+pub struct IPv4Options<'pkt> {
+    walker: OptionWalker<'pkt>,
+}
+
+impl<'pkt> IPv4Options<'pkt> {
+    pub fn new(slice: &'pkt [u8]) -> Self {
+        Self {
+            walker: OptionWalker::new(slice),
+        }
+    }
+}
+
+impl<'pkt> Iterator for IPv4Options<'pkt> {
+    type Item = Result<IPv4Option<'pkt>, OptionError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (kind, data) = match self.walker.next()? {
+            Ok(RawOption::Eol) => return Some(Ok(IPv4Option::Eol)),
+            Ok(RawOption::Nop) => return Some(Ok(IPv4Option::Nop)),
+            Ok(RawOption::Value(kind, data)) => (kind, data),
+            Err(e) => return Some(Err(e)),
+        };
+        let len = data.len() as u8 + 2;
+
+        let option = match kind {
+            7 | 131 | 137 if len >= 3 && (len - 3) % 4 == 0 => {
+                let route = Route {
+                    pointer: data[0],
+                    slice: &data[1..],
+                };
+
+                match kind {
+                    7 => IPv4Option::RecordRoute(route),
+                    131 => IPv4Option::LooseSourceRoute(route),
+                    _ => IPv4Option::StrictSourceRoute(route),
+                }
+            }
+            68 if len >= 4 => IPv4Option::Timestamp(Timestamp {
+                pointer: data[0],
+                overflow_flags: data[1],
+                slice: &data[2..],
+            }),
+            148 if len == 4 => {
+                IPv4Option::RouterAlert(u16::from_be_bytes(*data.first_chunk::<2>().unwrap()))
+            }
+            7 | 68 | 131 | 137 | 148 => {
+                self.walker.finish();
+                return Some(Err(OptionError::InvalidLength(kind, len)));
+            }
+            _ => IPv4Option::Unknown(kind, data),
+        };
+
+        Some(Ok(option))
+    }
+}
+
+impl<P: AsRef<[u8]>> IPv4<P> {
+    pub fn options_iter(&self) -> IPv4Options<'_> {
+        IPv4Options::new(self.options())
+    }
+
+    /// Whether the header carries a loose or strict source route option. Malformed options are
+    /// reported as a source route too, since they can not be proven not to be one
+    pub fn has_source_route(&self) -> bool {
+        self.options_iter().any(|o| {
+            matches!(
+                o,
+                Ok(IPv4Option::LooseSourceRoute(_) | IPv4Option::StrictSourceRoute(_)) | Err(_)
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::network::ipv4opt::{IPv4Option, IPv4Options, OptionError};
+    use crate::network::IPv4;
+
+    #[test]
+    fn options() {
+        let packet = [
+            0x47, 0x00, 0x00, 0x1C, 0x00, 0x00, 0x40, 0x00, 0x40, 0x11, 0x00, 0x00, 0x0A, 0x00,
+            0x00, 0x01, 0x0A, 0x00, 0x00, 0x02, 0x94, 0x04, 0x00, 0x00, 0x83, 0x03, 0x04, 0x00,
+        ];
+        let (ip4, _) = IPv4::new(&packet).unwrap();
+        let mut options = ip4.options_iter();
+
+        assert_eq!(options.next(), Some(Ok(IPv4Option::RouterAlert(0))));
+        let Some(Ok(IPv4Option::LooseSourceRoute(route))) = options.next() else {
+            panic!("expected a loose source route");
+        };
+        assert_eq!(route.pointer(), 4);
+        assert_eq!(route.addresses().count(), 0);
+        assert_eq!(options.next(), Some(Ok(IPv4Option::Eol)));
+        assert_eq!(options.next(), None);
+
+        assert!(ip4.has_source_route());
+    }
+
+    #[test]
+    fn record_route() {
+        let options = [0x07, 0x0B, 0x08, 10, 0, 0, 1, 10, 0, 0, 2, 0x01];
+        let Some(Ok(IPv4Option::RecordRoute(route))) = IPv4Options::new(&options).next() else {
+            panic!("expected a record route");
+        };
+
+        let mut addresses = route.addresses();
+        assert_eq!(addresses.next(), Some(&[10, 0, 0, 1]));
+        assert_eq!(addresses.next(), Some(&[10, 0, 0, 2]));
+        assert_eq!(addresses.next(), None);
+    }
+
+    #[test]
+    fn malformed() {
+        let mut options = IPv4Options::new(&[0x94, 0x03, 0x00, 0x00]);
+        assert_eq!(
+            options.next(),
+            Some(Err(OptionError::InvalidLength(148, 3)))
+        );
+        assert_eq!(options.next(), None);
+
+        let mut options = IPv4Options::new(&[0x89, 0x0B, 0x04, 0x00]);
+        assert_eq!(options.next(), Some(Err(OptionError::Truncated(0))));
+    }
+
+    #[test]
+    fn fragments() {
+        let mut packet = [
+            0x45, 0x00, 0x00, 0x14, 0x00, 0x00, 0x20, 0x00, 0x40, 0x11, 0x00, 0x00, 0x0A, 0x00,
+            0x00, 0x01, 0x0A, 0x00, 0x00, 0x02,
+        ];
+
+        {
+            let (ip4, _) = IPv4::new(&packet).unwrap();
+            assert!(ip4.is_fragment());
+            assert!(ip4.is_first_fragment());
+            assert_eq!(ip4.fragment_offset_u16(), 0);
+        }
+
+        packet[6] = 0x01;
+        packet[7] = 0x02;
+
+        let (ip4, _) = IPv4::new(&packet).unwrap();
+        assert!(ip4.is_fragment());
+        assert!(!ip4.is_first_fragment());
+        assert_eq!(ip4.fragment_offset_u16(), 0x102);
+    }
+}
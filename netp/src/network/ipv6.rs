@@ -0,0 +1,538 @@
+use core::fmt;
+use core::net::Ipv6Addr;
+
+use super::ipnum::{InetProtocol, ProtocolName};
+
+#[derive(Debug)]
+pub struct IPv6<P = ()> {
+    slice: P,
+}
+
+impl<'pkt> IPv6<&'pkt [u8]> {
+    pub fn new(slice: &'pkt [u8]) -> Result<(Self, &'pkt [u8]), Error> {
+        if slice.len() < IPv6::MIN_LEN {
+            return Err(Error::InvalidSize(slice.len()));
+        }
+
+        if slice[0] >> 4 != 6 {
+            return Err(Error::InvalidVersion(slice[0] >> 4));
+        }
+
+        let (slice, rem) = slice.split_at(IPv6::MIN_LEN);
+        Ok((Self { slice }, rem))
+    }
+}
+
+impl<'pkt> IPv6<&'pkt mut [u8]> {
+    pub fn new_mut(slice: &'pkt mut [u8]) -> Result<(Self, &'pkt mut [u8]), Error> {
+        if slice.len() < IPv6::MIN_LEN {
+            return Err(Error::InvalidSize(slice.len()));
+        }
+
+        if slice[0] >> 4 != 6 {
+            return Err(Error::InvalidVersion(slice[0] >> 4));
+        }
+
+        let (slice, rem) = slice.split_at_mut(IPv6::MIN_LEN);
+        Ok((Self { slice }, rem))
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    InvalidSize(usize),
+    InvalidVersion(u8),
+}
+
+impl IPv6<()> {
+    /// The IPv6 fixed header is always 40 bytes, extension headers are parsed separately
+    pub const MIN_LEN: usize = 40;
+}
+
+impl<P: AsMut<[u8]> + AsRef<[u8]>> IPv6<P> {
+    pub fn slice_mut(&mut self) -> &mut [u8] {
+        self.slice.as_mut()
+    }
+
+    pub fn set_traffic_class(&mut self, traffic_class: u8) {
+        let slice = self.slice.as_mut();
+        slice[0] = (6 << 4) | (traffic_class >> 4);
+        slice[1] = (traffic_class << 4) | (slice[1] & 0xF);
+    }
+
+    pub fn set_dscp(&mut self, dscp: u8) {
+        self.set_traffic_class(((dscp & 0x3F) << 2) | self.ecn())
+    }
+
+    pub fn set_ecn(&mut self, ecn: u8) {
+        self.set_traffic_class((self.dscp() << 2) | (ecn & 0b11))
+    }
+
+    /// Only the lower 20 bits of `flow_label` are used
+    pub fn set_flow_label(&mut self, flow_label: u32) {
+        let slice = self.slice.as_mut();
+        let bytes = flow_label.to_be_bytes();
+        slice[1] = (slice[1] & 0xF0) | (bytes[1] & 0xF);
+        slice[2] = bytes[2];
+        slice[3] = bytes[3];
+    }
+
+    pub fn set_payload_length(&mut self, value: &[u8; 2]) {
+        self.slice.as_mut()[4..6].copy_from_slice(value)
+    }
+
+    pub fn set_payload_length_u16(&mut self, value: u16) {
+        self.slice.as_mut()[4..6].copy_from_slice(&value.to_be_bytes())
+    }
+
+    pub fn set_next_header(&mut self, protocol: InetProtocol) {
+        self.slice.as_mut()[6] = u8::from(protocol);
+    }
+
+    pub fn set_hop_limit(&mut self, hop_limit: u8) {
+        self.slice.as_mut()[7] = hop_limit;
+    }
+
+    pub fn set_source(&mut self, source: &[u8; 16]) {
+        self.slice.as_mut()[8..24].copy_from_slice(source)
+    }
+
+    pub fn set_source_u128(&mut self, source: u128) {
+        self.slice.as_mut()[8..24].copy_from_slice(&source.to_be_bytes())
+    }
+
+    pub fn set_destination(&mut self, destination: &[u8; 16]) {
+        self.slice.as_mut()[24..40].copy_from_slice(destination)
+    }
+
+    pub fn set_destination_u128(&mut self, destination: u128) {
+        self.slice.as_mut()[24..40].copy_from_slice(&destination.to_be_bytes())
+    }
+}
+
+impl<P: AsRef<[u8]>> IPv6<P> {
+    pub fn slice(&self) -> &[u8] {
+        self.slice.as_ref()
+    }
+
+    pub fn version(&self) -> u8 {
+        self.slice.as_ref()[0] >> 4
+    }
+
+    pub fn traffic_class(&self) -> u8 {
+        let slice = self.slice.as_ref();
+        (slice[0] << 4) | (slice[1] >> 4)
+    }
+
+    pub fn dscp(&self) -> u8 {
+        self.traffic_class() >> 2
+    }
+
+    pub fn ecn(&self) -> u8 {
+        self.traffic_class() & 0b11
+    }
+
+    pub fn flow_label(&self) -> u32 {
+        let slice = self.slice.as_ref();
+        u32::from_be_bytes([0, slice[1] & 0xF, slice[2], slice[3]])
+    }
+
+    pub fn payload_length(&self) -> u16 {
+        u16::from_be_bytes(*self.slice.as_ref()[4..6].first_chunk::<2>().unwrap())
+    }
+
+    /// The protocol of the header that immediately follows the fixed header, which may be an
+    /// extension header rather than the upper-layer protocol
+    #[allow(clippy::result_unit_err)]
+    pub fn next_header(&self) -> Result<InetProtocol, ()> {
+        InetProtocol::try_from(self.slice.as_ref()[6])
+    }
+
+    pub fn next_header_u8(&self) -> u8 {
+        self.slice.as_ref()[6]
+    }
+
+    pub fn hop_limit(&self) -> u8 {
+        self.slice.as_ref()[7]
+    }
+
+    pub fn source(&self) -> &[u8; 16] {
+        self.slice.as_ref()[8..24].first_chunk::<16>().unwrap()
+    }
+
+    pub fn source_u128(&self) -> u128 {
+        u128::from_be_bytes(*self.slice.as_ref()[8..24].first_chunk::<16>().unwrap())
+    }
+
+    pub fn destination(&self) -> &[u8; 16] {
+        self.slice.as_ref()[24..40].first_chunk::<16>().unwrap()
+    }
+
+    pub fn destination_u128(&self) -> u128 {
+        u128::from_be_bytes(*self.slice.as_ref()[24..40].first_chunk::<16>().unwrap())
+    }
+
+    pub fn size_usize(&self) -> usize {
+        IPv6::MIN_LEN
+    }
+
+    /// Extension headers found at the start of `payload`, the slice that follows this header
+    pub fn extensions<'pkt>(&self, payload: &'pkt [u8]) -> Extensions<'pkt> {
+        Extensions::new(self.next_header_u8(), payload)
+    }
+}
+
+/// Upper bound on the number of extension headers walked before giving up, keeps the loop
+/// bounded so the verifier accepts it inside XDP programs
+pub const MAX_EXTENSION_HEADERS: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ExtensionHeader {
+    HopByHop,
+    Routing,
+    Fragment,
+    DestinationOptions,
+    AuthenticationHeader,
+    EncapsulatingSecurityPayload,
+}
+
+impl ExtensionHeader {
+    pub fn from_u8(value: u8) -> Option<Self> {
+        Some(match value {
+            0 => Self::HopByHop,
+            43 => Self::Routing,
+            44 => Self::Fragment,
+            60 => Self::DestinationOptions,
+            51 => Self::AuthenticationHeader,
+            50 => Self::EncapsulatingSecurityPayload,
+            _ => return None,
+        })
+    }
+}
+
+impl From<ExtensionHeader> for InetProtocol {
+    fn from(value: ExtensionHeader) -> Self {
+        match value {
+            ExtensionHeader::HopByHop => InetProtocol::IPV6_HEADER_HOP_BY_HOP,
+            ExtensionHeader::Routing => InetProtocol::IPV6_ROUTE_HEADER,
+            ExtensionHeader::Fragment => InetProtocol::IPV6_FRAGMENTATION_HEADER,
+            ExtensionHeader::DestinationOptions => InetProtocol::IPV6_DESTINATION_OPTIONS,
+            ExtensionHeader::AuthenticationHeader => InetProtocol::AUTHENTICATION_HEADER,
+            ExtensionHeader::EncapsulatingSecurityPayload => {
+                InetProtocol::ENCAPSULATING_SECURITY_PAYLOAD
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExtensionError {
+    /// The extension header does not fit in the remaining bytes, holds the offset it started at
+    Truncated(usize),
+    TooManyHeaders,
+    UnknownProtocol(u8),
+}
+
+pub struct Extension<'pkt> {
+    kind: ExtensionHeader,
+    slice: &'pkt [u8],
+}
+
+impl<'pkt> Extension<'pkt> {
+    pub fn kind(&self) -> ExtensionHeader {
+        self.kind
+    }
+
+    pub fn slice(&self) -> &'pkt [u8] {
+        self.slice
+    }
+
+    /// ESP headers do not carry a next header in the clear, for them this is always
+    /// `ENCAPSULATING_SECURITY_PAYLOAD`
+    pub fn next_header_u8(&self) -> u8 {
+        match self.kind {
+            ExtensionHeader::EncapsulatingSecurityPayload => 50,
+            _ => self.slice[0],
+        }
+    }
+
+    /// Only meaningful for `ExtensionHeader::Fragment`
+    pub fn fragment_offset(&self) -> u16 {
+        u16::from_be_bytes(*self.slice[2..4].first_chunk::<2>().unwrap()) >> 3
+    }
+
+    /// Only meaningful for `ExtensionHeader::Fragment`
+    pub fn more_fragments(&self) -> bool {
+        self.slice[3] & 1 == 1
+    }
+
+    /// Only meaningful for `ExtensionHeader::Fragment`
+    pub fn identification(&self) -> u32 {
+        u32::from_be_bytes(*self.slice[4..8].first_chunk::<4>().unwrap())
+    }
+}
+
+/// Walks the extension header chain that follows an IPv6 fixed header.
+///
+/// The walk stops at the first header that is not an extension header, at an ESP header (its
+/// payload is encrypted) and at a fragment header with a non zero offset (there is no upper-layer
+/// header in non-first fragments). At most `MAX_EXTENSION_HEADERS` headers are visited.
+pub struct Extensions<'pkt> {
+    slice: &'pkt [u8],
+    next_header: u8,
+    offset: usize,
+    count: usize,
+    done: bool,
+}
+
+impl<'pkt> Extensions<'pkt> {
+    pub fn new(next_header: u8, slice: &'pkt [u8]) -> Self {
+        Self {
+            slice,
+            next_header,
+            offset: 0,
+            count: 0,
+            done: false,
+        }
+    }
+
+    /// Protocol number of the header at `offset()`
+    pub fn next_header_u8(&self) -> u8 {
+        self.next_header
+    }
+
+    /// Offset of the next unvisited header relative to the end of the IPv6 fixed header
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Consumes the chain and returns the upper-layer protocol together with the offset of its
+    /// header, relative to the end of the IPv6 fixed header
+    pub fn upper_layer(mut self) -> Result<(InetProtocol, usize), ExtensionError> {
+        for _ in 0..=MAX_EXTENSION_HEADERS {
+            match self.next() {
+                Some(Ok(_)) => continue,
+                Some(Err(e)) => return Err(e),
+                None => {
+                    return InetProtocol::try_from(self.next_header)
+                        .map(|p| (p, self.offset))
+                        .map_err(|_| ExtensionError::UnknownProtocol(self.next_header))
+                }
+            }
+        }
+
+        Err(ExtensionError::TooManyHeaders)
+    }
+}
+
+impl<'pkt> Iterator for Extensions<'pkt> {
+    type Item = Result<Extension<'pkt>, ExtensionError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let kind = ExtensionHeader::from_u8(self.next_header)?;
+
+        if self.count >= MAX_EXTENSION_HEADERS {
+            self.done = true;
+            return Some(Err(ExtensionError::TooManyHeaders));
+        }
+
+        let rem = &self.slice[self.offset..];
+        if rem.len() < 8 {
+            self.done = true;
+            return Some(Err(ExtensionError::Truncated(self.offset)));
+        }
+
+        let len = match kind {
+            ExtensionHeader::Fragment | ExtensionHeader::EncapsulatingSecurityPayload => 8,
+            ExtensionHeader::AuthenticationHeader => (rem[1] as usize + 2) * 4,
+            _ => (rem[1] as usize + 1) * 8,
+        };
+
+        if rem.len() < len {
+            self.done = true;
+            return Some(Err(ExtensionError::Truncated(self.offset)));
+        }
+
+        let ext = Extension {
+            kind,
+            slice: &rem[..len],
+        };
+
+        self.count += 1;
+
+        match kind {
+            ExtensionHeader::EncapsulatingSecurityPayload => self.done = true,
+            ExtensionHeader::Fragment if ext.fragment_offset() != 0 => self.done = true,
+            _ => {
+                self.next_header = ext.next_header_u8();
+                self.offset += len;
+            }
+        }
+
+        Some(Ok(ext))
+    }
+}
+
+/// `IP6 (class 0x0, flowlabel 0x00000, hlim 64, next-header UDP (17), payload length 10) ::1 > ::2`
+impl<P: AsRef<[u8]>> fmt::Display for IPv6<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "IP6 (class {:#x}, flowlabel {:#07x}, hlim {}, next-header {}, payload length {}) {} > {}",
+            self.traffic_class(),
+            self.flow_label(),
+            self.hop_limit(),
+            ProtocolName(self.next_header_u8()),
+            self.payload_length(),
+            Ipv6Addr::from(*self.source()),
+            Ipv6Addr::from(*self.destination()),
+        )
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<P: AsRef<[u8]>> serde::Serialize for IPv6<P> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("IPv6", 7)?;
+        state.serialize_field("source", &Ipv6Addr::from(*self.source()))?;
+        state.serialize_field("destination", &Ipv6Addr::from(*self.destination()))?;
+        state.serialize_field("traffic_class", &self.traffic_class())?;
+        state.serialize_field("flow_label", &self.flow_label())?;
+        state.serialize_field("payload_length", &self.payload_length())?;
+        state.serialize_field("next_header", &self.next_header_u8())?;
+        state.serialize_field("hop_limit", &self.hop_limit())?;
+        state.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::network::ipv6::{ExtensionError, ExtensionHeader, MAX_EXTENSION_HEADERS};
+    use crate::network::{IPv6, InetProtocol};
+
+    const PACKET: [u8; 40] = [
+        0x60, 0xA1, 0x23, 0x45, 0x00, 0x14, 0x06, 0x40, 0x20, 0x01, 0x0d, 0xb8, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x20, 0x01, 0x0d, 0xb8, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02,
+    ];
+
+    #[test]
+    fn create_ref() {
+        let (ip6, rem) = IPv6::new(&PACKET).unwrap();
+
+        assert_eq!(rem.len(), 0);
+        assert_eq!(ip6.version(), 6);
+        assert_eq!(ip6.traffic_class(), 0x0A);
+        assert_eq!(ip6.flow_label(), 0x12345);
+        assert_eq!(ip6.payload_length(), 20);
+        assert_eq!(ip6.next_header(), Ok(InetProtocol::TCP));
+        assert_eq!(ip6.hop_limit(), 64);
+        assert_eq!(ip6.source_u128(), 0x2001_0db8_0000_0000_0000_0000_0000_0001);
+        assert_eq!(
+            ip6.destination_u128(),
+            0x2001_0db8_0000_0000_0000_0000_0000_0002
+        );
+    }
+
+    #[test]
+    fn create_mut() {
+        let mut packet = PACKET;
+        let (mut ip6, _) = IPv6::new_mut(&mut packet).unwrap();
+
+        ip6.set_traffic_class(0xB8);
+        ip6.set_flow_label(0xFFFFF);
+        ip6.set_hop_limit(1);
+        ip6.set_next_header(InetProtocol::UDP);
+        ip6.set_source_u128(1);
+        ip6.set_destination(&[0xFF; 16]);
+
+        assert_eq!(ip6.version(), 6);
+        assert_eq!(ip6.traffic_class(), 0xB8);
+        assert_eq!(ip6.dscp(), 46);
+        assert_eq!(ip6.ecn(), 0);
+        assert_eq!(ip6.flow_label(), 0xFFFFF);
+        assert_eq!(ip6.hop_limit(), 1);
+        assert_eq!(ip6.next_header(), Ok(InetProtocol::UDP));
+        assert_eq!(ip6.source_u128(), 1);
+        assert_eq!(ip6.destination_u128(), u128::MAX);
+    }
+
+    #[test]
+    fn wrong_version() {
+        let mut packet = PACKET;
+        packet[0] = 0x45;
+
+        assert!(IPv6::new(&packet).is_err());
+    }
+
+    #[test]
+    fn extension_chain() {
+        let mut packet = [0u8; 40 + 8 + 8 + 4];
+        packet[..40].copy_from_slice(&PACKET);
+        packet[6] = 0; // Hop-by-Hop
+        packet[40] = 44; // -> Fragment
+        packet[48] = 6; // -> TCP
+        packet[51] = 1; // offset 0, more fragments
+
+        let (ip6, rem) = IPv6::new(&packet).unwrap();
+        let mut extensions = ip6.extensions(rem);
+
+        assert_eq!(
+            extensions.next().unwrap().unwrap().kind(),
+            ExtensionHeader::HopByHop
+        );
+        assert_eq!(
+            extensions.next().unwrap().unwrap().kind(),
+            ExtensionHeader::Fragment
+        );
+        assert!(extensions.next().is_none());
+        assert_eq!(
+            ip6.extensions(rem).upper_layer(),
+            Ok((InetProtocol::TCP, 16))
+        );
+    }
+
+    #[test]
+    fn extension_non_first_fragment() {
+        let mut packet = [0u8; 40 + 8];
+        packet[..40].copy_from_slice(&PACKET);
+        packet[6] = 44;
+        packet[40] = 6;
+        packet[43] = 0x08; // offset 1
+
+        let (ip6, rem) = IPv6::new(&packet).unwrap();
+
+        assert_eq!(
+            ip6.extensions(rem).upper_layer(),
+            Ok((InetProtocol::IPV6_FRAGMENTATION_HEADER, 0))
+        );
+    }
+
+    #[test]
+    fn extension_errors() {
+        let mut packet = [0u8; 40 + 4];
+        packet[..40].copy_from_slice(&PACKET);
+        packet[6] = 60;
+
+        let (ip6, rem) = IPv6::new(&packet).unwrap();
+        assert_eq!(
+            ip6.extensions(rem).upper_layer(),
+            Err(ExtensionError::Truncated(0))
+        );
+
+        let mut chain = [0u8; 8 * (MAX_EXTENSION_HEADERS + 1)];
+        for i in 0..=MAX_EXTENSION_HEADERS {
+            chain[i * 8] = 60;
+        }
+        assert_eq!(
+            ip6.extensions(&chain).upper_layer(),
+            Err(ExtensionError::TooManyHeaders)
+        );
+    }
+}
@@ -1,5 +1,10 @@
+pub use cidr::{IpNetwork, LpmKey};
 pub use ipnum::*;
 pub use ipv4::*;
+pub use ipv6::IPv6;
 
-pub mod ipv4;
+pub mod cidr;
 pub mod ipnum;
+pub mod ipv4;
+pub mod ipv4opt;
+pub mod ipv6;
@@ -0,0 +1,967 @@
+//! Walks every header netp knows about in one call.
+//!
+//! `Packet::new` stops at the first malformed header and only ever loops over the IPv6 extension
+//! chain, which is capped at `MAX_EXTENSION_HEADERS`, so it can be used from XDP programs. With
+//! the `std` feature `Packet::dissect` also keeps the layers parsed before the error.
+//!
+//! Tunnels are not followed automatically, `Packet::decapsulate` dissects the inner packet of a
+//! GRE, VXLAN or Geneve packet one level at a time.
+
+use core::fmt;
+use core::net::IpAddr;
+
+use crate::link::{arp, eth, Arp, EtherType, Ethernet, VlanTag};
+use crate::network::ipnum::ProtocolName;
+use crate::network::ipv6::{self, ExtensionError, ExtensionHeader, MAX_EXTENSION_HEADERS};
+use crate::network::{ipv4, IPv4, IPv6, InetProtocol};
+use crate::transport::icmp::{self, Icmp};
+use crate::transport::icmpv6::{self, Icmpv6};
+use crate::transport::igmp::{self, Igmp};
+use crate::transport::sctp::{self, Sctp};
+use crate::transport::tcp::{self, Tcp};
+use crate::transport::udp::{self, Udp};
+use crate::tunnel::geneve::{self, Geneve};
+use crate::tunnel::gre::{self, Gre};
+use crate::tunnel::vxlan::{self, Vxlan};
+
+pub enum Network<'pkt> {
+    IPv4(IPv4<&'pkt [u8]>),
+    IPv6(IPv6<&'pkt [u8]>),
+    Arp(Arp<&'pkt [u8]>),
+}
+
+pub enum Transport<'pkt> {
+    Tcp(Tcp<&'pkt [u8]>),
+    Udp(Udp<&'pkt [u8]>),
+    Icmp(Icmp<&'pkt [u8]>),
+    Icmpv6(Icmpv6<&'pkt [u8]>),
+    Sctp(Sctp<&'pkt [u8]>),
+    Igmp(Igmp<&'pkt [u8]>),
+}
+
+pub enum Tunnel<'pkt> {
+    Gre(Gre<&'pkt [u8]>),
+    Vxlan(Vxlan<&'pkt [u8]>),
+    Geneve(Geneve<&'pkt [u8]>),
+}
+
+impl Tunnel<'_> {
+    /// Virtual network identifier of VXLAN and Geneve, or the key of GRE
+    pub fn vni(&self) -> Option<u32> {
+        match self {
+            Tunnel::Gre(gre) => gre.key(),
+            Tunnel::Vxlan(vxlan) => Some(vxlan.vni()),
+            Tunnel::Geneve(geneve) => Some(geneve.vni()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Layer {
+    Link,
+    Network,
+    Transport,
+    Tunnel,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Ethernet(eth::Error),
+    Arp(arp::Error),
+    IPv4(ipv4::Error),
+    IPv6(ipv6::Error),
+    IPv6Extension(ExtensionError),
+    /// The first nibble of a raw IP packet is neither 4 nor 6
+    UnknownVersion(u8),
+    Tcp(tcp::Error),
+    Udp(udp::Error),
+    Icmp(icmp::Error),
+    Icmpv6(icmpv6::Error),
+    Sctp(sctp::Error),
+    Igmp(igmp::Error),
+    Gre(gre::Error),
+    Vxlan(vxlan::Error),
+    Geneve(geneve::Error),
+}
+
+impl Error {
+    pub fn layer(&self) -> Layer {
+        match self {
+            Error::Ethernet(_) => Layer::Link,
+            Error::Arp(_)
+            | Error::IPv4(_)
+            | Error::IPv6(_)
+            | Error::IPv6Extension(_)
+            | Error::UnknownVersion(_) => Layer::Network,
+            Error::Tcp(_)
+            | Error::Udp(_)
+            | Error::Icmp(_)
+            | Error::Icmpv6(_)
+            | Error::Sctp(_)
+            | Error::Igmp(_) => Layer::Transport,
+            Error::Gre(_) | Error::Vxlan(_) | Error::Geneve(_) => Layer::Tunnel,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::Ethernet(e) => write!(f, "invalid Ethernet header: {e:?}"),
+            Error::Arp(e) => write!(f, "invalid ARP packet: {e:?}"),
+            Error::IPv4(e) => write!(f, "invalid IPv4 header: {e:?}"),
+            Error::IPv6(e) => write!(f, "invalid IPv6 header: {e:?}"),
+            Error::IPv6Extension(e) => write!(f, "invalid IPv6 extension header: {e:?}"),
+            Error::UnknownVersion(v) => write!(f, "unknown IP version {v}"),
+            Error::Tcp(e) => write!(f, "invalid TCP header: {e:?}"),
+            Error::Udp(e) => write!(f, "invalid UDP header: {e:?}"),
+            Error::Icmp(e) => write!(f, "invalid ICMP header: {e:?}"),
+            Error::Icmpv6(e) => write!(f, "invalid ICMPv6 header: {e:?}"),
+            Error::Sctp(e) => write!(f, "invalid SCTP header: {e:?}"),
+            Error::Igmp(e) => write!(f, "invalid IGMP message: {e:?}"),
+            Error::Gre(e) => write!(f, "invalid GRE header: {e:?}"),
+            Error::Vxlan(e) => write!(f, "invalid VXLAN header: {e:?}"),
+            Error::Geneve(e) => write!(f, "invalid Geneve header: {e:?}"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+/// Every layer found in a packet. Layers netp can not parse are left as `None` and their bytes
+/// are part of `payload`
+pub struct Packet<'pkt> {
+    pub link: Option<Ethernet<&'pkt [u8]>>,
+    pub vlans: [Option<VlanTag>; 2],
+    pub network: Option<Network<'pkt>>,
+    /// Upper-layer protocol, past any IPv6 extension header
+    pub protocol: Option<InetProtocol>,
+    pub transport: Option<Transport<'pkt>>,
+    /// Offset of `payload` from the start of the packet
+    pub payload_offset: usize,
+    /// Bytes after the last parsed header, trimmed to the length announced by the IP header
+    pub payload: &'pkt [u8],
+}
+
+impl<'pkt> Packet<'pkt> {
+    pub(crate) fn empty(slice: &'pkt [u8]) -> Self {
+        Self {
+            link: None,
+            vlans: [None, None],
+            network: None,
+            protocol: None,
+            transport: None,
+            payload_offset: 0,
+            payload: slice,
+        }
+    }
+
+    /// Dissects a frame starting at its Ethernet header
+    pub fn new(slice: &'pkt [u8]) -> Result<Self, Error> {
+        let mut packet = Self::empty(slice);
+        packet.parse_link()?;
+        Ok(packet)
+    }
+
+    /// Dissects a packet starting at its IP header, as read from a raw or tun socket
+    pub fn new_ip(slice: &'pkt [u8]) -> Result<Self, Error> {
+        let mut packet = Self::empty(slice);
+        packet.parse_ip()?;
+        Ok(packet)
+    }
+
+    /// Like `new`, but the layers parsed before a malformed header are kept
+    #[cfg(feature = "std")]
+    pub fn dissect(slice: &'pkt [u8]) -> (Self, Option<Error>) {
+        let mut packet = Self::empty(slice);
+        let error = packet.parse_link().err();
+        (packet, error)
+    }
+
+    /// Like `new_ip`, but the layers parsed before a malformed header are kept
+    #[cfg(feature = "std")]
+    pub fn dissect_ip(slice: &'pkt [u8]) -> (Self, Option<Error>) {
+        let mut packet = Self::empty(slice);
+        let error = packet.parse_ip().err();
+        (packet, error)
+    }
+
+    /// Dissects the packet carried by a GRE, VXLAN or Geneve tunnel. VXLAN and Geneve are
+    /// recognized by their UDP destination port. `None` when the packet is not a tunnel, the
+    /// offsets of the inner packet are relative to the start of the inner frame
+    pub fn decapsulate(&self) -> Option<Result<(Tunnel<'pkt>, Packet<'pkt>), Error>> {
+        match (&self.transport, self.protocol) {
+            (Some(Transport::Udp(udp)), _) if udp.destination() == Vxlan::PORT => {
+                Some(self.decapsulate_vxlan())
+            }
+            (Some(Transport::Udp(udp)), _) if udp.destination() == Geneve::PORT => {
+                Some(self.decapsulate_geneve())
+            }
+            // Non-first fragments carry the protocol but no GRE header
+            (None, Some(InetProtocol::GRE))
+                if self.ipv4().is_none_or(|ip4| ip4.fragment_offset_u16() == 0) =>
+            {
+                Some(self.decapsulate_gre())
+            }
+            _ => None,
+        }
+    }
+
+    fn decapsulate_gre(&self) -> Result<(Tunnel<'pkt>, Packet<'pkt>), Error> {
+        let (gre, rem) = Gre::new(self.payload).map_err(Error::Gre)?;
+        let inner = Self::inner(gre.protocol_type(), rem)?;
+
+        Ok((Tunnel::Gre(gre), inner))
+    }
+
+    fn decapsulate_vxlan(&self) -> Result<(Tunnel<'pkt>, Packet<'pkt>), Error> {
+        let (vxlan, rem) = Vxlan::new(self.payload).map_err(Error::Vxlan)?;
+        let inner = Self::new(rem)?;
+
+        Ok((Tunnel::Vxlan(vxlan), inner))
+    }
+
+    fn decapsulate_geneve(&self) -> Result<(Tunnel<'pkt>, Packet<'pkt>), Error> {
+        let (geneve, rem) = Geneve::new(self.payload).map_err(Error::Geneve)?;
+        let inner = Self::inner(geneve.protocol_type(), rem)?;
+
+        Ok((Tunnel::Geneve(geneve), inner))
+    }
+
+    /// Inner packets of other protocols are left unparsed in `payload`
+    fn inner(protocol_type: EtherType, slice: &'pkt [u8]) -> Result<Self, Error> {
+        let mut packet = Self::empty(slice);
+
+        match protocol_type {
+            EtherType::TransparentEthernetBridging => packet.parse_link()?,
+            EtherType::IPv4 => packet.parse_ipv4()?,
+            EtherType::IPv6 => packet.parse_ipv6()?,
+            _ => (),
+        }
+
+        Ok(packet)
+    }
+
+    fn advance(&mut self, size: usize, rem: &'pkt [u8]) {
+        self.payload_offset += size;
+        self.payload = rem;
+    }
+
+    fn parse_link(&mut self) -> Result<(), Error> {
+        let (eth, rem) = Ethernet::new(self.payload).map_err(Error::Ethernet)?;
+        let ethertype = eth.ethertype();
+
+        self.vlans = eth.vlan_tags();
+        self.advance(eth.size_usize(), rem);
+        self.link = Some(eth);
+
+        match ethertype {
+            EtherType::IPv4 => self.parse_ipv4(),
+            EtherType::IPv6 => self.parse_ipv6(),
+            EtherType::Arp => self.parse_arp(),
+            _ => Ok(()),
+        }
+    }
+
+    fn parse_ip(&mut self) -> Result<(), Error> {
+        match self.payload.first().map(|b| b >> 4) {
+            Some(4) => self.parse_ipv4(),
+            Some(6) => self.parse_ipv6(),
+            Some(version) => Err(Error::UnknownVersion(version)),
+            None => Err(Error::IPv4(ipv4::Error::InvalidSize(0))),
+        }
+    }
+
+    fn parse_arp(&mut self) -> Result<(), Error> {
+        let (arp, rem) = Arp::new(self.payload).map_err(Error::Arp)?;
+
+        self.advance(Arp::LEN, rem);
+        self.network = Some(Network::Arp(arp));
+        Ok(())
+    }
+
+    fn parse_ipv4(&mut self) -> Result<(), Error> {
+        let (ip4, rem) = IPv4::new(self.payload).map_err(Error::IPv4)?;
+        let size = ip4.size_usize();
+        let rem = trim(rem, (ip4.total_length() as usize).checked_sub(size));
+        let protocol = ip4.protocol_u8();
+        let has_transport = ip4.fragment_offset_u16() == 0;
+        let more_fragments = ip4.more_fragments();
+
+        self.advance(size, rem);
+        self.protocol = InetProtocol::try_from(protocol).ok();
+        self.network = Some(Network::IPv4(ip4));
+
+        if !has_transport {
+            return Ok(());
+        }
+
+        match protocol {
+            6 if more_fragments => self.parse_tcp_fragment(),
+            6 => self.parse_tcp(),
+            17 if more_fragments => self.parse_udp_fragment(),
+            17 => self.parse_udp(),
+            132 => self.parse_sctp(),
+            2 => {
+                let (igmp, rem) = Igmp::new(self.payload).map_err(Error::Igmp)?;
+                self.advance(igmp.slice().len(), rem);
+                self.transport = Some(Transport::Igmp(igmp));
+                Ok(())
+            }
+            1 => {
+                let (icmp, rem) = Icmp::new(self.payload).map_err(Error::Icmp)?;
+                self.advance(Icmp::MIN_LEN, rem);
+                self.transport = Some(Transport::Icmp(icmp));
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn parse_ipv6(&mut self) -> Result<(), Error> {
+        let (ip6, rem) = IPv6::new(self.payload).map_err(Error::IPv6)?;
+        let payload_length = match ip6.payload_length() {
+            // Jumbograms announce their length in a hop-by-hop option instead
+            0 => None,
+            len => Some(len as usize),
+        };
+        let rem = trim(rem, payload_length);
+        let mut extensions = ip6.extensions(rem);
+        let mut more_fragments = false;
+
+        self.advance(IPv6::MIN_LEN, rem);
+        self.network = Some(Network::IPv6(ip6));
+
+        for _ in 0..=MAX_EXTENSION_HEADERS {
+            match extensions.next() {
+                Some(Ok(ext)) => {
+                    more_fragments |=
+                        ext.kind() == ExtensionHeader::Fragment && ext.more_fragments();
+                }
+                Some(Err(e)) => return Err(Error::IPv6Extension(e)),
+                None => break,
+            }
+        }
+
+        let protocol = extensions.next_header_u8();
+        let offset = extensions.offset();
+
+        self.advance(offset, &rem[offset..]);
+
+        // The walk stopped at ESP or at a non-first fragment, there is no upper-layer header
+        if ExtensionHeader::from_u8(protocol).is_some() {
+            return Ok(());
+        }
+
+        self.protocol = InetProtocol::try_from(protocol).ok();
+
+        match protocol {
+            6 if more_fragments => self.parse_tcp_fragment(),
+            6 => self.parse_tcp(),
+            17 if more_fragments => self.parse_udp_fragment(),
+            17 => self.parse_udp(),
+            132 => self.parse_sctp(),
+            58 => {
+                let (icmp, rem) = Icmpv6::new(self.payload).map_err(Error::Icmpv6)?;
+                self.advance(Icmpv6::MIN_LEN, rem);
+                self.transport = Some(Transport::Icmpv6(icmp));
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn parse_tcp(&mut self) -> Result<(), Error> {
+        let (tcp, rem) = Tcp::new(self.payload).map_err(Error::Tcp)?;
+
+        self.advance(tcp.size_usize(), rem);
+        self.transport = Some(Transport::Tcp(tcp));
+        Ok(())
+    }
+
+    /// A first fragment may end before the TCP header does, it then has no transport header like
+    /// the fragments following it
+    fn parse_tcp_fragment(&mut self) -> Result<(), Error> {
+        match Tcp::new(self.payload) {
+            Ok((tcp, rem)) => {
+                self.advance(tcp.size_usize(), rem);
+                self.transport = Some(Transport::Tcp(tcp));
+                Ok(())
+            }
+            Err(tcp::Error::InvalidSize(_) | tcp::Error::InvalidSizeForOffset(..)) => Ok(()),
+            Err(e) => Err(Error::Tcp(e)),
+        }
+    }
+
+    fn parse_udp(&mut self) -> Result<(), Error> {
+        let (udp, rem) = Udp::new(self.payload).map_err(Error::Udp)?;
+
+        self.advance(Udp::MIN_LEN, rem);
+        self.transport = Some(Transport::Udp(udp));
+        Ok(())
+    }
+
+    /// The length field of a fragmented datagram goes past the end of the first fragment
+    fn parse_udp_fragment(&mut self) -> Result<(), Error> {
+        let (udp, rem) = Udp::new_fragment(self.payload).map_err(Error::Udp)?;
+
+        self.advance(Udp::MIN_LEN, rem);
+        self.transport = Some(Transport::Udp(udp));
+        Ok(())
+    }
+
+    fn parse_sctp(&mut self) -> Result<(), Error> {
+        let (sctp, rem) = Sctp::new(self.payload).map_err(Error::Sctp)?;
+
+        self.advance(Sctp::MIN_LEN, rem);
+        self.transport = Some(Transport::Sctp(sctp));
+        Ok(())
+    }
+
+    pub fn ipv4(&self) -> Option<&IPv4<&'pkt [u8]>> {
+        match &self.network {
+            Some(Network::IPv4(ip4)) => Some(ip4),
+            _ => None,
+        }
+    }
+
+    pub fn ipv6(&self) -> Option<&IPv6<&'pkt [u8]>> {
+        match &self.network {
+            Some(Network::IPv6(ip6)) => Some(ip6),
+            _ => None,
+        }
+    }
+
+    pub fn tcp(&self) -> Option<&Tcp<&'pkt [u8]>> {
+        match &self.transport {
+            Some(Transport::Tcp(tcp)) => Some(tcp),
+            _ => None,
+        }
+    }
+
+    pub fn udp(&self) -> Option<&Udp<&'pkt [u8]>> {
+        match &self.transport {
+            Some(Transport::Udp(udp)) => Some(udp),
+            _ => None,
+        }
+    }
+
+    /// Source and destination ports of a TCP, UDP or SCTP packet
+    pub fn ports(&self) -> Option<(u16, u16)> {
+        match &self.transport {
+            Some(Transport::Tcp(tcp)) => Some((tcp.source(), tcp.destination())),
+            Some(Transport::Udp(udp)) => Some((udp.source(), udp.destination())),
+            Some(Transport::Sctp(sctp)) => Some((sctp.source(), sctp.destination())),
+            _ => None,
+        }
+    }
+}
+
+/// Cuts the bytes past the length announced by a header, e.g. Ethernet padding. Lengths larger
+/// than what was captured are ignored
+fn trim(slice: &[u8], len: Option<usize>) -> &[u8] {
+    match len {
+        Some(len) if len <= slice.len() => &slice[..len],
+        _ => slice,
+    }
+}
+
+impl fmt::Display for Network<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Network::IPv4(ip4) => ip4.fmt(f),
+            Network::IPv6(ip6) => ip6.fmt(f),
+            Network::Arp(arp) => arp.fmt(f),
+        }
+    }
+}
+
+impl fmt::Display for Transport<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Transport::Tcp(tcp) => tcp.fmt(f),
+            Transport::Udp(udp) => udp.fmt(f),
+            Transport::Icmp(icmp) => icmp.fmt(f),
+            Transport::Icmpv6(icmp) => icmp.fmt(f),
+            Transport::Sctp(sctp) => sctp.fmt(f),
+            Transport::Igmp(igmp) => igmp.fmt(f),
+        }
+    }
+}
+
+impl fmt::Display for Tunnel<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Tunnel::Gre(gre) => gre.fmt(f),
+            Tunnel::Vxlan(vxlan) => vxlan.fmt(f),
+            Tunnel::Geneve(geneve) => geneve.fmt(f),
+        }
+    }
+}
+
+/// One line in the style of tcpdump, e.g.
+/// `IP 10.0.0.1.12345 > 10.0.0.2.80: Flags [S], seq 1, win 1024, length 0`
+impl fmt::Display for Packet<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (family, source, destination) = match &self.network {
+            Some(Network::IPv4(ip4)) => (
+                "IP",
+                IpAddr::from(*ip4.source()),
+                IpAddr::from(*ip4.destination()),
+            ),
+            Some(Network::IPv6(ip6)) => (
+                "IP6",
+                IpAddr::from(*ip6.source()),
+                IpAddr::from(*ip6.destination()),
+            ),
+            Some(Network::Arp(arp)) => return arp.fmt(f),
+            None => {
+                if let Some(eth) = &self.link {
+                    write!(f, "{eth}, ")?;
+                }
+
+                return write!(f, "length {}", self.payload.len());
+            }
+        };
+
+        match &self.transport {
+            Some(Transport::Tcp(tcp)) => {
+                write!(f, "{family} {source}.{} > ", tcp.source())?;
+                write!(f, "{destination}.{}: ", tcp.destination())?;
+                tcp.fmt_details(f)?;
+            }
+            // The UDP details already hold the length
+            Some(Transport::Udp(udp)) => {
+                write!(f, "{family} {source}.{} > ", udp.source())?;
+                write!(f, "{destination}.{}: ", udp.destination())?;
+                return udp.fmt_details(f);
+            }
+            Some(Transport::Icmp(icmp)) => write!(f, "{family} {source} > {destination}: {icmp}")?,
+            Some(Transport::Icmpv6(icmp)) => {
+                write!(f, "{family} {source} > {destination}: {icmp}")?
+            }
+            Some(Transport::Sctp(sctp)) => {
+                write!(f, "{family} {source}.{} > ", sctp.source())?;
+                write!(f, "{destination}.{}: SCTP", sctp.destination())?;
+            }
+            Some(Transport::Igmp(igmp)) => write!(f, "{family} {source} > {destination}: {igmp}")?,
+            None => {
+                write!(f, "{family} {source} > {destination}: ")?;
+
+                match self.protocol {
+                    Some(protocol) => write!(f, "{}", ProtocolName(protocol.into()))?,
+                    None => f.write_str("unknown")?,
+                }
+            }
+        }
+
+        write!(f, ", length {}", self.payload.len())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Network<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Network::IPv4(ip4) => serializer.serialize_newtype_variant("Network", 0, "IPv4", ip4),
+            Network::IPv6(ip6) => serializer.serialize_newtype_variant("Network", 1, "IPv6", ip6),
+            Network::Arp(arp) => serializer.serialize_newtype_variant("Network", 2, "Arp", arp),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Transport<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Transport::Tcp(tcp) => serializer.serialize_newtype_variant("Transport", 0, "Tcp", tcp),
+            Transport::Udp(udp) => serializer.serialize_newtype_variant("Transport", 1, "Udp", udp),
+            Transport::Icmp(icmp) => {
+                serializer.serialize_newtype_variant("Transport", 2, "Icmp", icmp)
+            }
+            Transport::Icmpv6(icmp) => {
+                serializer.serialize_newtype_variant("Transport", 3, "Icmpv6", icmp)
+            }
+            Transport::Sctp(sctp) => {
+                serializer.serialize_newtype_variant("Transport", 4, "Sctp", sctp)
+            }
+            Transport::Igmp(igmp) => {
+                serializer.serialize_newtype_variant("Transport", 5, "Igmp", igmp)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Tunnel<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Tunnel::Gre(gre) => serializer.serialize_newtype_variant("Tunnel", 0, "Gre", gre),
+            Tunnel::Vxlan(vxlan) => {
+                serializer.serialize_newtype_variant("Tunnel", 1, "Vxlan", vxlan)
+            }
+            Tunnel::Geneve(geneve) => {
+                serializer.serialize_newtype_variant("Tunnel", 2, "Geneve", geneve)
+            }
+        }
+    }
+}
+
+/// The payload itself is left out, only its length is kept
+#[cfg(feature = "serde")]
+impl serde::Serialize for Packet<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("Packet", 6)?;
+        state.serialize_field("link", &self.link)?;
+        state.serialize_field("network", &self.network)?;
+        state.serialize_field("protocol", &self.protocol)?;
+        state.serialize_field("transport", &self.transport)?;
+        state.serialize_field("payload_offset", &self.payload_offset)?;
+        state.serialize_field("payload_length", &self.payload.len())?;
+        state.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::PacketBuilder;
+    use crate::link::{EtherType, VlanTag};
+    use crate::network::InetProtocol;
+    use crate::packet::{Error, Layer, Network, Packet, Transport, Tunnel};
+    use crate::transport::tcp;
+
+    #[test]
+    fn vlan_ipv4_tcp() {
+        let mut buffer = [0u8; 80];
+        let len = PacketBuilder::new()
+            .ethernet([0x01; 6], [0x02; 6])
+            .vlan(VlanTag::new(EtherType::VlanTaggedFrame, 0, false, 7))
+            .ipv4([10, 0, 0, 1], [10, 0, 0, 2], 64)
+            .tcp(12345, 80, 1, 0, 0x02, 1024)
+            .write(&mut buffer, b"abc")
+            .unwrap();
+
+        // Trailing padding is not part of the payload
+        let packet = Packet::new(&buffer[..len + 4]).unwrap();
+
+        assert_eq!(packet.vlans[0].map(|t| t.vid()), Some(7));
+        assert_eq!(packet.ipv4().map(|ip4| ip4.ttl()), Some(64));
+        assert_eq!(packet.protocol, Some(InetProtocol::TCP));
+        assert_eq!(packet.ports(), Some((12345, 80)));
+        assert_eq!(packet.payload_offset, 18 + 20 + 20);
+        assert_eq!(packet.payload, b"abc");
+    }
+
+    #[test]
+    fn ipv4_udp_first_fragment() {
+        let mut buffer = [0u8; 64];
+        let len = PacketBuilder::new()
+            .ipv4([10, 0, 0, 1], [10, 0, 0, 2], 64)
+            .udp(5353, 53)
+            .write(&mut buffer, &[0xAA; 16])
+            .unwrap();
+
+        // More fragments, the UDP length covers the whole 3000 bytes datagram
+        buffer[6] = 0x20;
+        buffer[24..26].copy_from_slice(&3000u16.to_be_bytes());
+
+        let packet = Packet::new_ip(&buffer[..len]).unwrap();
+
+        assert_eq!(packet.protocol, Some(InetProtocol::UDP));
+        assert_eq!(packet.ports(), Some((5353, 53)));
+        assert_eq!(packet.payload, &[0xAA; 16]);
+
+        // Without more fragments the length field is checked
+        buffer[6] = 0x00;
+        assert!(matches!(Packet::new_ip(&buffer[..len]), Err(Error::Udp(_))));
+    }
+
+    #[test]
+    fn ipv4_tcp_short_first_fragment() {
+        let mut buffer = [0u8; 64];
+        let len = PacketBuilder::new()
+            .ipv4([10, 0, 0, 1], [10, 0, 0, 2], 64)
+            .tcp(40000, 22, 1, 0, 0x02, 1024)
+            .write(&mut buffer, &[])
+            .unwrap();
+
+        // More fragments, the first one ends after 12 bytes of the TCP header
+        buffer[6] = 0x20;
+        buffer[2..4].copy_from_slice(&32u16.to_be_bytes());
+
+        let packet = Packet::new_ip(&buffer[..32]).unwrap();
+        assert_eq!(packet.protocol, Some(InetProtocol::TCP));
+        assert!(packet.transport.is_none());
+        assert_eq!(packet.ports(), None);
+
+        // A data offset below the minimum is malformed rather than cut
+        buffer[32] = 0x40;
+        buffer[2..4].copy_from_slice(&(len as u16).to_be_bytes());
+        assert!(matches!(
+            Packet::new_ip(&buffer[..len]),
+            Err(Error::Tcp(tcp::Error::InvalidDataOffset(_)))
+        ));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn ipv4_udp_fragment_short_length() {
+        let mut buffer = [0u8; 64];
+        let len = PacketBuilder::new()
+            .ipv4([10, 0, 0, 1], [10, 0, 0, 2], 64)
+            .udp(5353, 53)
+            .write(&mut buffer, &[0xAA; 16])
+            .unwrap();
+
+        // More fragments with a UDP length smaller than the header
+        buffer[6] = 0x20;
+        buffer[24..26].copy_from_slice(&0u16.to_be_bytes());
+
+        let packet = Packet::new_ip(&buffer[..len]).unwrap();
+        assert!(packet.to_string().ends_with("UDP, length 0"));
+    }
+
+    #[test]
+    fn ipv6_extensions_udp() {
+        let mut buffer = [0u8; 80];
+        let len = PacketBuilder::new()
+            .ethernet([0x01; 6], [0x02; 6])
+            .ipv6([0x01; 16], [0x02; 16], 64)
+            .protocol(InetProtocol::IPV6_HEADER_HOP_BY_HOP)
+            .write(&mut buffer, &[17, 0, 1, 4, 0, 0, 0, 0])
+            .unwrap();
+
+        // Append a UDP header after the hop-by-hop header
+        let udp = [0x14, 0xE9, 0x00, 0x35, 0x00, 0x0A, 0x00, 0x00, 0xAA, 0xBB];
+        buffer[len..len + udp.len()].copy_from_slice(&udp);
+        buffer[18..20].copy_from_slice(&18u16.to_be_bytes());
+
+        let packet = Packet::new(&buffer[..len + udp.len()]).unwrap();
+
+        assert!(matches!(packet.network, Some(Network::IPv6(_))));
+        assert_eq!(packet.protocol, Some(InetProtocol::UDP));
+        assert_eq!(packet.ports(), Some((5353, 53)));
+        assert_eq!(packet.payload_offset, 14 + 40 + 8 + 8);
+        assert_eq!(packet.payload, &[0xAA, 0xBB]);
+    }
+
+    #[test]
+    fn raw_ip() {
+        let mut buffer = [0u8; 40];
+        let len = PacketBuilder::new()
+            .ipv4([10, 0, 0, 1], [10, 0, 0, 2], 64)
+            .udp(1, 2)
+            .write(&mut buffer, &[])
+            .unwrap();
+
+        let packet = Packet::new_ip(&buffer[..len]).unwrap();
+        assert!(packet.link.is_none());
+        assert!(matches!(packet.transport, Some(Transport::Udp(_))));
+
+        buffer[0] = 0x55;
+        assert!(matches!(
+            Packet::new_ip(&buffer),
+            Err(Error::UnknownVersion(5))
+        ));
+    }
+
+    #[test]
+    fn truncated_transport() {
+        let mut buffer = [0u8; 80];
+        let len = PacketBuilder::new()
+            .ethernet([0x01; 6], [0x02; 6])
+            .ipv4([10, 0, 0, 1], [10, 0, 0, 2], 64)
+            .tcp(12345, 80, 1, 0, 0x02, 1024)
+            .write(&mut buffer, &[])
+            .unwrap();
+
+        let error = Packet::new(&buffer[..len - 4]).err().unwrap();
+        assert_eq!(error.layer(), Layer::Transport);
+        assert!(matches!(error, Error::Tcp(_)));
+    }
+
+    #[test]
+    fn vxlan() {
+        let mut inner = [0u8; 64];
+        let inner_len = PacketBuilder::new()
+            .ethernet([0x03; 6], [0x04; 6])
+            .ipv4([192, 168, 0, 1], [192, 168, 0, 2], 64)
+            .udp(5353, 53)
+            .write(&mut inner[8..], b"abc")
+            .unwrap();
+        inner[..8].copy_from_slice(&[0x08, 0, 0, 0, 0x00, 0x00, 0x64, 0x00]);
+
+        let mut buffer = [0u8; 128];
+        let len = PacketBuilder::new()
+            .ethernet([0x01; 6], [0x02; 6])
+            .ipv4([10, 0, 0, 1], [10, 0, 0, 2], 64)
+            .udp(49152, 4789)
+            .write(&mut buffer, &inner[..8 + inner_len])
+            .unwrap();
+
+        let packet = Packet::new(&buffer[..len]).unwrap();
+        let (tunnel, inner) = packet.decapsulate().unwrap().unwrap();
+
+        assert!(matches!(tunnel, Tunnel::Vxlan(_)));
+        assert_eq!(tunnel.vni(), Some(100));
+        assert_eq!(
+            inner.ipv4().map(|ip4| *ip4.source()),
+            Some([192, 168, 0, 1])
+        );
+        assert_eq!(inner.ports(), Some((5353, 53)));
+        assert_eq!(inner.payload, b"abc");
+        assert!(inner.decapsulate().is_none());
+    }
+
+    #[test]
+    fn gre() {
+        let mut inner = [0u8; 64];
+        let inner_len = PacketBuilder::new()
+            .ipv4([192, 168, 0, 1], [192, 168, 0, 2], 64)
+            .tcp(12345, 80, 1, 0, 0x02, 1024)
+            .write(&mut inner[8..], &[])
+            .unwrap();
+        inner[..8].copy_from_slice(&[0x20, 0, 0x08, 0x00, 0, 0, 0x04, 0xD2]);
+
+        let mut buffer = [0u8; 128];
+        let len = PacketBuilder::new()
+            .ethernet([0x01; 6], [0x02; 6])
+            .ipv4([10, 0, 0, 1], [10, 0, 0, 2], 64)
+            .protocol(InetProtocol::GRE)
+            .write(&mut buffer, &inner[..8 + inner_len])
+            .unwrap();
+
+        let packet = Packet::new(&buffer[..len]).unwrap();
+        let (tunnel, inner) = packet.decapsulate().unwrap().unwrap();
+
+        assert_eq!(tunnel.vni(), Some(1234));
+        assert!(inner.link.is_none());
+        assert_eq!(inner.ports(), Some((12345, 80)));
+
+        // A truncated inner packet is reported as such
+        let packet = Packet::new(&buffer[..len - 4]).unwrap();
+        let error = packet.decapsulate().unwrap().err().unwrap();
+        assert_eq!(error.layer(), Layer::Transport);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn dissect_partial() {
+        let mut buffer = [0u8; 80];
+        let len = PacketBuilder::new()
+            .ethernet([0x01; 6], [0x02; 6])
+            .ipv4([10, 0, 0, 1], [10, 0, 0, 2], 64)
+            .udp(1, 2)
+            .write(&mut buffer, &[])
+            .unwrap();
+
+        let (packet, error) = Packet::dissect(&buffer[..len - 2]);
+        assert!(packet.ipv4().is_some());
+        assert!(packet.transport.is_none());
+        assert_eq!(
+            error.map(|e| e.to_string()).as_deref(),
+            Some("invalid UDP header: InvalidLength(6)")
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn summary() {
+        let mut buffer = [0u8; 80];
+        let len = PacketBuilder::new()
+            .ethernet([0x01; 6], [0x02; 6])
+            .ipv4([10, 0, 0, 1], [10, 0, 0, 2], 64)
+            .tcp(12345, 80, 1, 2, 0x12, 1024)
+            .write(&mut buffer, b"abc")
+            .unwrap();
+
+        let packet = Packet::new(&buffer[..len]).unwrap();
+        assert_eq!(
+            packet.to_string(),
+            "IP 10.0.0.1.12345 > 10.0.0.2.80: Flags [S.], seq 1, ack 2, win 1024, length 3"
+        );
+        assert_eq!(
+            packet.link.unwrap().to_string(),
+            "01:01:01:01:01:01 > 02:02:02:02:02:02, ethertype IPv4 (0x0800)"
+        );
+
+        let len = PacketBuilder::new()
+            .ipv6([0x01; 16], [0x02; 16], 64)
+            .udp(5353, 53)
+            .write(&mut buffer, b"ab")
+            .unwrap();
+
+        let packet = Packet::new_ip(&buffer[..len]).unwrap();
+        assert_eq!(
+            packet.to_string(),
+            "IP6 101:101:101:101:101:101:101:101.5353 > 202:202:202:202:202:202:202:202.53: \
+             UDP, length 2"
+        );
+        assert!(packet
+            .network
+            .unwrap()
+            .to_string()
+            .starts_with("IP6 (class 0x0, flowlabel 0x00000, hlim 64, next-header UDP (17)"));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serialize() {
+        let mut buffer = [0u8; 80];
+        let len = PacketBuilder::new()
+            .ethernet([0x01; 6], [0x02; 6])
+            .vlan(VlanTag::new(EtherType::VlanTaggedFrame, 0, false, 7))
+            .ipv4([10, 0, 0, 1], [10, 0, 0, 2], 64)
+            .udp(5353, 53)
+            .write(&mut buffer, b"ab")
+            .unwrap();
+
+        let packet = Packet::new(&buffer[..len]).unwrap();
+        let json = serde_json::to_value(&packet).unwrap();
+
+        assert_eq!(json["link"]["source"], "01:01:01:01:01:01");
+        assert_eq!(json["link"]["vlans"][0]["vid"], 7);
+        assert_eq!(json["network"]["IPv4"]["destination"], "10.0.0.2");
+        assert_eq!(json["network"]["IPv4"]["ttl"], 64);
+        assert_eq!(json["protocol"], "UDP");
+        assert_eq!(json["transport"]["Udp"]["destination"], 53);
+        assert_eq!(json["payload_length"], 2);
+    }
+
+    #[test]
+    fn sctp_and_igmp() {
+        let sctp = [
+            0x0B, 0x59, 0x0B, 0x59, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0B, 0x00,
+            0x00, 0x04,
+        ];
+
+        let mut buffer = [0u8; 80];
+        let len = PacketBuilder::new()
+            .ipv4([10, 0, 0, 1], [10, 0, 0, 2], 64)
+            .protocol(InetProtocol::SCTP)
+            .write(&mut buffer, &sctp)
+            .unwrap();
+
+        let packet = Packet::new_ip(&buffer[..len]).unwrap();
+        assert!(matches!(packet.transport, Some(Transport::Sctp(_))));
+        assert_eq!(packet.ports(), Some((2905, 2905)));
+        assert_eq!(packet.payload.len(), 4);
+
+        let len = PacketBuilder::new()
+            .ipv4([10, 0, 0, 1], [224, 0, 0, 22], 1)
+            .protocol(InetProtocol::IGMP)
+            .write(&mut buffer, &[0x16, 0, 0, 0, 239, 1, 1, 1])
+            .unwrap();
+
+        let packet = Packet::new_ip(&buffer[..len]).unwrap();
+        match &packet.transport {
+            Some(Transport::Igmp(igmp)) => assert_eq!(igmp.group_address(), &[239, 1, 1, 1]),
+            _ => panic!("expected an IGMP message"),
+        }
+        assert!(packet.ports().is_none());
+    }
+}
@@ -0,0 +1,210 @@
+//! Classic pcap and pcapng capture files.
+//!
+//! `Reader` detects the format from the first bytes of the file and yields one `Record` per
+//! packet, which can be dissected with `Record::packet`. Timestamps keep their full resolution,
+//! nanosecond captures included.
+
+use std::fmt;
+use std::io;
+use std::time::Duration;
+
+use crate::packet::{self, Packet};
+
+mod reader;
+mod writer;
+
+pub use reader::Reader;
+pub use writer::{PcapWriter, PcapngWriter};
+
+const PCAP_MAGIC_MICROS: u32 = 0xA1B2C3D4;
+const PCAP_MAGIC_NANOS: u32 = 0xA1B23C4D;
+const PCAPNG_BYTE_ORDER_MAGIC: u32 = 0x1A2B3C4D;
+
+const BLOCK_SECTION_HEADER: u32 = 0x0A0D0D0A;
+const BLOCK_INTERFACE_DESCRIPTION: u32 = 0x00000001;
+const BLOCK_SIMPLE_PACKET: u32 = 0x00000003;
+const BLOCK_ENHANCED_PACKET: u32 = 0x00000006;
+
+const OPTION_END: u16 = 0;
+const OPTION_IF_TSRESOL: u16 = 9;
+
+/// Records and blocks larger than this are rejected instead of being allocated
+pub const MAX_RECORD_LEN: u32 = 16 * 1024 * 1024;
+
+/// Snapshot length written to file headers, the one used by tcpdump
+pub const SNAPLEN: u32 = 262144;
+
+/// Link-layer header type of the captured packets, the `LINKTYPE_` values of tcpdump.org
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LinkType {
+    Ethernet,
+    /// Raw IPv4 or IPv6 packets, told apart by their version
+    Raw,
+    IPv4,
+    IPv6,
+    Other(u16),
+}
+
+impl From<u16> for LinkType {
+    fn from(value: u16) -> Self {
+        match value {
+            1 => LinkType::Ethernet,
+            101 => LinkType::Raw,
+            228 => LinkType::IPv4,
+            229 => LinkType::IPv6,
+            other => LinkType::Other(other),
+        }
+    }
+}
+
+impl From<LinkType> for u16 {
+    fn from(value: LinkType) -> Self {
+        match value {
+            LinkType::Ethernet => 1,
+            LinkType::Raw => 101,
+            LinkType::IPv4 => 228,
+            LinkType::IPv6 => 229,
+            LinkType::Other(other) => other,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum TimestampResolution {
+    #[default]
+    Microseconds,
+    Nanoseconds,
+}
+
+impl TimestampResolution {
+    /// Value of the pcapng `if_tsresol` option, the negative power of 10 of a tick
+    fn tsresol(self) -> u8 {
+        match self {
+            TimestampResolution::Microseconds => 6,
+            TimestampResolution::Nanoseconds => 9,
+        }
+    }
+
+    fn ticks(self, timestamp: Duration) -> u64 {
+        match self {
+            TimestampResolution::Microseconds => timestamp.as_micros() as u64,
+            TimestampResolution::Nanoseconds => timestamp.as_nanos() as u64,
+        }
+    }
+}
+
+/// Converts a pcapng timestamp, `tsresol` is the value of the `if_tsresol` option of the
+/// interface. The high bit selects a power of 2 instead of a power of 10
+fn timestamp(ticks: u64, tsresol: u8) -> Duration {
+    let exponent = (tsresol & 0x7F) as u32;
+
+    let nanos = if tsresol & 0x80 != 0 {
+        (ticks as u128 * 1_000_000_000)
+            .checked_shr(exponent)
+            .unwrap_or(0)
+    } else if exponent <= 9 {
+        ticks as u128 * 10u128.pow(9 - exponent)
+    } else {
+        10u128
+            .checked_pow(exponent - 9)
+            .map_or(0, |divisor| ticks as u128 / divisor)
+    };
+
+    Duration::new(
+        (nanos / 1_000_000_000) as u64,
+        (nanos % 1_000_000_000) as u32,
+    )
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    /// Neither a pcap nor a pcapng file, or a pcapng section with an unknown byte order
+    InvalidMagic(u32),
+    /// Holds the type of a block whose length or content is inconsistent
+    InvalidBlock(u32),
+    /// Holds the length of a record larger than `MAX_RECORD_LEN`
+    TooLarge(u32),
+    /// A packet refers to an interface not described in its section
+    UnknownInterface(u32),
+}
+
+impl From<io::Error> for Error {
+    fn from(value: io::Error) -> Self {
+        Error::Io(value)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{e}"),
+            Error::InvalidMagic(magic) => write!(f, "invalid capture file magic {magic:#010x}"),
+            Error::InvalidBlock(kind) => write!(f, "invalid pcapng block of type {kind:#010x}"),
+            Error::TooLarge(len) => write!(f, "record of {len} bytes is too large"),
+            Error::UnknownInterface(id) => write!(f, "unknown interface {id}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// One captured packet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    /// Time since the Unix epoch
+    pub timestamp: Duration,
+    pub link_type: LinkType,
+    /// Index of the interface in the pcapng section, always 0 for pcap files
+    pub interface: u32,
+    /// Length of the packet on the wire, `data` is shorter when the capture was truncated
+    pub original_len: u32,
+    pub data: Vec<u8>,
+}
+
+impl Record {
+    pub fn is_truncated(&self) -> bool {
+        (self.data.len() as u64) < self.original_len as u64
+    }
+
+    /// Dissects `data` according to the link type. Packets of unknown link types have no parsed
+    /// layer, all their bytes are in `payload`
+    pub fn packet(&self) -> Result<Packet<'_>, packet::Error> {
+        match self.link_type {
+            LinkType::Ethernet => Packet::new(&self.data),
+            LinkType::Raw | LinkType::IPv4 | LinkType::IPv6 => Packet::new_ip(&self.data),
+            LinkType::Other(_) => Ok(Packet::empty(&self.data)),
+        }
+    }
+
+    /// Like `packet`, but the layers parsed before a malformed header are kept
+    pub fn dissect(&self) -> (Packet<'_>, Option<packet::Error>) {
+        match self.link_type {
+            LinkType::Ethernet => Packet::dissect(&self.data),
+            LinkType::Raw | LinkType::IPv4 | LinkType::IPv6 => Packet::dissect_ip(&self.data),
+            LinkType::Other(_) => (Packet::empty(&self.data), None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::pcap::timestamp;
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(timestamp(1_500_000, 6), Duration::from_millis(1500));
+        assert_eq!(timestamp(1_500_000_001, 9), Duration::new(1, 500_000_001));
+        assert_eq!(timestamp(3, 0x81), Duration::from_millis(1500));
+        assert_eq!(timestamp(12_000, 12), Duration::from_nanos(12));
+    }
+}
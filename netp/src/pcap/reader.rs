@@ -0,0 +1,352 @@
+use std::io::{self, Read};
+use std::time::Duration;
+
+use super::{
+    timestamp, Error, LinkType, Record, BLOCK_ENHANCED_PACKET, BLOCK_INTERFACE_DESCRIPTION,
+    BLOCK_SECTION_HEADER, BLOCK_SIMPLE_PACKET, MAX_RECORD_LEN, OPTION_END, OPTION_IF_TSRESOL,
+    PCAPNG_BYTE_ORDER_MAGIC, PCAP_MAGIC_MICROS, PCAP_MAGIC_NANOS,
+};
+
+#[derive(Debug, Clone, Copy)]
+struct Interface {
+    link_type: LinkType,
+    tsresol: u8,
+}
+
+enum Format {
+    Pcap {
+        big_endian: bool,
+        nanos: bool,
+        link_type: LinkType,
+    },
+    Pcapng {
+        big_endian: bool,
+        interfaces: Vec<Interface>,
+    },
+}
+
+/// Reads pcap and pcapng files, detected from their magic number. Wrap files in a `BufReader`,
+/// records are read with several small reads.
+///
+/// The iterator stops after the first error, the position in the file is unknown from there
+pub struct Reader<R> {
+    reader: R,
+    format: Format,
+    failed: bool,
+}
+
+fn u16_at(slice: &[u8], at: usize, big_endian: bool) -> u16 {
+    let bytes = *slice[at..at + 2].first_chunk::<2>().unwrap();
+
+    if big_endian {
+        u16::from_be_bytes(bytes)
+    } else {
+        u16::from_le_bytes(bytes)
+    }
+}
+
+fn u32_at(slice: &[u8], at: usize, big_endian: bool) -> u32 {
+    let bytes = *slice[at..at + 4].first_chunk::<4>().unwrap();
+
+    if big_endian {
+        u32::from_be_bytes(bytes)
+    } else {
+        u32::from_le_bytes(bytes)
+    }
+}
+
+/// Like `read_exact`, but `Ok(false)` when the reader is at its end before the first byte
+fn fill<R: Read>(reader: &mut R, buffer: &mut [u8]) -> io::Result<bool> {
+    let mut read = 0;
+
+    while read < buffer.len() {
+        match reader.read(&mut buffer[read..]) {
+            Ok(0) if read == 0 => return Ok(false),
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(n) => read += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(true)
+}
+
+impl<R: Read> Reader<R> {
+    /// Reads the file header, or the first section header of pcapng files
+    pub fn new(mut reader: R) -> Result<Self, Error> {
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+
+        if u32::from_le_bytes(magic) == BLOCK_SECTION_HEADER {
+            let mut this = Self {
+                reader,
+                format: Format::Pcapng {
+                    big_endian: false,
+                    interfaces: Vec::new(),
+                },
+                failed: false,
+            };
+            this.read_block(BLOCK_SECTION_HEADER)?;
+
+            return Ok(this);
+        }
+
+        let (big_endian, nanos) = match (u32::from_le_bytes(magic), u32::from_be_bytes(magic)) {
+            (PCAP_MAGIC_MICROS, _) => (false, false),
+            (PCAP_MAGIC_NANOS, _) => (false, true),
+            (_, PCAP_MAGIC_MICROS) => (true, false),
+            (_, PCAP_MAGIC_NANOS) => (true, true),
+            (_, magic) => return Err(Error::InvalidMagic(magic)),
+        };
+
+        // Version, time zone, accuracy, snapshot length and link type
+        let mut header = [0u8; 20];
+        reader.read_exact(&mut header)?;
+
+        // The upper bits of the link type hold FCS information
+        let link_type = LinkType::from(u32_at(&header, 16, big_endian) as u16);
+
+        Ok(Self {
+            reader,
+            format: Format::Pcap {
+                big_endian,
+                nanos,
+                link_type,
+            },
+            failed: false,
+        })
+    }
+
+    /// Link type of pcap files or of the first interface of pcapng files, if described yet
+    pub fn link_type(&self) -> Option<LinkType> {
+        match &self.format {
+            Format::Pcap { link_type, .. } => Some(*link_type),
+            Format::Pcapng { interfaces, .. } => interfaces.first().map(|i| i.link_type),
+        }
+    }
+
+    pub fn is_pcapng(&self) -> bool {
+        matches!(self.format, Format::Pcapng { .. })
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn read_data(&mut self, len: u32) -> Result<Vec<u8>, Error> {
+        if len > MAX_RECORD_LEN {
+            return Err(Error::TooLarge(len));
+        }
+
+        let mut data = vec![0u8; len as usize];
+        self.reader.read_exact(&mut data)?;
+
+        Ok(data)
+    }
+
+    fn next_pcap(&mut self) -> Result<Option<Record>, Error> {
+        let Format::Pcap {
+            big_endian,
+            nanos,
+            link_type,
+        } = self.format
+        else {
+            unreachable!()
+        };
+
+        let mut header = [0u8; 16];
+        if !fill(&mut self.reader, &mut header)? {
+            return Ok(None);
+        }
+
+        let seconds = u32_at(&header, 0, big_endian) as u64;
+        let fraction = u32_at(&header, 4, big_endian) as u64;
+        let fraction = if nanos { fraction } else { fraction * 1000 };
+
+        let data = self.read_data(u32_at(&header, 8, big_endian))?;
+
+        Ok(Some(Record {
+            timestamp: Duration::from_secs(seconds) + Duration::from_nanos(fraction),
+            link_type,
+            interface: 0,
+            original_len: u32_at(&header, 12, big_endian),
+            data,
+        }))
+    }
+
+    /// Reads the rest of a block whose type was already read, returns its body without the
+    /// trailing length. Section headers switch the byte order and drop the known interfaces
+    fn read_block(&mut self, kind: u32) -> Result<Vec<u8>, Error> {
+        let mut len = [0u8; 4];
+        self.reader.read_exact(&mut len)?;
+
+        let mut prefix = Vec::new();
+
+        if kind == BLOCK_SECTION_HEADER {
+            let mut magic = [0u8; 4];
+            self.reader.read_exact(&mut magic)?;
+
+            let big_endian = match u32::from_be_bytes(magic) {
+                PCAPNG_BYTE_ORDER_MAGIC => true,
+                magic if magic.swap_bytes() == PCAPNG_BYTE_ORDER_MAGIC => false,
+                magic => return Err(Error::InvalidMagic(magic)),
+            };
+
+            self.format = Format::Pcapng {
+                big_endian,
+                interfaces: Vec::new(),
+            };
+            prefix.extend_from_slice(&magic);
+        }
+
+        let Format::Pcapng { big_endian, .. } = self.format else {
+            unreachable!()
+        };
+
+        let len = u32_at(&len, 0, big_endian);
+        if len % 4 != 0 || (len as usize) < 12 + prefix.len() {
+            return Err(Error::InvalidBlock(kind));
+        }
+
+        let mut body = self.read_data(len - 12 - prefix.len() as u32)?;
+        prefix.append(&mut body);
+
+        let mut trailer = [0u8; 4];
+        self.reader.read_exact(&mut trailer)?;
+
+        if u32_at(&trailer, 0, big_endian) != len {
+            return Err(Error::InvalidBlock(kind));
+        }
+
+        Ok(prefix)
+    }
+
+    fn next_pcapng(&mut self) -> Result<Option<Record>, Error> {
+        loop {
+            let mut kind = [0u8; 4];
+            if !fill(&mut self.reader, &mut kind)? {
+                return Ok(None);
+            }
+
+            let Format::Pcapng { big_endian, .. } = self.format else {
+                unreachable!()
+            };
+
+            // The section header type reads the same in both byte orders
+            let kind = u32_at(&kind, 0, big_endian);
+            let body = self.read_block(kind)?;
+
+            let Format::Pcapng {
+                big_endian,
+                ref mut interfaces,
+            } = self.format
+            else {
+                unreachable!()
+            };
+
+            match kind {
+                BLOCK_INTERFACE_DESCRIPTION => {
+                    if body.len() < 8 {
+                        return Err(Error::InvalidBlock(kind));
+                    }
+
+                    interfaces.push(Interface {
+                        link_type: LinkType::from(u16_at(&body, 0, big_endian)),
+                        tsresol: tsresol(&body[8..], big_endian),
+                    });
+                }
+                BLOCK_ENHANCED_PACKET => {
+                    if body.len() < 20 {
+                        return Err(Error::InvalidBlock(kind));
+                    }
+
+                    let id = u32_at(&body, 0, big_endian);
+                    let interface = interfaces
+                        .get(id as usize)
+                        .ok_or(Error::UnknownInterface(id))?;
+
+                    let ticks = (u32_at(&body, 4, big_endian) as u64) << 32
+                        | u32_at(&body, 8, big_endian) as u64;
+                    let captured = u32_at(&body, 12, big_endian) as usize;
+
+                    let data = body
+                        .get(20..20 + captured)
+                        .ok_or(Error::InvalidBlock(kind))?;
+
+                    return Ok(Some(Record {
+                        timestamp: timestamp(ticks, interface.tsresol),
+                        link_type: interface.link_type,
+                        interface: id,
+                        original_len: u32_at(&body, 16, big_endian),
+                        data: data.to_vec(),
+                    }));
+                }
+                // No timestamp, and the captured length is implied by the block length
+                BLOCK_SIMPLE_PACKET => {
+                    if body.len() < 4 {
+                        return Err(Error::InvalidBlock(kind));
+                    }
+
+                    let interface = interfaces.first().ok_or(Error::UnknownInterface(0))?;
+                    let original_len = u32_at(&body, 0, big_endian);
+                    let captured = (body.len() - 4).min(original_len as usize);
+
+                    return Ok(Some(Record {
+                        timestamp: Duration::ZERO,
+                        link_type: interface.link_type,
+                        interface: 0,
+                        original_len,
+                        data: body[4..4 + captured].to_vec(),
+                    }));
+                }
+                // Section headers were handled by read_block, other blocks carry no packet
+                _ => continue,
+            }
+        }
+    }
+}
+
+/// Looks for the `if_tsresol` option of an interface description, microseconds when absent
+fn tsresol(mut options: &[u8], big_endian: bool) -> u8 {
+    while options.len() >= 4 {
+        let code = u16_at(options, 0, big_endian);
+        let len = u16_at(options, 2, big_endian) as usize;
+
+        if code == OPTION_END {
+            break;
+        }
+
+        if code == OPTION_IF_TSRESOL && len == 1 && options.len() > 4 {
+            return options[4];
+        }
+
+        let padded = 4 + len.next_multiple_of(4);
+        options = options.get(padded..).unwrap_or_default();
+    }
+
+    6
+}
+
+impl<R: Read> Iterator for Reader<R> {
+    type Item = Result<Record, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+
+        let record = match self.format {
+            Format::Pcap { .. } => self.next_pcap(),
+            Format::Pcapng { .. } => self.next_pcapng(),
+        };
+
+        match record {
+            Ok(record) => record.map(Ok),
+            Err(e) => {
+                self.failed = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
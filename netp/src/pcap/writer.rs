@@ -0,0 +1,326 @@
+use std::io::{self, Write};
+use std::time::Duration;
+
+use super::{
+    LinkType, Record, TimestampResolution, BLOCK_ENHANCED_PACKET, BLOCK_INTERFACE_DESCRIPTION,
+    BLOCK_SECTION_HEADER, OPTION_END, OPTION_IF_TSRESOL, PCAPNG_BYTE_ORDER_MAGIC,
+    PCAP_MAGIC_MICROS, PCAP_MAGIC_NANOS, SNAPLEN,
+};
+
+/// Writes classic pcap files in little-endian byte order
+pub struct PcapWriter<W> {
+    writer: W,
+    resolution: TimestampResolution,
+}
+
+impl<W: Write> PcapWriter<W> {
+    /// Writes the file header
+    pub fn new(
+        mut writer: W,
+        link_type: LinkType,
+        resolution: TimestampResolution,
+    ) -> io::Result<Self> {
+        let magic = match resolution {
+            TimestampResolution::Microseconds => PCAP_MAGIC_MICROS,
+            TimestampResolution::Nanoseconds => PCAP_MAGIC_NANOS,
+        };
+
+        let mut header = [0u8; 24];
+        header[0..4].copy_from_slice(&magic.to_le_bytes());
+        header[4..6].copy_from_slice(&2u16.to_le_bytes());
+        header[6..8].copy_from_slice(&4u16.to_le_bytes());
+        header[16..20].copy_from_slice(&SNAPLEN.to_le_bytes());
+        header[20..24].copy_from_slice(&(u16::from(link_type) as u32).to_le_bytes());
+        writer.write_all(&header)?;
+
+        Ok(Self { writer, resolution })
+    }
+
+    /// `timestamp` is the time since the Unix epoch
+    pub fn write(&mut self, timestamp: Duration, data: &[u8]) -> io::Result<()> {
+        self.write_truncated(timestamp, data, data.len() as u32)
+    }
+
+    /// Writes a packet of which only `data` was captured
+    pub fn write_truncated(
+        &mut self,
+        timestamp: Duration,
+        data: &[u8],
+        original_len: u32,
+    ) -> io::Result<()> {
+        let fraction = match self.resolution {
+            TimestampResolution::Microseconds => timestamp.subsec_micros(),
+            TimestampResolution::Nanoseconds => timestamp.subsec_nanos(),
+        };
+
+        let mut header = [0u8; 16];
+        header[0..4].copy_from_slice(&(timestamp.as_secs() as u32).to_le_bytes());
+        header[4..8].copy_from_slice(&fraction.to_le_bytes());
+        header[8..12].copy_from_slice(&(data.len() as u32).to_le_bytes());
+        header[12..16].copy_from_slice(&original_len.to_le_bytes());
+
+        self.writer.write_all(&header)?;
+        self.writer.write_all(data)
+    }
+
+    /// The link type and interface of the record are ignored
+    pub fn write_record(&mut self, record: &Record) -> io::Result<()> {
+        self.write_truncated(record.timestamp, &record.data, record.original_len)
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Writes pcapng files made of a single section in little-endian byte order. Interfaces must be
+/// added before the packets that refer to them
+pub struct PcapngWriter<W> {
+    writer: W,
+    interfaces: Vec<TimestampResolution>,
+}
+
+impl<W: Write> PcapngWriter<W> {
+    /// Writes the section header
+    pub fn new(writer: W) -> io::Result<Self> {
+        let mut this = Self {
+            writer,
+            interfaces: Vec::new(),
+        };
+
+        let mut body = [0u8; 16];
+        body[0..4].copy_from_slice(&PCAPNG_BYTE_ORDER_MAGIC.to_le_bytes());
+        body[4..6].copy_from_slice(&1u16.to_le_bytes());
+        // Unknown section length
+        body[8..16].copy_from_slice(&u64::MAX.to_le_bytes());
+
+        this.write_block(BLOCK_SECTION_HEADER, &body, &[])?;
+
+        Ok(this)
+    }
+
+    /// Writes an interface description block, returns the interface ID to pass to `write`
+    pub fn add_interface(
+        &mut self,
+        link_type: LinkType,
+        resolution: TimestampResolution,
+    ) -> io::Result<u32> {
+        let mut body = [0u8; 16];
+        body[0..2].copy_from_slice(&u16::from(link_type).to_le_bytes());
+        body[4..8].copy_from_slice(&SNAPLEN.to_le_bytes());
+
+        // if_tsresol padded to 4 bytes, then the end of options
+        body[8..10].copy_from_slice(&OPTION_IF_TSRESOL.to_le_bytes());
+        body[10..12].copy_from_slice(&1u16.to_le_bytes());
+        body[12] = resolution.tsresol();
+
+        let mut end = [0u8; 4];
+        end[0..2].copy_from_slice(&OPTION_END.to_le_bytes());
+
+        self.write_block(BLOCK_INTERFACE_DESCRIPTION, &body, &end)?;
+        self.interfaces.push(resolution);
+
+        Ok(self.interfaces.len() as u32 - 1)
+    }
+
+    /// Writes an enhanced packet block, `timestamp` is the time since the Unix epoch
+    pub fn write(&mut self, interface: u32, timestamp: Duration, data: &[u8]) -> io::Result<()> {
+        self.write_truncated(interface, timestamp, data, data.len() as u32)
+    }
+
+    /// Writes a packet of which only `data` was captured
+    pub fn write_truncated(
+        &mut self,
+        interface: u32,
+        timestamp: Duration,
+        data: &[u8],
+        original_len: u32,
+    ) -> io::Result<()> {
+        let Some(resolution) = self.interfaces.get(interface as usize) else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "unknown pcapng interface",
+            ));
+        };
+
+        let ticks = resolution.ticks(timestamp);
+
+        let mut header = [0u8; 20];
+        header[0..4].copy_from_slice(&interface.to_le_bytes());
+        header[4..8].copy_from_slice(&((ticks >> 32) as u32).to_le_bytes());
+        header[8..12].copy_from_slice(&(ticks as u32).to_le_bytes());
+        header[12..16].copy_from_slice(&(data.len() as u32).to_le_bytes());
+        header[16..20].copy_from_slice(&original_len.to_le_bytes());
+
+        self.write_block(BLOCK_ENHANCED_PACKET, &header, data)
+    }
+
+    /// The interface of the record must have been added to this writer
+    pub fn write_record(&mut self, record: &Record) -> io::Result<()> {
+        self.write_truncated(
+            record.interface,
+            record.timestamp,
+            &record.data,
+            record.original_len,
+        )
+    }
+
+    /// `data` is padded to 4 bytes
+    fn write_block(&mut self, kind: u32, header: &[u8], data: &[u8]) -> io::Result<()> {
+        let padding = data.len().next_multiple_of(4) - data.len();
+        let len = (12 + header.len() + data.len() + padding) as u32;
+
+        self.writer.write_all(&kind.to_le_bytes())?;
+        self.writer.write_all(&len.to_le_bytes())?;
+        self.writer.write_all(header)?;
+        self.writer.write_all(data)?;
+        self.writer.write_all(&[0u8; 3][..padding])?;
+        self.writer.write_all(&len.to_le_bytes())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::time::Duration;
+
+    use crate::builder::PacketBuilder;
+    use crate::pcap::{
+        Error, LinkType, PcapWriter, PcapngWriter, Reader, Record, TimestampResolution,
+    };
+
+    fn udp_frame() -> Vec<u8> {
+        let mut buffer = [0u8; 64];
+        let len = PacketBuilder::new()
+            .ethernet([0x01; 6], [0x02; 6])
+            .ipv4([10, 0, 0, 1], [10, 0, 0, 2], 64)
+            .udp(5353, 53)
+            .write(&mut buffer, b"abc")
+            .unwrap();
+
+        buffer[..len].to_vec()
+    }
+
+    #[test]
+    fn pcap_round_trip() {
+        let frame = udp_frame();
+        let timestamp = Duration::new(1_700_000_000, 123_456_789);
+
+        for (resolution, expected) in [
+            (TimestampResolution::Microseconds, 123_456_000),
+            (TimestampResolution::Nanoseconds, 123_456_789),
+        ] {
+            let mut writer = PcapWriter::new(Vec::new(), LinkType::Ethernet, resolution).unwrap();
+            writer.write(timestamp, &frame).unwrap();
+            writer
+                .write_truncated(timestamp, &frame[..20], frame.len() as u32)
+                .unwrap();
+
+            let mut reader = Reader::new(Cursor::new(writer.into_inner())).unwrap();
+            assert!(!reader.is_pcapng());
+            assert_eq!(reader.link_type(), Some(LinkType::Ethernet));
+
+            let record = reader.next().unwrap().unwrap();
+            assert_eq!(record.timestamp, Duration::new(1_700_000_000, expected));
+            assert_eq!(record.data, frame);
+
+            let packet = record.packet().unwrap();
+            assert_eq!(packet.ports(), Some((5353, 53)));
+            assert_eq!(packet.payload, b"abc");
+
+            let record = reader.next().unwrap().unwrap();
+            assert!(record.is_truncated());
+            assert!(record.dissect().0.ipv4().is_none());
+
+            assert!(reader.next().is_none());
+        }
+    }
+
+    #[test]
+    fn pcapng_round_trip() {
+        let frame = udp_frame();
+        let timestamp = Duration::new(1_700_000_000, 123_456_789);
+
+        let mut writer = PcapngWriter::new(Vec::new()).unwrap();
+        let eth = writer
+            .add_interface(LinkType::Ethernet, TimestampResolution::Microseconds)
+            .unwrap();
+        let raw = writer
+            .add_interface(LinkType::Raw, TimestampResolution::Nanoseconds)
+            .unwrap();
+
+        writer.write(eth, timestamp, &frame).unwrap();
+        writer.write(raw, timestamp, &frame[14..]).unwrap();
+        assert!(writer.write(2, timestamp, &frame).is_err());
+
+        let reader = Reader::new(Cursor::new(writer.into_inner())).unwrap();
+        assert!(reader.is_pcapng());
+
+        let records = reader.collect::<Result<Vec<Record>, Error>>().unwrap();
+        assert_eq!(records.len(), 2);
+
+        assert_eq!(records[0].link_type, LinkType::Ethernet);
+        assert_eq!(
+            records[0].timestamp,
+            Duration::new(1_700_000_000, 123_456_000)
+        );
+        assert_eq!(records[1].interface, 1);
+        assert_eq!(records[1].timestamp, timestamp);
+        assert_eq!(records[1].data.len(), frame.len() - 14);
+
+        for record in &records {
+            assert_eq!(record.packet().unwrap().ports(), Some((5353, 53)));
+        }
+    }
+
+    #[test]
+    fn big_endian_pcap() {
+        let mut file = vec![
+            0xA1, 0xB2, 0xC3, 0xD4, 0x00, 0x02, 0x00, 0x04, 0, 0, 0, 0, 0, 0, 0, 0, 0x00, 0x04,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x65,
+        ];
+        file.extend_from_slice(&[0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, 1, 0, 0, 0, 1, 0x45]);
+
+        let mut reader = Reader::new(Cursor::new(file)).unwrap();
+        assert_eq!(reader.link_type(), Some(LinkType::Raw));
+
+        let record = reader.next().unwrap().unwrap();
+        assert_eq!(record.timestamp, Duration::new(1, 2000));
+        assert_eq!(record.data, [0x45]);
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(matches!(
+            Reader::new(Cursor::new([0u8; 24])),
+            Err(Error::InvalidMagic(0))
+        ));
+
+        let mut writer = PcapWriter::new(
+            Vec::new(),
+            LinkType::Ethernet,
+            TimestampResolution::Microseconds,
+        )
+        .unwrap();
+        writer.write(Duration::ZERO, &udp_frame()).unwrap();
+
+        let mut file = writer.into_inner();
+        file.truncate(file.len() - 1);
+
+        let mut reader = Reader::new(Cursor::new(file)).unwrap();
+        assert!(matches!(reader.next(), Some(Err(Error::Io(_)))));
+        assert!(reader.next().is_none());
+    }
+}
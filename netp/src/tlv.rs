@@ -0,0 +1,102 @@
+//! Walker over the kind/length/value option lists of the IPv4 and TCP headers. Both headers use
+//! single byte end of list and no-operation options, every other option carries a length byte
+//! that counts the kind and length bytes themselves.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OptionError {
+    /// The option at the given offset has no length byte or its length overruns the options
+    Truncated(usize),
+    /// The length byte of an option of the given kind is not valid for it
+    InvalidLength(u8, u8),
+}
+
+/// An option before its kind is interpreted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RawOption<'pkt> {
+    Eol,
+    Nop,
+    /// Kind and the bytes following the length byte
+    Value(u8, &'pkt [u8]),
+}
+
+/// Iterator over raw options. Option lists are at most 40 bytes long so the walk is bounded, it
+/// stops after the end of option list or the first malformed option
+pub struct OptionWalker<'pkt> {
+    slice: &'pkt [u8],
+    offset: usize,
+}
+
+impl<'pkt> OptionWalker<'pkt> {
+    pub fn new(slice: &'pkt [u8]) -> Self {
+        Self { slice, offset: 0 }
+    }
+
+    /// Stop the walk, for options whose kind rejects their length
+    pub fn finish(&mut self) {
+        self.offset = self.slice.len();
+    }
+}
+
+impl<'pkt> Iterator for OptionWalker<'pkt> {
+    type Item = Result<RawOption<'pkt>, OptionError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let rem = self.slice.get(self.offset..)?;
+        let kind = *rem.first()?;
+
+        match kind {
+            0 => {
+                self.finish();
+                return Some(Ok(RawOption::Eol));
+            }
+            1 => {
+                self.offset += 1;
+                return Some(Ok(RawOption::Nop));
+            }
+            _ => {}
+        }
+
+        let Some(&len) = rem.get(1) else {
+            let offset = self.offset;
+            self.finish();
+            return Some(Err(OptionError::Truncated(offset)));
+        };
+
+        if len < 2 {
+            self.finish();
+            return Some(Err(OptionError::InvalidLength(kind, len)));
+        }
+
+        let Some(data) = rem.get(2..len as usize) else {
+            let offset = self.offset;
+            self.finish();
+            return Some(Err(OptionError::Truncated(offset)));
+        };
+
+        self.offset += len as usize;
+        Some(Ok(RawOption::Value(kind, data)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tlv::{OptionError, OptionWalker, RawOption};
+
+    #[test]
+    fn walk() {
+        let mut options = OptionWalker::new(&[0x01, 0x1E, 0x03, 0xAA, 0x00, 0x01]);
+        assert_eq!(options.next(), Some(Ok(RawOption::Nop)));
+        assert_eq!(options.next(), Some(Ok(RawOption::Value(30, &[0xAA]))));
+        assert_eq!(options.next(), Some(Ok(RawOption::Eol)));
+        assert_eq!(options.next(), None);
+
+        let mut options = OptionWalker::new(&[0x1E, 0x01, 0x01]);
+        assert_eq!(options.next(), Some(Err(OptionError::InvalidLength(30, 1))));
+        assert_eq!(options.next(), None);
+
+        let mut options = OptionWalker::new(&[0x01, 0x1E]);
+        assert_eq!(options.next(), Some(Ok(RawOption::Nop)));
+        assert_eq!(options.next(), Some(Err(OptionError::Truncated(1))));
+        assert_eq!(options.next(), None);
+    }
+}
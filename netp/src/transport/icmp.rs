@@ -0,0 +1,411 @@
+use core::fmt;
+
+use crate::network::IPv4;
+
+#[derive(Debug)]
+pub struct Icmp<P = ()> {
+    slice: P,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    InvalidSize(usize),
+}
+
+impl Icmp<()> {
+    pub const MIN_LEN: usize = 8;
+}
+
+impl<'pkt> Icmp<&'pkt [u8]> {
+    pub fn new(slice: &'pkt [u8]) -> Result<(Self, &'pkt [u8]), Error> {
+        if slice.len() < Icmp::MIN_LEN {
+            return Err(Error::InvalidSize(slice.len()));
+        }
+
+        let (slice, rem) = slice.split_at(Icmp::MIN_LEN);
+
+        Ok((Self { slice }, rem))
+    }
+}
+
+impl<'pkt> Icmp<&'pkt mut [u8]> {
+    pub fn new_mut(slice: &'pkt mut [u8]) -> Result<(Self, &'pkt mut [u8]), Error> {
+        if slice.len() < Icmp::MIN_LEN {
+            return Err(Error::InvalidSize(slice.len()));
+        }
+
+        let (slice, rem) = slice.split_at_mut(Icmp::MIN_LEN);
+
+        Ok((Self { slice }, rem))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum IcmpType {
+    EchoReply,
+    DestinationUnreachable,
+    SourceQuench,
+    Redirect,
+    EchoRequest,
+    RouterAdvertisement,
+    RouterSolicitation,
+    TimeExceeded,
+    ParameterProblem,
+    Timestamp,
+    TimestampReply,
+    Other(u8),
+}
+
+impl From<u8> for IcmpType {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => Self::EchoReply,
+            3 => Self::DestinationUnreachable,
+            4 => Self::SourceQuench,
+            5 => Self::Redirect,
+            8 => Self::EchoRequest,
+            9 => Self::RouterAdvertisement,
+            10 => Self::RouterSolicitation,
+            11 => Self::TimeExceeded,
+            12 => Self::ParameterProblem,
+            13 => Self::Timestamp,
+            14 => Self::TimestampReply,
+            x => Self::Other(x),
+        }
+    }
+}
+
+impl From<IcmpType> for u8 {
+    fn from(value: IcmpType) -> Self {
+        match value {
+            IcmpType::EchoReply => 0,
+            IcmpType::DestinationUnreachable => 3,
+            IcmpType::SourceQuench => 4,
+            IcmpType::Redirect => 5,
+            IcmpType::EchoRequest => 8,
+            IcmpType::RouterAdvertisement => 9,
+            IcmpType::RouterSolicitation => 10,
+            IcmpType::TimeExceeded => 11,
+            IcmpType::ParameterProblem => 12,
+            IcmpType::Timestamp => 13,
+            IcmpType::TimestampReply => 14,
+            IcmpType::Other(x) => x,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UnreachableCode {
+    Network,
+    Host,
+    Protocol,
+    Port,
+    FragmentationNeeded,
+    SourceRouteFailed,
+    NetworkUnknown,
+    HostUnknown,
+    NetworkProhibited,
+    HostProhibited,
+    AdministrativelyProhibited,
+    Other(u8),
+}
+
+impl From<u8> for UnreachableCode {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => Self::Network,
+            1 => Self::Host,
+            2 => Self::Protocol,
+            3 => Self::Port,
+            4 => Self::FragmentationNeeded,
+            5 => Self::SourceRouteFailed,
+            6 => Self::NetworkUnknown,
+            7 => Self::HostUnknown,
+            9 => Self::NetworkProhibited,
+            10 => Self::HostProhibited,
+            13 => Self::AdministrativelyProhibited,
+            x => Self::Other(x),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RedirectCode {
+    Network,
+    Host,
+    TosNetwork,
+    TosHost,
+    Other(u8),
+}
+
+impl From<u8> for RedirectCode {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => Self::Network,
+            1 => Self::Host,
+            2 => Self::TosNetwork,
+            3 => Self::TosHost,
+            x => Self::Other(x),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TimeExceededCode {
+    TtlExceeded,
+    FragmentReassembly,
+    Other(u8),
+}
+
+impl From<u8> for TimeExceededCode {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => Self::TtlExceeded,
+            1 => Self::FragmentReassembly,
+            x => Self::Other(x),
+        }
+    }
+}
+
+/// Typed view of the type/code pair of an ICMP message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IcmpMessage {
+    EchoReply { identifier: u16, sequence: u16 },
+    EchoRequest { identifier: u16, sequence: u16 },
+    DestinationUnreachable(UnreachableCode),
+    Redirect(RedirectCode),
+    TimeExceeded(TimeExceededCode),
+    ParameterProblem { pointer: u8 },
+    Other { icmp_type: u8, code: u8 },
+}
+
+impl<P: AsMut<[u8]> + AsRef<[u8]>> Icmp<P> {
+    pub fn slice_mut(&mut self) -> &mut [u8] {
+        self.slice.as_mut()
+    }
+
+    pub fn set_icmp_type(&mut self, icmp_type: IcmpType) {
+        self.slice.as_mut()[0] = u8::from(icmp_type);
+    }
+
+    pub fn set_code(&mut self, code: u8) {
+        self.slice.as_mut()[1] = code;
+    }
+
+    pub fn set_csum(&mut self, csum: u16) {
+        self.slice.as_mut()[2..4].copy_from_slice(&csum.to_be_bytes());
+    }
+
+    pub fn update_csum(&mut self, payload: &[u8]) {
+        self.set_csum(self.calc_csum(payload))
+    }
+
+    pub fn set_identifier(&mut self, identifier: u16) {
+        self.slice.as_mut()[4..6].copy_from_slice(&identifier.to_be_bytes());
+    }
+
+    pub fn set_sequence(&mut self, sequence: u16) {
+        self.slice.as_mut()[6..8].copy_from_slice(&sequence.to_be_bytes());
+    }
+
+    pub fn set_rest_of_header(&mut self, rest: &[u8; 4]) {
+        self.slice.as_mut()[4..8].copy_from_slice(rest);
+    }
+}
+
+impl<P: AsRef<[u8]>> Icmp<P> {
+    pub fn slice(&self) -> &[u8] {
+        self.slice.as_ref()
+    }
+
+    pub fn icmp_type(&self) -> IcmpType {
+        IcmpType::from(self.slice.as_ref()[0])
+    }
+
+    pub fn icmp_type_u8(&self) -> u8 {
+        self.slice.as_ref()[0]
+    }
+
+    pub fn code(&self) -> u8 {
+        self.slice.as_ref()[1]
+    }
+
+    pub fn csum(&self) -> u16 {
+        u16::from_be_bytes(*self.slice.as_ref()[2..4].first_chunk::<2>().unwrap())
+    }
+
+    /// The checksum covers the ICMP header and the whole message `payload`
+    pub fn calc_csum(&self, payload: &[u8]) -> u16 {
+        etherparse::checksum::Sum16BitWords::new()
+            .add_2bytes([self.icmp_type_u8(), self.code()])
+            .add_4bytes(*self.rest_of_header())
+            .add_slice(payload)
+            .ones_complement()
+            .to_be()
+    }
+
+    pub fn rest_of_header(&self) -> &[u8; 4] {
+        self.slice.as_ref()[4..8].first_chunk::<4>().unwrap()
+    }
+
+    /// Only meaningful for echo and timestamp messages
+    pub fn identifier(&self) -> u16 {
+        u16::from_be_bytes(*self.slice.as_ref()[4..6].first_chunk::<2>().unwrap())
+    }
+
+    /// Only meaningful for echo and timestamp messages
+    pub fn sequence(&self) -> u16 {
+        u16::from_be_bytes(*self.slice.as_ref()[6..8].first_chunk::<2>().unwrap())
+    }
+
+    /// Only meaningful for redirect messages
+    pub fn gateway(&self) -> &[u8; 4] {
+        self.rest_of_header()
+    }
+
+    pub fn message(&self) -> IcmpMessage {
+        match self.icmp_type() {
+            IcmpType::EchoReply => IcmpMessage::EchoReply {
+                identifier: self.identifier(),
+                sequence: self.sequence(),
+            },
+            IcmpType::EchoRequest => IcmpMessage::EchoRequest {
+                identifier: self.identifier(),
+                sequence: self.sequence(),
+            },
+            IcmpType::DestinationUnreachable => {
+                IcmpMessage::DestinationUnreachable(UnreachableCode::from(self.code()))
+            }
+            IcmpType::Redirect => IcmpMessage::Redirect(RedirectCode::from(self.code())),
+            IcmpType::TimeExceeded => {
+                IcmpMessage::TimeExceeded(TimeExceededCode::from(self.code()))
+            }
+            IcmpType::ParameterProblem => IcmpMessage::ParameterProblem {
+                pointer: self.slice.as_ref()[4],
+            },
+            _ => IcmpMessage::Other {
+                icmp_type: self.icmp_type_u8(),
+                code: self.code(),
+            },
+        }
+    }
+
+    /// Error messages carry the IP header and the first bytes of the datagram that caused them
+    pub fn is_error(&self) -> bool {
+        matches!(
+            self.icmp_type(),
+            IcmpType::DestinationUnreachable
+                | IcmpType::SourceQuench
+                | IcmpType::Redirect
+                | IcmpType::TimeExceeded
+                | IcmpType::ParameterProblem
+        )
+    }
+
+    /// Parses the original datagram embedded in the `payload` of an error message, returning its
+    /// IP header and the leading bytes of its transport header
+    pub fn original<'pkt>(&self, payload: &'pkt [u8]) -> Option<(IPv4<&'pkt [u8]>, &'pkt [u8])> {
+        if !self.is_error() {
+            return None;
+        }
+
+        IPv4::new(payload).ok()
+    }
+}
+
+/// Names used by tcpdump
+impl fmt::Display for IcmpType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IcmpType::EchoReply => f.write_str("echo reply"),
+            IcmpType::DestinationUnreachable => f.write_str("unreachable"),
+            IcmpType::SourceQuench => f.write_str("source quench"),
+            IcmpType::Redirect => f.write_str("redirect"),
+            IcmpType::EchoRequest => f.write_str("echo request"),
+            IcmpType::RouterAdvertisement => f.write_str("router advertisement"),
+            IcmpType::RouterSolicitation => f.write_str("router solicitation"),
+            IcmpType::TimeExceeded => f.write_str("time exceeded"),
+            IcmpType::ParameterProblem => f.write_str("parameter problem"),
+            IcmpType::Timestamp => f.write_str("time stamp request"),
+            IcmpType::TimestampReply => f.write_str("time stamp reply"),
+            IcmpType::Other(icmp_type) => write!(f, "type {icmp_type}"),
+        }
+    }
+}
+
+/// `ICMP echo request, id 1, seq 2` or `ICMP unreachable, code 3`
+impl<P: AsRef<[u8]>> fmt::Display for Icmp<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let icmp_type = self.icmp_type();
+
+        match icmp_type {
+            IcmpType::EchoRequest | IcmpType::EchoReply => write!(
+                f,
+                "ICMP {icmp_type}, id {}, seq {}",
+                self.identifier(),
+                self.sequence()
+            ),
+            _ => write!(f, "ICMP {icmp_type}, code {}", self.code()),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<P: AsRef<[u8]>> serde::Serialize for Icmp<P> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("Icmp", 4)?;
+        state.serialize_field("type", &self.icmp_type())?;
+        state.serialize_field("code", &self.code())?;
+        state.serialize_field("checksum", &self.csum())?;
+        state.serialize_field(
+            "rest_of_header",
+            &u32::from_be_bytes(*self.rest_of_header()),
+        )?;
+        state.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::transport::icmp::{Icmp, IcmpMessage, IcmpType, UnreachableCode};
+
+    #[test]
+    fn echo_request() {
+        let mut packet = [0x08, 0x00, 0x00, 0x00, 0x12, 0x34, 0x00, 0x01, b'h', b'i'];
+        let (mut icmp, payload) = Icmp::new_mut(&mut packet).unwrap();
+        icmp.update_csum(payload);
+
+        assert_eq!(icmp.icmp_type(), IcmpType::EchoRequest);
+        assert_eq!(
+            icmp.message(),
+            IcmpMessage::EchoRequest {
+                identifier: 0x1234,
+                sequence: 1
+            }
+        );
+        assert_eq!(icmp.csum(), 0x7D61);
+    }
+
+    #[test]
+    fn unreachable_original() {
+        let packet = [
+            0x03, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x45, 0x00, 0x00, 0x1C, 0x00, 0x00,
+            0x00, 0x00, 0x40, 0x11, 0x00, 0x00, 0x0A, 0x00, 0x00, 0x01, 0x0A, 0x00, 0x00, 0x02,
+            0x30, 0x39, 0x00, 0x35, 0x00, 0x08, 0x00, 0x00,
+        ];
+        let (icmp, payload) = Icmp::new(&packet).unwrap();
+
+        assert!(icmp.is_error());
+        assert_eq!(
+            icmp.message(),
+            IcmpMessage::DestinationUnreachable(UnreachableCode::Port)
+        );
+
+        let (ip4, transport) = icmp.original(payload).unwrap();
+        assert_eq!(ip4.destination(), &[10, 0, 0, 2]);
+        assert_eq!(transport.len(), 8);
+    }
+}
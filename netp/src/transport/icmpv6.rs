@@ -0,0 +1,486 @@
+use core::fmt;
+
+use crate::network::IPv6;
+
+#[derive(Debug)]
+pub struct Icmpv6<P = ()> {
+    slice: P,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    InvalidSize(usize),
+}
+
+impl Icmpv6<()> {
+    pub const MIN_LEN: usize = 8;
+}
+
+impl<'pkt> Icmpv6<&'pkt [u8]> {
+    pub fn new(slice: &'pkt [u8]) -> Result<(Self, &'pkt [u8]), Error> {
+        if slice.len() < Icmpv6::MIN_LEN {
+            return Err(Error::InvalidSize(slice.len()));
+        }
+
+        let (slice, rem) = slice.split_at(Icmpv6::MIN_LEN);
+
+        Ok((Self { slice }, rem))
+    }
+}
+
+impl<'pkt> Icmpv6<&'pkt mut [u8]> {
+    pub fn new_mut(slice: &'pkt mut [u8]) -> Result<(Self, &'pkt mut [u8]), Error> {
+        if slice.len() < Icmpv6::MIN_LEN {
+            return Err(Error::InvalidSize(slice.len()));
+        }
+
+        let (slice, rem) = slice.split_at_mut(Icmpv6::MIN_LEN);
+
+        Ok((Self { slice }, rem))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Icmpv6Type {
+    DestinationUnreachable,
+    PacketTooBig,
+    TimeExceeded,
+    ParameterProblem,
+    EchoRequest,
+    EchoReply,
+    MulticastListenerQuery,
+    MulticastListenerReport,
+    MulticastListenerDone,
+    RouterSolicitation,
+    RouterAdvertisement,
+    NeighborSolicitation,
+    NeighborAdvertisement,
+    Redirect,
+    Other(u8),
+}
+
+impl From<u8> for Icmpv6Type {
+    fn from(value: u8) -> Self {
+        match value {
+            1 => Self::DestinationUnreachable,
+            2 => Self::PacketTooBig,
+            3 => Self::TimeExceeded,
+            4 => Self::ParameterProblem,
+            128 => Self::EchoRequest,
+            129 => Self::EchoReply,
+            130 => Self::MulticastListenerQuery,
+            131 => Self::MulticastListenerReport,
+            132 => Self::MulticastListenerDone,
+            133 => Self::RouterSolicitation,
+            134 => Self::RouterAdvertisement,
+            135 => Self::NeighborSolicitation,
+            136 => Self::NeighborAdvertisement,
+            137 => Self::Redirect,
+            x => Self::Other(x),
+        }
+    }
+}
+
+impl From<Icmpv6Type> for u8 {
+    fn from(value: Icmpv6Type) -> Self {
+        match value {
+            Icmpv6Type::DestinationUnreachable => 1,
+            Icmpv6Type::PacketTooBig => 2,
+            Icmpv6Type::TimeExceeded => 3,
+            Icmpv6Type::ParameterProblem => 4,
+            Icmpv6Type::EchoRequest => 128,
+            Icmpv6Type::EchoReply => 129,
+            Icmpv6Type::MulticastListenerQuery => 130,
+            Icmpv6Type::MulticastListenerReport => 131,
+            Icmpv6Type::MulticastListenerDone => 132,
+            Icmpv6Type::RouterSolicitation => 133,
+            Icmpv6Type::RouterAdvertisement => 134,
+            Icmpv6Type::NeighborSolicitation => 135,
+            Icmpv6Type::NeighborAdvertisement => 136,
+            Icmpv6Type::Redirect => 137,
+            Icmpv6Type::Other(x) => x,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Unreachablev6Code {
+    NoRoute,
+    AdministrativelyProhibited,
+    BeyondScope,
+    Address,
+    Port,
+    SourcePolicy,
+    RejectRoute,
+    Other(u8),
+}
+
+impl From<u8> for Unreachablev6Code {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => Self::NoRoute,
+            1 => Self::AdministrativelyProhibited,
+            2 => Self::BeyondScope,
+            3 => Self::Address,
+            4 => Self::Port,
+            5 => Self::SourcePolicy,
+            6 => Self::RejectRoute,
+            x => Self::Other(x),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TimeExceededv6Code {
+    HopLimitExceeded,
+    FragmentReassembly,
+    Other(u8),
+}
+
+impl From<u8> for TimeExceededv6Code {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => Self::HopLimitExceeded,
+            1 => Self::FragmentReassembly,
+            x => Self::Other(x),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ParameterProblemCode {
+    ErroneousHeaderField,
+    UnrecognizedNextHeader,
+    UnrecognizedOption,
+    Other(u8),
+}
+
+impl From<u8> for ParameterProblemCode {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => Self::ErroneousHeaderField,
+            1 => Self::UnrecognizedNextHeader,
+            2 => Self::UnrecognizedOption,
+            x => Self::Other(x),
+        }
+    }
+}
+
+/// Typed view of the type/code pair of an ICMPv6 message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Icmpv6Message {
+    EchoRequest {
+        identifier: u16,
+        sequence: u16,
+    },
+    EchoReply {
+        identifier: u16,
+        sequence: u16,
+    },
+    DestinationUnreachable(Unreachablev6Code),
+    PacketTooBig {
+        mtu: u32,
+    },
+    TimeExceeded(TimeExceededv6Code),
+    ParameterProblem {
+        code: ParameterProblemCode,
+        pointer: u32,
+    },
+    RouterSolicitation,
+    RouterAdvertisement,
+    NeighborSolicitation,
+    NeighborAdvertisement,
+    Redirect,
+    Other {
+        icmp_type: u8,
+        code: u8,
+    },
+}
+
+impl<P: AsMut<[u8]> + AsRef<[u8]>> Icmpv6<P> {
+    pub fn slice_mut(&mut self) -> &mut [u8] {
+        self.slice.as_mut()
+    }
+
+    pub fn set_icmp_type(&mut self, icmp_type: Icmpv6Type) {
+        self.slice.as_mut()[0] = u8::from(icmp_type);
+    }
+
+    pub fn set_code(&mut self, code: u8) {
+        self.slice.as_mut()[1] = code;
+    }
+
+    pub fn set_csum(&mut self, csum: u16) {
+        self.slice.as_mut()[2..4].copy_from_slice(&csum.to_be_bytes());
+    }
+
+    pub fn update_csum(&mut self, source: &[u8; 16], destination: &[u8; 16], payload: &[u8]) {
+        self.set_csum(self.calc_csum(source, destination, payload))
+    }
+
+    pub fn set_identifier(&mut self, identifier: u16) {
+        self.slice.as_mut()[4..6].copy_from_slice(&identifier.to_be_bytes());
+    }
+
+    pub fn set_sequence(&mut self, sequence: u16) {
+        self.slice.as_mut()[6..8].copy_from_slice(&sequence.to_be_bytes());
+    }
+
+    pub fn set_rest_of_header(&mut self, rest: &[u8; 4]) {
+        self.slice.as_mut()[4..8].copy_from_slice(rest);
+    }
+}
+
+impl<P: AsRef<[u8]>> Icmpv6<P> {
+    pub fn slice(&self) -> &[u8] {
+        self.slice.as_ref()
+    }
+
+    pub fn icmp_type(&self) -> Icmpv6Type {
+        Icmpv6Type::from(self.slice.as_ref()[0])
+    }
+
+    pub fn icmp_type_u8(&self) -> u8 {
+        self.slice.as_ref()[0]
+    }
+
+    pub fn code(&self) -> u8 {
+        self.slice.as_ref()[1]
+    }
+
+    pub fn csum(&self) -> u16 {
+        u16::from_be_bytes(*self.slice.as_ref()[2..4].first_chunk::<2>().unwrap())
+    }
+
+    /// Unlike ICMP, the ICMPv6 checksum also covers the IPv6 pseudo-header
+    pub fn calc_csum(&self, source: &[u8; 16], destination: &[u8; 16], payload: &[u8]) -> u16 {
+        let length = (Icmpv6::MIN_LEN + payload.len()) as u32;
+
+        etherparse::checksum::Sum16BitWords::new()
+            .add_16bytes(*source)
+            .add_16bytes(*destination)
+            .add_4bytes(length.to_be_bytes())
+            .add_4bytes([0, 0, 0, 58])
+            .add_2bytes([self.icmp_type_u8(), self.code()])
+            .add_4bytes(*self.rest_of_header())
+            .add_slice(payload)
+            .ones_complement()
+            .to_be()
+    }
+
+    pub fn rest_of_header(&self) -> &[u8; 4] {
+        self.slice.as_ref()[4..8].first_chunk::<4>().unwrap()
+    }
+
+    /// Only meaningful for echo messages
+    pub fn identifier(&self) -> u16 {
+        u16::from_be_bytes(*self.slice.as_ref()[4..6].first_chunk::<2>().unwrap())
+    }
+
+    /// Only meaningful for echo messages
+    pub fn sequence(&self) -> u16 {
+        u16::from_be_bytes(*self.slice.as_ref()[6..8].first_chunk::<2>().unwrap())
+    }
+
+    /// Only meaningful for packet too big messages
+    pub fn mtu(&self) -> u32 {
+        u32::from_be_bytes(*self.rest_of_header())
+    }
+
+    /// Only meaningful for parameter problem messages
+    pub fn pointer(&self) -> u32 {
+        u32::from_be_bytes(*self.rest_of_header())
+    }
+
+    pub fn message(&self) -> Icmpv6Message {
+        match self.icmp_type() {
+            Icmpv6Type::EchoRequest => Icmpv6Message::EchoRequest {
+                identifier: self.identifier(),
+                sequence: self.sequence(),
+            },
+            Icmpv6Type::EchoReply => Icmpv6Message::EchoReply {
+                identifier: self.identifier(),
+                sequence: self.sequence(),
+            },
+            Icmpv6Type::DestinationUnreachable => {
+                Icmpv6Message::DestinationUnreachable(Unreachablev6Code::from(self.code()))
+            }
+            Icmpv6Type::PacketTooBig => Icmpv6Message::PacketTooBig { mtu: self.mtu() },
+            Icmpv6Type::TimeExceeded => {
+                Icmpv6Message::TimeExceeded(TimeExceededv6Code::from(self.code()))
+            }
+            Icmpv6Type::ParameterProblem => Icmpv6Message::ParameterProblem {
+                code: ParameterProblemCode::from(self.code()),
+                pointer: self.pointer(),
+            },
+            Icmpv6Type::RouterSolicitation => Icmpv6Message::RouterSolicitation,
+            Icmpv6Type::RouterAdvertisement => Icmpv6Message::RouterAdvertisement,
+            Icmpv6Type::NeighborSolicitation => Icmpv6Message::NeighborSolicitation,
+            Icmpv6Type::NeighborAdvertisement => Icmpv6Message::NeighborAdvertisement,
+            Icmpv6Type::Redirect => Icmpv6Message::Redirect,
+            _ => Icmpv6Message::Other {
+                icmp_type: self.icmp_type_u8(),
+                code: self.code(),
+            },
+        }
+    }
+
+    /// Error messages have the high bit of the type cleared
+    pub fn is_error(&self) -> bool {
+        self.icmp_type_u8() < 128
+    }
+
+    /// Parses the original packet embedded in the `payload` of an error message, returning its
+    /// IPv6 header and whatever follows it
+    pub fn original<'pkt>(&self, payload: &'pkt [u8]) -> Option<(IPv6<&'pkt [u8]>, &'pkt [u8])> {
+        if !self.is_error() {
+            return None;
+        }
+
+        IPv6::new(payload).ok()
+    }
+}
+
+/// Names used by tcpdump
+impl fmt::Display for Icmpv6Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Icmpv6Type::DestinationUnreachable => f.write_str("destination unreachable"),
+            Icmpv6Type::PacketTooBig => f.write_str("packet too big"),
+            Icmpv6Type::TimeExceeded => f.write_str("time exceeded in-transit"),
+            Icmpv6Type::ParameterProblem => f.write_str("parameter problem"),
+            Icmpv6Type::EchoRequest => f.write_str("echo request"),
+            Icmpv6Type::EchoReply => f.write_str("echo reply"),
+            Icmpv6Type::MulticastListenerQuery => f.write_str("multicast listener query"),
+            Icmpv6Type::MulticastListenerReport => f.write_str("multicast listener report"),
+            Icmpv6Type::MulticastListenerDone => f.write_str("multicast listener done"),
+            Icmpv6Type::RouterSolicitation => f.write_str("router solicitation"),
+            Icmpv6Type::RouterAdvertisement => f.write_str("router advertisement"),
+            Icmpv6Type::NeighborSolicitation => f.write_str("neighbor solicitation"),
+            Icmpv6Type::NeighborAdvertisement => f.write_str("neighbor advertisement"),
+            Icmpv6Type::Redirect => f.write_str("redirect"),
+            Icmpv6Type::Other(icmp_type) => write!(f, "type {icmp_type}"),
+        }
+    }
+}
+
+/// `ICMP6, echo request, id 1, seq 2` or `ICMP6, packet too big, mtu 1280`
+impl<P: AsRef<[u8]>> fmt::Display for Icmpv6<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let icmp_type = self.icmp_type();
+
+        match icmp_type {
+            Icmpv6Type::EchoRequest | Icmpv6Type::EchoReply => write!(
+                f,
+                "ICMP6, {icmp_type}, id {}, seq {}",
+                self.identifier(),
+                self.sequence()
+            ),
+            Icmpv6Type::PacketTooBig => write!(f, "ICMP6, {icmp_type}, mtu {}", self.mtu()),
+            _ => write!(f, "ICMP6, {icmp_type}, code {}", self.code()),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<P: AsRef<[u8]>> serde::Serialize for Icmpv6<P> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("Icmpv6", 4)?;
+        state.serialize_field("type", &self.icmp_type())?;
+        state.serialize_field("code", &self.code())?;
+        state.serialize_field("checksum", &self.csum())?;
+        state.serialize_field(
+            "rest_of_header",
+            &u32::from_be_bytes(*self.rest_of_header()),
+        )?;
+        state.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::network::IPv6;
+    use crate::transport::icmpv6::{
+        Icmpv6, Icmpv6Message, Icmpv6Type, TimeExceededv6Code, Unreachablev6Code,
+    };
+
+    /// Echo request from `::1` to itself as sent by `ping -6 -s 16 ::1`, the checksum was computed
+    /// outside of this crate
+    const ECHO_REQUEST: [u8; 64] = [
+        0x60, 0x0E, 0x2F, 0x5A, 0x00, 0x18, 0x3A, 0x40, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x80, 0x00, 0xAA, 0xBE, 0x1C,
+        0x2B, 0x00, 0x01, 0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18, 0x19, 0x1A, 0x1B,
+        0x1C, 0x1D, 0x1E, 0x1F,
+    ];
+
+    #[test]
+    fn echo_request() {
+        let (ip6, rem) = IPv6::new(&ECHO_REQUEST).unwrap();
+        let (icmp, payload) = Icmpv6::new(rem).unwrap();
+
+        assert_eq!(icmp.icmp_type(), Icmpv6Type::EchoRequest);
+        assert_eq!(icmp.code(), 0);
+        assert!(!icmp.is_error());
+        assert_eq!(icmp.identifier(), 0x1C2B);
+        assert_eq!(icmp.sequence(), 1);
+        assert_eq!(
+            icmp.message(),
+            Icmpv6Message::EchoRequest {
+                identifier: 0x1C2B,
+                sequence: 1
+            }
+        );
+        assert_eq!(icmp.csum(), 0xAABE);
+        assert_eq!(
+            icmp.calc_csum(ip6.source(), ip6.destination(), payload),
+            icmp.csum()
+        );
+    }
+
+    #[test]
+    fn update_csum() {
+        let mut packet = ECHO_REQUEST;
+        let (source, destination) = (*IPv6::new(&packet).unwrap().0.source(), [0; 16]);
+        let (mut icmp, payload) = Icmpv6::new_mut(&mut packet[40..]).unwrap();
+
+        icmp.set_icmp_type(Icmpv6Type::EchoReply);
+        icmp.set_csum(0);
+        icmp.update_csum(&source, &destination, payload);
+        assert_eq!(icmp.icmp_type(), Icmpv6Type::EchoReply);
+        assert_ne!(icmp.csum(), 0xAABE);
+        assert_eq!(icmp.calc_csum(&source, &destination, payload), icmp.csum());
+    }
+
+    #[test]
+    fn type_code() {
+        let packet = [0x01, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+        let (icmp, _) = Icmpv6::new(&packet).unwrap();
+        assert!(icmp.is_error());
+        assert_eq!(
+            icmp.message(),
+            Icmpv6Message::DestinationUnreachable(Unreachablev6Code::Port)
+        );
+
+        let packet = [0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x05, 0x00];
+        let (icmp, _) = Icmpv6::new(&packet).unwrap();
+        assert_eq!(icmp.message(), Icmpv6Message::PacketTooBig { mtu: 1280 });
+
+        let packet = [0x03, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+        let (icmp, _) = Icmpv6::new(&packet).unwrap();
+        assert_eq!(
+            icmp.message(),
+            Icmpv6Message::TimeExceeded(TimeExceededv6Code::FragmentReassembly)
+        );
+
+        let packet = [0x87, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+        let (icmp, _) = Icmpv6::new(&packet).unwrap();
+        assert_eq!(icmp.icmp_type(), Icmpv6Type::NeighborSolicitation);
+        assert_eq!(u8::from(Icmpv6Type::from(200)), 200);
+
+        assert!(Icmpv6::new(&packet[..7]).is_err());
+    }
+}
@@ -0,0 +1,424 @@
+use core::fmt;
+use core::net::Ipv4Addr;
+
+/// IGMP message. Version 3 queries include their source list, the group records of version 3
+/// reports follow the 8 byte header and are read with `group_records`
+#[derive(Debug)]
+pub struct Igmp<P = ()> {
+    slice: P,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    InvalidSize(usize),
+    /// The source list of a version 3 query does not fit, holds the available bytes and the size
+    /// of the query
+    InvalidSizeForSources(usize, usize),
+}
+
+/// Raised by the group record iterator when a record runs past the end of the report
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GroupRecordError;
+
+impl Igmp<()> {
+    pub const MIN_LEN: usize = 8;
+    pub const V3_QUERY_MIN_LEN: usize = 12;
+}
+
+fn message_size(slice: &[u8]) -> Result<usize, Error> {
+    if slice.len() < Igmp::MIN_LEN {
+        return Err(Error::InvalidSize(slice.len()));
+    }
+
+    // Queries longer than 8 bytes are version 3 queries
+    if slice[0] != 0x11 || slice.len() < Igmp::V3_QUERY_MIN_LEN {
+        return Ok(Igmp::MIN_LEN);
+    }
+
+    let sources = u16::from_be_bytes([slice[10], slice[11]]) as usize;
+    let size = Igmp::V3_QUERY_MIN_LEN + sources * 4;
+
+    if slice.len() < size {
+        return Err(Error::InvalidSizeForSources(slice.len(), size));
+    }
+
+    Ok(size)
+}
+
+impl<'pkt> Igmp<&'pkt [u8]> {
+    pub fn new(slice: &'pkt [u8]) -> Result<(Self, &'pkt [u8]), Error> {
+        let size = message_size(slice)?;
+        let (slice, rem) = slice.split_at(size);
+
+        Ok((Self { slice }, rem))
+    }
+}
+
+impl<'pkt> Igmp<&'pkt mut [u8]> {
+    pub fn new_mut(slice: &'pkt mut [u8]) -> Result<(Self, &'pkt mut [u8]), Error> {
+        let size = message_size(slice)?;
+        let (slice, rem) = slice.split_at_mut(size);
+
+        Ok((Self { slice }, rem))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(u8)]
+pub enum IgmpType {
+    MembershipQuery = 0x11,
+    MembershipReportV1 = 0x12,
+    MembershipReportV2 = 0x16,
+    LeaveGroup = 0x17,
+    MembershipReportV3 = 0x22,
+    Other(u8),
+}
+
+impl From<u8> for IgmpType {
+    fn from(value: u8) -> Self {
+        match value {
+            0x11 => Self::MembershipQuery,
+            0x12 => Self::MembershipReportV1,
+            0x16 => Self::MembershipReportV2,
+            0x17 => Self::LeaveGroup,
+            0x22 => Self::MembershipReportV3,
+            x => Self::Other(x),
+        }
+    }
+}
+
+impl From<IgmpType> for u8 {
+    fn from(value: IgmpType) -> Self {
+        match value {
+            IgmpType::MembershipQuery => 0x11,
+            IgmpType::MembershipReportV1 => 0x12,
+            IgmpType::MembershipReportV2 => 0x16,
+            IgmpType::LeaveGroup => 0x17,
+            IgmpType::MembershipReportV3 => 0x22,
+            IgmpType::Other(x) => x,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(u8)]
+pub enum GroupRecordType {
+    ModeIsInclude = 1,
+    ModeIsExclude = 2,
+    ChangeToInclude = 3,
+    ChangeToExclude = 4,
+    AllowNewSources = 5,
+    BlockOldSources = 6,
+    Other(u8),
+}
+
+impl From<u8> for GroupRecordType {
+    fn from(value: u8) -> Self {
+        match value {
+            1 => Self::ModeIsInclude,
+            2 => Self::ModeIsExclude,
+            3 => Self::ChangeToInclude,
+            4 => Self::ChangeToExclude,
+            5 => Self::AllowNewSources,
+            6 => Self::BlockOldSources,
+            x => Self::Other(x),
+        }
+    }
+}
+
+impl<P: AsMut<[u8]> + AsRef<[u8]>> Igmp<P> {
+    pub fn slice_mut(&mut self) -> &mut [u8] {
+        self.slice.as_mut()
+    }
+
+    pub fn set_csum(&mut self, csum: u16) {
+        self.slice.as_mut()[2..4].copy_from_slice(&csum.to_be_bytes());
+    }
+
+    /// `payload` holds the group records of version 3 reports
+    pub fn update_csum(&mut self, payload: &[u8]) {
+        self.set_csum(self.calc_csum(payload))
+    }
+}
+
+impl<P: AsRef<[u8]>> Igmp<P> {
+    pub fn slice(&self) -> &[u8] {
+        self.slice.as_ref()
+    }
+
+    pub fn igmp_type(&self) -> IgmpType {
+        IgmpType::from(self.igmp_type_u8())
+    }
+
+    pub fn igmp_type_u8(&self) -> u8 {
+        self.slice.as_ref()[0]
+    }
+
+    /// Zero in version 1 queries. Version 3 encodes large values with a floating point format
+    pub fn max_resp_code(&self) -> u8 {
+        self.slice.as_ref()[1]
+    }
+
+    pub fn csum(&self) -> u16 {
+        u16::from_be_bytes(*self.slice.as_ref()[2..4].first_chunk::<2>().unwrap())
+    }
+
+    /// The checksum covers the whole message, `payload` holds the group records of version 3
+    /// reports
+    pub fn calc_csum(&self, payload: &[u8]) -> u16 {
+        let slice = self.slice.as_ref();
+
+        etherparse::checksum::Sum16BitWords::new()
+            .add_2bytes([slice[0], slice[1]])
+            .add_slice(&slice[4..])
+            .add_slice(payload)
+            .ones_complement()
+            .to_be()
+    }
+
+    /// Version of the protocol, told apart by the message type and the length of queries
+    pub fn version(&self) -> u8 {
+        match self.igmp_type() {
+            IgmpType::MembershipQuery if self.slice.as_ref().len() >= Igmp::V3_QUERY_MIN_LEN => 3,
+            IgmpType::MembershipQuery if self.max_resp_code() == 0 => 1,
+            IgmpType::MembershipReportV1 => 1,
+            IgmpType::MembershipReportV3 => 3,
+            _ => 2,
+        }
+    }
+
+    /// Group of queries, version 1 and 2 reports and leaves. Zero in general queries
+    pub fn group_address(&self) -> &[u8; 4] {
+        self.slice.as_ref()[4..8].first_chunk::<4>().unwrap()
+    }
+
+    /// Source list of version 3 queries, empty for other messages
+    pub fn sources(&self) -> Addresses<'_> {
+        Addresses {
+            slice: self
+                .slice
+                .as_ref()
+                .get(Igmp::V3_QUERY_MIN_LEN..)
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Only meaningful for version 3 reports
+    pub fn number_of_group_records(&self) -> u16 {
+        u16::from_be_bytes(*self.slice.as_ref()[6..8].first_chunk::<2>().unwrap())
+    }
+
+    /// Iterates over the group records in the `payload` of a version 3 report, at most
+    /// `number_of_group_records`. Empty for other messages
+    pub fn group_records<'pkt>(&self, payload: &'pkt [u8]) -> GroupRecords<'pkt> {
+        let remaining = match self.igmp_type() {
+            IgmpType::MembershipReportV3 => self.number_of_group_records(),
+            _ => 0,
+        };
+
+        GroupRecords {
+            slice: payload,
+            remaining,
+        }
+    }
+}
+
+/// IPv4 addresses packed one after the other
+#[derive(Clone)]
+pub struct Addresses<'pkt> {
+    slice: &'pkt [u8],
+}
+
+impl Iterator for Addresses<'_> {
+    type Item = [u8; 4];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (address, rem) = self.slice.split_first_chunk::<4>()?;
+        self.slice = rem;
+
+        Some(*address)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct GroupRecord<'pkt> {
+    slice: &'pkt [u8],
+}
+
+impl<'pkt> GroupRecord<'pkt> {
+    pub const MIN_LEN: usize = 8;
+
+    pub fn record_type(&self) -> GroupRecordType {
+        GroupRecordType::from(self.slice[0])
+    }
+
+    pub fn multicast_address(&self) -> &'pkt [u8; 4] {
+        self.slice[4..8].first_chunk::<4>().unwrap()
+    }
+
+    pub fn number_of_sources(&self) -> u16 {
+        u16::from_be_bytes(*self.slice[2..4].first_chunk::<2>().unwrap())
+    }
+
+    pub fn sources(&self) -> Addresses<'pkt> {
+        let end = Self::MIN_LEN + self.number_of_sources() as usize * 4;
+
+        Addresses {
+            slice: &self.slice[Self::MIN_LEN..end],
+        }
+    }
+
+    /// A host joins the group with an exclude record, possibly with an empty source list
+    pub fn is_join(&self) -> bool {
+        matches!(
+            self.record_type(),
+            GroupRecordType::ModeIsExclude | GroupRecordType::ChangeToExclude
+        )
+    }
+
+    /// A host leaves the group with an include record and an empty source list
+    pub fn is_leave(&self) -> bool {
+        self.record_type() == GroupRecordType::ChangeToInclude && self.number_of_sources() == 0
+    }
+}
+
+/// Stops after the first malformed record
+pub struct GroupRecords<'pkt> {
+    slice: &'pkt [u8],
+    remaining: u16,
+}
+
+impl<'pkt> Iterator for GroupRecords<'pkt> {
+    type Item = Result<GroupRecord<'pkt>, GroupRecordError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        self.remaining -= 1;
+
+        let len = match self.slice.first_chunk::<{ GroupRecord::MIN_LEN }>() {
+            // Auxiliary data is counted in 32-bit words
+            Some(header) => {
+                let sources = u16::from_be_bytes([header[2], header[3]]) as usize;
+                GroupRecord::MIN_LEN + sources * 4 + header[1] as usize * 4
+            }
+            None => usize::MAX,
+        };
+
+        if len > self.slice.len() {
+            self.remaining = 0;
+            return Some(Err(GroupRecordError));
+        }
+
+        let (slice, rem) = self.slice.split_at(len);
+        self.slice = rem;
+
+        Some(Ok(GroupRecord { slice }))
+    }
+}
+
+/// `igmp v2 report 239.1.1.1` or `igmp query v3 [gaddr 239.1.1.1]`
+impl<P: AsRef<[u8]>> fmt::Display for Igmp<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let group = Ipv4Addr::from(*self.group_address());
+
+        match self.igmp_type() {
+            IgmpType::MembershipQuery if group.is_unspecified() => {
+                write!(f, "igmp query v{}", self.version())
+            }
+            IgmpType::MembershipQuery => {
+                write!(f, "igmp query v{} [gaddr {group}]", self.version())
+            }
+            IgmpType::MembershipReportV1 => write!(f, "igmp v1 report {group}"),
+            IgmpType::MembershipReportV2 => write!(f, "igmp v2 report {group}"),
+            IgmpType::LeaveGroup => write!(f, "igmp leave {group}"),
+            IgmpType::MembershipReportV3 => write!(
+                f,
+                "igmp v3 report, {} group record(s)",
+                self.number_of_group_records()
+            ),
+            IgmpType::Other(igmp_type) => write!(f, "igmp type {igmp_type:#x}"),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<P: AsRef<[u8]>> serde::Serialize for Igmp<P> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("Igmp", 5)?;
+        state.serialize_field("type", &self.igmp_type())?;
+        state.serialize_field("version", &self.version())?;
+        state.serialize_field("max_resp_code", &self.max_resp_code())?;
+        state.serialize_field("checksum", &self.csum())?;
+        state.serialize_field("group_address", &Ipv4Addr::from(*self.group_address()))?;
+        state.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::transport::igmp::{GroupRecordType, Igmp, IgmpType};
+
+    #[test]
+    fn v2_report() {
+        let mut packet = [0x16, 0x00, 0x00, 0x00, 0xEF, 0x01, 0x01, 0x01];
+        let (mut igmp, rem) = Igmp::new_mut(&mut packet).unwrap();
+
+        assert!(rem.is_empty());
+        assert_eq!(igmp.igmp_type(), IgmpType::MembershipReportV2);
+        assert_eq!(igmp.version(), 2);
+        assert_eq!(igmp.group_address(), &[239, 1, 1, 1]);
+
+        igmp.update_csum(&[]);
+        assert_eq!(igmp.csum(), 0xF9FC);
+        assert_eq!(igmp.calc_csum(&[]), igmp.csum());
+    }
+
+    #[test]
+    fn v3_query() {
+        let packet = [
+            0x11, 0x64, 0x00, 0x00, 0xEF, 0x01, 0x01, 0x01, 0x02, 0x7D, 0x00, 0x01, 0x0A, 0x00,
+            0x00, 0x01,
+        ];
+        let (igmp, _) = Igmp::new(&packet).unwrap();
+
+        assert_eq!(igmp.version(), 3);
+        assert!(igmp.sources().eq([[10, 0, 0, 1]]));
+        assert!(Igmp::new(&packet[..14]).is_err());
+    }
+
+    #[test]
+    fn v3_report() {
+        let packet = [
+            0x22, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, // Header with 2 records
+            0x04, 0x00, 0x00, 0x00, 0xEF, 0x01, 0x01, 0x01, // Join 239.1.1.1
+            0x01, 0x01, 0x00, 0x01, 0xEF, 0x02, 0x02,
+            0x02, // Include with a source and aux data
+            0x0A, 0x00, 0x00, 0x01, 0xFF, 0xFF, 0xFF, 0xFF,
+        ];
+        let (igmp, rem) = Igmp::new(&packet).unwrap();
+
+        assert_eq!(igmp.version(), 3);
+        assert_eq!(igmp.sources().count(), 0);
+
+        let mut records = igmp.group_records(rem);
+        let join = records.next().unwrap().unwrap();
+        assert!(join.is_join());
+        assert_eq!(join.multicast_address(), &[239, 1, 1, 1]);
+
+        let include = records.next().unwrap().unwrap();
+        assert_eq!(include.record_type(), GroupRecordType::ModeIsInclude);
+        assert!(include.sources().eq([[10, 0, 0, 1]]));
+        assert!(records.next().is_none());
+
+        let mut records = igmp.group_records(&rem[..20]);
+        records.next();
+        assert!(records.next().unwrap().is_err());
+    }
+}
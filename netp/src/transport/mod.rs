@@ -1,2 +1,7 @@
+pub mod icmp;
+pub mod icmpv6;
+pub mod igmp;
+pub mod sctp;
 pub mod tcp;
-pub mod udp;
\ No newline at end of file
+pub mod tcpopt;
+pub mod udp;
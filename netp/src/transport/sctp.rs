@@ -0,0 +1,303 @@
+use core::fmt;
+
+/// SCTP common header, the chunks follow it
+#[derive(Debug)]
+pub struct Sctp<P = ()> {
+    slice: P,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    InvalidSize(usize),
+}
+
+/// Raised by the chunk iterator when a chunk length is shorter than the chunk header or runs past
+/// the end of the packet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkError;
+
+impl Sctp<()> {
+    pub const MIN_LEN: usize = 12;
+}
+
+impl<'pkt> Sctp<&'pkt [u8]> {
+    pub fn new(slice: &'pkt [u8]) -> Result<(Self, &'pkt [u8]), Error> {
+        if slice.len() < Sctp::MIN_LEN {
+            return Err(Error::InvalidSize(slice.len()));
+        }
+
+        let (slice, rem) = slice.split_at(Sctp::MIN_LEN);
+
+        Ok((Self { slice }, rem))
+    }
+}
+
+impl<'pkt> Sctp<&'pkt mut [u8]> {
+    pub fn new_mut(slice: &'pkt mut [u8]) -> Result<(Self, &'pkt mut [u8]), Error> {
+        if slice.len() < Sctp::MIN_LEN {
+            return Err(Error::InvalidSize(slice.len()));
+        }
+
+        let (slice, rem) = slice.split_at_mut(Sctp::MIN_LEN);
+
+        Ok((Self { slice }, rem))
+    }
+}
+
+impl<P: AsMut<[u8]> + AsRef<[u8]>> Sctp<P> {
+    pub fn slice_mut(&mut self) -> &mut [u8] {
+        self.slice.as_mut()
+    }
+
+    pub fn set_source(&mut self, source: u16) {
+        self.slice.as_mut()[0..2].copy_from_slice(&source.to_be_bytes());
+    }
+
+    pub fn set_destination(&mut self, destination: u16) {
+        self.slice.as_mut()[2..4].copy_from_slice(&destination.to_be_bytes());
+    }
+}
+
+impl<P: AsRef<[u8]>> Sctp<P> {
+    pub fn slice(&self) -> &[u8] {
+        self.slice.as_ref()
+    }
+
+    pub fn source(&self) -> u16 {
+        u16::from_be_bytes(*self.slice.as_ref()[0..2].first_chunk::<2>().unwrap())
+    }
+
+    pub fn destination(&self) -> u16 {
+        u16::from_be_bytes(*self.slice.as_ref()[2..4].first_chunk::<2>().unwrap())
+    }
+
+    /// Zero only in packets carrying an INIT chunk
+    pub fn verification_tag(&self) -> u32 {
+        u32::from_be_bytes(*self.slice.as_ref()[4..8].first_chunk::<4>().unwrap())
+    }
+
+    /// CRC32c of the packet, as found on the wire
+    pub fn checksum(&self) -> &[u8; 4] {
+        self.slice.as_ref()[8..12].first_chunk::<4>().unwrap()
+    }
+
+    pub fn size_usize(&self) -> usize {
+        Sctp::MIN_LEN
+    }
+
+    /// Iterates over the chunks of `payload`, the bytes following the common header
+    pub fn chunks<'pkt>(&self, payload: &'pkt [u8]) -> Chunks<'pkt> {
+        Chunks { slice: payload }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(u8)]
+pub enum ChunkType {
+    Data = 0,
+    Init = 1,
+    InitAck = 2,
+    Sack = 3,
+    Heartbeat = 4,
+    HeartbeatAck = 5,
+    Abort = 6,
+    Shutdown = 7,
+    ShutdownAck = 8,
+    Error = 9,
+    CookieEcho = 10,
+    CookieAck = 11,
+    ShutdownComplete = 14,
+    Other(u8),
+}
+
+impl From<u8> for ChunkType {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => Self::Data,
+            1 => Self::Init,
+            2 => Self::InitAck,
+            3 => Self::Sack,
+            4 => Self::Heartbeat,
+            5 => Self::HeartbeatAck,
+            6 => Self::Abort,
+            7 => Self::Shutdown,
+            8 => Self::ShutdownAck,
+            9 => Self::Error,
+            10 => Self::CookieEcho,
+            11 => Self::CookieAck,
+            14 => Self::ShutdownComplete,
+            x => Self::Other(x),
+        }
+    }
+}
+
+impl From<ChunkType> for u8 {
+    fn from(value: ChunkType) -> Self {
+        match value {
+            ChunkType::Data => 0,
+            ChunkType::Init => 1,
+            ChunkType::InitAck => 2,
+            ChunkType::Sack => 3,
+            ChunkType::Heartbeat => 4,
+            ChunkType::HeartbeatAck => 5,
+            ChunkType::Abort => 6,
+            ChunkType::Shutdown => 7,
+            ChunkType::ShutdownAck => 8,
+            ChunkType::Error => 9,
+            ChunkType::CookieEcho => 10,
+            ChunkType::CookieAck => 11,
+            ChunkType::ShutdownComplete => 14,
+            ChunkType::Other(x) => x,
+        }
+    }
+}
+
+/// A chunk without its padding
+#[derive(Debug, Clone, Copy)]
+pub struct Chunk<'pkt> {
+    slice: &'pkt [u8],
+}
+
+impl<'pkt> Chunk<'pkt> {
+    pub const HEADER_LEN: usize = 4;
+
+    pub fn slice(&self) -> &'pkt [u8] {
+        self.slice
+    }
+
+    pub fn chunk_type(&self) -> ChunkType {
+        ChunkType::from(self.slice[0])
+    }
+
+    pub fn chunk_type_u8(&self) -> u8 {
+        self.slice[0]
+    }
+
+    pub fn flags(&self) -> u8 {
+        self.slice[1]
+    }
+
+    /// Length of the chunk header and value, without padding
+    pub fn length(&self) -> u16 {
+        u16::from_be_bytes(*self.slice[2..4].first_chunk::<2>().unwrap())
+    }
+
+    pub fn value(&self) -> &'pkt [u8] {
+        &self.slice[Self::HEADER_LEN..]
+    }
+
+    /// Payload protocol identifier of DATA chunks, e.g. 3 for M3UA or 46 for Diameter
+    pub fn ppid(&self) -> Option<u32> {
+        if self.chunk_type() != ChunkType::Data {
+            return None;
+        }
+
+        let ppid = self.value().get(8..12)?.first_chunk::<4>()?;
+        Some(u32::from_be_bytes(*ppid))
+    }
+}
+
+/// Stops after the first malformed chunk
+#[derive(Clone)]
+pub struct Chunks<'pkt> {
+    slice: &'pkt [u8],
+}
+
+impl<'pkt> Iterator for Chunks<'pkt> {
+    type Item = Result<Chunk<'pkt>, ChunkError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.slice.is_empty() {
+            return None;
+        }
+
+        let len = match self.slice.get(2..4) {
+            Some(len) => u16::from_be_bytes([len[0], len[1]]) as usize,
+            None => 0,
+        };
+
+        if len < Chunk::HEADER_LEN || len > self.slice.len() {
+            self.slice = &[];
+            return Some(Err(ChunkError));
+        }
+
+        let chunk = Chunk {
+            slice: &self.slice[..len],
+        };
+
+        // The padding of the last chunk may be left out
+        let padded = len.next_multiple_of(4).min(self.slice.len());
+        self.slice = &self.slice[padded..];
+
+        Some(Ok(chunk))
+    }
+}
+
+/// `36412 > 36412: SCTP`
+impl<P: AsRef<[u8]>> fmt::Display for Sctp<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} > {}: SCTP", self.source(), self.destination())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<P: AsRef<[u8]>> serde::Serialize for Sctp<P> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("Sctp", 4)?;
+        state.serialize_field("source", &self.source())?;
+        state.serialize_field("destination", &self.destination())?;
+        state.serialize_field("verification_tag", &self.verification_tag())?;
+        state.serialize_field("checksum", &u32::from_be_bytes(*self.checksum()))?;
+        state.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::transport::sctp::{ChunkType, Sctp};
+
+    #[test]
+    fn chunks() {
+        let packet = [
+            0x0B, 0x59, 0x0B, 0x59, 0x00, 0x00, 0x00, 0x2A, 0x00, 0x00, 0x00, 0x00, // Header
+            0x00, 0x03, 0x00, 0x13, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x03, 0xAA, 0xBB, 0xCC, 0x00, // DATA with 3 bytes of user data and padding
+            0x0B, 0x00, 0x00, 0x04, // COOKIE ACK
+        ];
+        let (sctp, rem) = Sctp::new(&packet).unwrap();
+
+        assert_eq!(sctp.source(), 2905);
+        assert_eq!(sctp.verification_tag(), 42);
+
+        let mut chunks = sctp.chunks(rem);
+        let data = chunks.next().unwrap().unwrap();
+        assert_eq!(data.chunk_type(), ChunkType::Data);
+        assert_eq!(data.flags(), 0x03);
+        assert_eq!(data.length(), 19);
+        assert_eq!(data.ppid(), Some(3));
+        assert_eq!(&data.value()[12..], &[0xAA, 0xBB, 0xCC]);
+
+        let cookie_ack = chunks.next().unwrap().unwrap();
+        assert_eq!(cookie_ack.chunk_type(), ChunkType::CookieAck);
+        assert_eq!(cookie_ack.ppid(), None);
+        assert!(chunks.next().is_none());
+    }
+
+    #[test]
+    fn malformed_chunk() {
+        let packet = [
+            0x0B, 0x59, 0x0B, 0x59, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00,
+            0x00, 0x14, 0x00, 0x00,
+        ];
+        let (sctp, rem) = Sctp::new(&packet).unwrap();
+
+        let mut chunks = sctp.chunks(rem);
+        assert!(chunks.next().unwrap().is_err());
+        assert!(chunks.next().is_none());
+
+        assert!(Sctp::new(&packet[..8]).is_err());
+    }
+}
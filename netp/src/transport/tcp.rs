@@ -1,8 +1,12 @@
+use core::fmt;
+
+#[derive(Debug)]
 pub struct Tcp<P = ()> {
     slice: P,
     size: TcpSize,
 }
 
+#[derive(Debug)]
 pub enum Error {
     InvalidSize(usize),
     InvalidSizeForOffset(usize, TcpSize),
@@ -52,7 +56,139 @@ impl<'pkt> Tcp<&'pkt mut [u8]> {
     }
 }
 
-impl Tcp<&[u8]> {
+impl<P: AsMut<[u8]> + AsRef<[u8]>> Tcp<P> {
+    pub fn slice_mut(&mut self) -> &mut [u8] {
+        self.slice.as_mut()
+    }
+
+    pub fn set_source(&mut self, source: u16) {
+        self.slice.as_mut()[0..2].copy_from_slice(&source.to_be_bytes())
+    }
+
+    pub fn set_destination(&mut self, destination: u16) {
+        self.slice.as_mut()[2..4].copy_from_slice(&destination.to_be_bytes())
+    }
+
+    pub fn set_sequence_num(&mut self, sequence_num: u32) {
+        self.slice.as_mut()[4..8].copy_from_slice(&sequence_num.to_be_bytes())
+    }
+
+    pub fn set_ack_num(&mut self, ack_num: u32) {
+        self.slice.as_mut()[8..12].copy_from_slice(&ack_num.to_be_bytes())
+    }
+
+    pub fn set_flags(&mut self, flags: u8) {
+        self.slice.as_mut()[13] = flags;
+    }
+
+    fn set_flag(&mut self, bit: u8, value: bool) {
+        if value {
+            self.slice.as_mut()[13] |= 1 << bit;
+        } else {
+            self.slice.as_mut()[13] &= !(1 << bit);
+        }
+    }
+
+    pub fn set_cwr(&mut self, value: bool) {
+        self.set_flag(7, value)
+    }
+
+    pub fn set_ece(&mut self, value: bool) {
+        self.set_flag(6, value)
+    }
+
+    pub fn set_urg(&mut self, value: bool) {
+        self.set_flag(5, value)
+    }
+
+    pub fn set_ack(&mut self, value: bool) {
+        self.set_flag(4, value)
+    }
+
+    pub fn set_psh(&mut self, value: bool) {
+        self.set_flag(3, value)
+    }
+
+    pub fn set_rst(&mut self, value: bool) {
+        self.set_flag(2, value)
+    }
+
+    pub fn set_syn(&mut self, value: bool) {
+        self.set_flag(1, value)
+    }
+
+    pub fn set_fin(&mut self, value: bool) {
+        self.set_flag(0, value)
+    }
+
+    pub fn set_window_size(&mut self, window_size: u16) {
+        self.slice.as_mut()[14..16].copy_from_slice(&window_size.to_be_bytes())
+    }
+
+    pub fn set_csum(&mut self, csum: u16) {
+        self.slice.as_mut()[16..18].copy_from_slice(&csum.to_be_bytes())
+    }
+
+    pub fn set_urgent_pointer(&mut self, urgent_pointer: u16) {
+        self.slice.as_mut()[18..20].copy_from_slice(&urgent_pointer.to_be_bytes())
+    }
+
+    pub fn update_csum_ipv4(&mut self, source: &[u8; 4], destination: &[u8; 4], payload: &[u8]) {
+        self.set_csum(self.calc_csum_ipv4(source, destination, payload))
+    }
+
+    pub fn update_csum_ipv6(&mut self, source: &[u8; 16], destination: &[u8; 16], payload: &[u8]) {
+        self.set_csum(self.calc_csum_ipv6(source, destination, payload))
+    }
+}
+
+/// Incremental checksum updates (RFC 1624), they only touch the bytes that change so they are
+/// the way to rewrite headers from XDP without walking the payload
+#[cfg(feature = "aya")]
+impl<P: AsMut<[u8]> + AsRef<[u8]>> Tcp<P> {
+    /// Adjusts the checksum for a change of `old` into `new` anywhere in the covered data, e.g. an
+    /// address of the pseudo-header when doing NAT. The size of `T` must be a multiple of 4
+    pub fn update_csum_diff<T: Copy>(&mut self, old: T, new: T) {
+        let raw = *self.slice.as_ref()[16..18].first_chunk::<2>().unwrap();
+        let seed = !u16::from_ne_bytes(raw) as u32;
+        let csum = crate::aya::csum_fold_helper(crate::aya::csum_diff(old, new, seed));
+
+        self.slice.as_mut()[16..18].copy_from_slice(&csum.to_ne_bytes());
+    }
+
+    fn replace_word(&mut self, offset: usize, new: [u8; 4]) {
+        let old = *self.slice.as_ref()[offset..offset + 4]
+            .first_chunk::<4>()
+            .unwrap();
+
+        self.slice.as_mut()[offset..offset + 4].copy_from_slice(&new);
+        self.update_csum_diff(old, new);
+    }
+
+    pub fn set_ports_incremental(&mut self, source: u16, destination: u16) {
+        let [s0, s1] = source.to_be_bytes();
+        let [d0, d1] = destination.to_be_bytes();
+
+        self.replace_word(0, [s0, s1, d0, d1])
+    }
+
+    pub fn set_sequence_num_incremental(&mut self, sequence_num: u32) {
+        self.replace_word(4, sequence_num.to_be_bytes())
+    }
+
+    pub fn set_ack_num_incremental(&mut self, ack_num: u32) {
+        self.replace_word(8, ack_num.to_be_bytes())
+    }
+
+    pub fn set_flags_window_incremental(&mut self, flags: u8, window_size: u16) {
+        let [w0, w1] = window_size.to_be_bytes();
+        let offset = self.slice.as_ref()[12];
+
+        self.replace_word(12, [offset, flags, w0, w1])
+    }
+}
+
+impl<P: AsRef<[u8]>> Tcp<P> {
     pub fn size(&self) -> TcpSize {
         self.size
     }
@@ -76,83 +212,115 @@ impl Tcp<&[u8]> {
     }
 
     pub fn destination(&self) -> u16 {
-        u16::from_be_bytes(*self.slice[2..4].first_chunk::<2>().unwrap())
+        u16::from_be_bytes(*self.slice.as_ref()[2..4].first_chunk::<2>().unwrap())
     }
 
     pub fn source(&self) -> u16 {
-        u16::from_be_bytes(*self.slice[0..2].first_chunk::<2>().unwrap())
+        u16::from_be_bytes(*self.slice.as_ref()[0..2].first_chunk::<2>().unwrap())
     }
 
     pub fn window_size(&self) -> u16 {
-        u16::from_be_bytes(*self.slice[14..16].first_chunk::<2>().unwrap())
+        u16::from_be_bytes(*self.slice.as_ref()[14..16].first_chunk::<2>().unwrap())
     }
 
     pub fn slice(&self) -> &[u8] {
-        self.slice
+        self.slice.as_ref()
     }
 
     pub fn csum(&self) -> u16 {
-        u16::from_be_bytes(*self.slice[16..18].first_chunk::<2>().unwrap())
+        u16::from_be_bytes(*self.slice.as_ref()[16..18].first_chunk::<2>().unwrap())
+    }
+
+    /// Checksum over the IPv4 pseudo-header, this header and `payload`
+    pub fn calc_csum_ipv4(&self, source: &[u8; 4], destination: &[u8; 4], payload: &[u8]) -> u16 {
+        let length = (self.size_usize() + payload.len()) as u16;
+
+        etherparse::checksum::Sum16BitWords::new()
+            .add_4bytes(*source)
+            .add_4bytes(*destination)
+            .add_2bytes([0, 6])
+            .add_2bytes(length.to_be_bytes())
+            .add_slice(&self.slice.as_ref()[0..16])
+            .add_slice(&self.slice.as_ref()[18..])
+            .add_slice(payload)
+            .ones_complement()
+            .to_be()
+    }
+
+    /// Checksum over the IPv6 pseudo-header, this header and `payload`
+    pub fn calc_csum_ipv6(&self, source: &[u8; 16], destination: &[u8; 16], payload: &[u8]) -> u16 {
+        let length = (self.size_usize() + payload.len()) as u32;
+
+        etherparse::checksum::Sum16BitWords::new()
+            .add_16bytes(*source)
+            .add_16bytes(*destination)
+            .add_4bytes(length.to_be_bytes())
+            .add_4bytes([0, 0, 0, 6])
+            .add_slice(&self.slice.as_ref()[0..16])
+            .add_slice(&self.slice.as_ref()[18..])
+            .add_slice(payload)
+            .ones_complement()
+            .to_be()
     }
 
     pub fn urgent_pointer(&self) -> u16 {
-        u16::from_be_bytes(*self.slice[18..20].first_chunk::<2>().unwrap())
+        u16::from_be_bytes(*self.slice.as_ref()[18..20].first_chunk::<2>().unwrap())
     }
 
     pub fn sequence_num(&self) -> u32 {
-        u32::from_be_bytes(*self.slice[4..8].first_chunk::<4>().unwrap())
+        u32::from_be_bytes(*self.slice.as_ref()[4..8].first_chunk::<4>().unwrap())
     }
 
     pub fn ack_num(&self) -> u32 {
-        u32::from_be_bytes(*self.slice[8..12].first_chunk::<4>().unwrap())
+        u32::from_be_bytes(*self.slice.as_ref()[8..12].first_chunk::<4>().unwrap())
     }
 
     pub fn data_offset(&self) -> u8 {
-        self.slice[12] >> 4
+        self.slice.as_ref()[12] >> 4
     }
 
     pub fn flags(&self) -> u8 {
-        self.slice[13]
+        self.slice.as_ref()[13]
     }
 
     pub fn options(&self) -> &[u8] {
-        &self.slice[Tcp::MIN_LEN..self.size as usize]
+        &self.slice.as_ref()[Tcp::MIN_LEN..self.size as usize]
     }
 
     pub fn cwr(&self) -> bool {
-        self.slice[13] >> 7 == 1
+        self.slice.as_ref()[13] >> 7 == 1
     }
 
     pub fn ece(&self) -> bool {
-        (self.slice[13] >> 6) & 1 == 1
+        (self.slice.as_ref()[13] >> 6) & 1 == 1
     }
 
     pub fn urg(&self) -> bool {
-        (self.slice[13] >> 5) & 1 == 1
+        (self.slice.as_ref()[13] >> 5) & 1 == 1
     }
 
     pub fn ack(&self) -> bool {
-        (self.slice[13] >> 4) & 1 == 1
+        (self.slice.as_ref()[13] >> 4) & 1 == 1
     }
 
     pub fn psh(&self) -> bool {
-        (self.slice[13] >> 3) & 1 == 1
+        (self.slice.as_ref()[13] >> 3) & 1 == 1
     }
 
     pub fn rst(&self) -> bool {
-        (self.slice[13] >> 2) & 1 == 1
+        (self.slice.as_ref()[13] >> 2) & 1 == 1
     }
 
     pub fn syn(&self) -> bool {
-        (self.slice[13] >> 1) & 1 == 1
+        (self.slice.as_ref()[13] >> 1) & 1 == 1
     }
 
     pub fn fin(&self) -> bool {
-        self.slice[13] & 1 == 1
+        self.slice.as_ref()[13] & 1 == 1
     }
 
     pub fn ns(&self) -> bool {
-        self.slice[12] & 1 == 1
+        self.slice.as_ref()[12] & 1 == 1
     }
 }
 
@@ -197,3 +365,160 @@ impl TcpSize {
 pub enum DataOffsetError {
     InvalidOffset(u8),
 }
+
+impl<P: AsRef<[u8]>> Tcp<P> {
+    /// Everything but the ports, `Flags [S.], seq 1, ack 2, win 1024`. Flags are listed in
+    /// tcpdump's order, `.` is ACK
+    pub(crate) fn fmt_details(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Flags [")?;
+
+        let flags = [
+            (self.fin(), 'F'),
+            (self.syn(), 'S'),
+            (self.rst(), 'R'),
+            (self.psh(), 'P'),
+            (self.ack(), '.'),
+            (self.urg(), 'U'),
+            (self.ece(), 'E'),
+            (self.cwr(), 'W'),
+        ];
+
+        if flags.iter().all(|(set, _)| !set) {
+            f.write_str("none")?;
+        }
+
+        for (_, flag) in flags.iter().filter(|(set, _)| *set) {
+            write!(f, "{flag}")?;
+        }
+
+        write!(f, "], seq {}", self.sequence_num())?;
+
+        if self.ack() {
+            write!(f, ", ack {}", self.ack_num())?;
+        }
+
+        write!(f, ", win {}", self.window_size())
+    }
+}
+
+/// `12345 > 80: Flags [S], seq 1, win 1024`
+impl<P: AsRef<[u8]>> fmt::Display for Tcp<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} > {}: ", self.source(), self.destination())?;
+        self.fmt_details(f)
+    }
+}
+
+/// Names of the flags that are set
+#[cfg(feature = "serde")]
+struct TcpFlags(u8, bool);
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for TcpFlags {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        const NAMES: [&str; 8] = ["FIN", "SYN", "RST", "PSH", "ACK", "URG", "ECE", "CWR"];
+
+        let ns = self.1.then_some("NS");
+        let names = NAMES
+            .iter()
+            .enumerate()
+            .filter(|(bit, _)| self.0 & (1 << bit) != 0)
+            .map(|(_, name)| *name);
+
+        serializer.collect_seq(names.chain(ns))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<P: AsRef<[u8]>> serde::Serialize for Tcp<P> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("Tcp", 9)?;
+        state.serialize_field("source", &self.source())?;
+        state.serialize_field("destination", &self.destination())?;
+        state.serialize_field("sequence", &self.sequence_num())?;
+        state.serialize_field("acknowledgment", &self.ack_num())?;
+        state.serialize_field("data_offset", &self.data_offset())?;
+        state.serialize_field("flags", &TcpFlags(self.flags(), self.ns()))?;
+        state.serialize_field("window", &self.window_size())?;
+        state.serialize_field("checksum", &self.csum())?;
+        state.serialize_field("urgent_pointer", &self.urgent_pointer())?;
+        state.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::transport::tcp::Tcp;
+
+    const SYN: [u8; 20] = [
+        0x30, 0x39, 0x00, 0x50, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x50, 0x02, 0xFF,
+        0xFF, 0x00, 0x00, 0x00, 0x00,
+    ];
+
+    #[test]
+    fn setters() {
+        let mut packet = SYN;
+        let (mut tcp, _) = Tcp::new_mut(&mut packet).unwrap();
+
+        tcp.set_source(80);
+        tcp.set_destination(12345);
+        tcp.set_sequence_num(100);
+        tcp.set_ack_num(2);
+        tcp.set_ack(true);
+        tcp.set_window_size(1024);
+
+        assert_eq!(tcp.source(), 80);
+        assert_eq!(tcp.destination(), 12345);
+        assert_eq!(tcp.sequence_num(), 100);
+        assert_eq!(tcp.ack_num(), 2);
+        assert!(tcp.syn() && tcp.ack() && !tcp.rst());
+        assert_eq!(tcp.window_size(), 1024);
+
+        tcp.set_syn(false);
+        assert_eq!(tcp.flags(), 0x10);
+    }
+
+    #[test]
+    fn pseudo_header_csum() {
+        let mut packet = SYN;
+        let (mut tcp, _) = Tcp::new_mut(&mut packet).unwrap();
+
+        tcp.update_csum_ipv4(&[10, 0, 0, 1], &[10, 0, 0, 2], &[]);
+        assert_eq!(tcp.csum(), 0x6B56);
+
+        let mut source = [0; 16];
+        let mut destination = [0; 16];
+        source[15] = 1;
+        destination[15] = 2;
+
+        tcp.update_csum_ipv6(&source, &destination, &[]);
+        assert_eq!(tcp.csum(), 0x7F56);
+    }
+
+    #[cfg(feature = "aya")]
+    #[test]
+    fn incremental_csum() {
+        let (source, destination) = ([10, 0, 0, 1], [10, 0, 0, 2]);
+        let payload = b"GET / HTTP/1.1\r\n";
+        let mut packet = SYN;
+        let (mut tcp, _) = Tcp::new_mut(&mut packet).unwrap();
+        tcp.update_csum_ipv4(&source, &destination, payload);
+
+        tcp.set_ports_incremental(8080, 54321);
+        tcp.set_sequence_num_incremental(0xDEADBEEF);
+        tcp.set_ack_num_incremental(42);
+        tcp.set_flags_window_incremental(0x12, 512);
+        assert_eq!((tcp.source(), tcp.destination()), (8080, 54321));
+        assert_eq!(tcp.flags(), 0x12);
+        assert_eq!(
+            tcp.csum(),
+            tcp.calc_csum_ipv4(&source, &destination, payload)
+        );
+
+        let nat = [192, 168, 1, 1];
+        tcp.update_csum_diff(source, nat);
+        assert_eq!(tcp.csum(), tcp.calc_csum_ipv4(&nat, &destination, payload));
+    }
+}
@@ -0,0 +1,173 @@
+use super::tcp::Tcp;
+use crate::tlv::{OptionWalker, RawOption};
+
+pub use crate::tlv::OptionError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TcpOption<'pkt> {
+    Eol,
+    Nop,
+    Mss(u16),
+    WindowScale(u8),
+    SackPermitted,
+    Sack(SackBlocks<'pkt>),
+    Timestamps { value: u32, echo_reply: u32 },
+    Unknown(u8, &'pkt [u8]),
+}
+
+/// Left and right edges of up to four SACK blocks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SackBlocks<'pkt> {
+    slice: &'pkt [u8],
+}
+
+impl SackBlocks<'_> {
+    pub fn len(&self) -> usize {
+        self.slice.len() / 8
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slice.is_empty()
+    }
+}
+
+impl Iterator for SackBlocks<'_> {
+    type Item = (u32, u32);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (block, rem) = self.slice.split_first_chunk::<8>()?;
+        self.slice = rem;
+
+        Some((
+            u32::from_be_bytes(*block[0..4].first_chunk::<4>().unwrap()),
+            u32::from_be_bytes(*block[4..8].first_chunk::<4>().unwrap()),
+        ))
+    }
+}
+
+/// Iterator over the options of a TCP header, decoding the options negotiated on SYN and the
+/// SACK blocks and timestamps carried afterwards. An option whose length does not fit its kind
+/// ends the walk
+pub struct TcpOptions<'pkt> {
+    walker: OptionWalker<'pkt>,
+}
+
+impl<'pkt> TcpOptions<'pkt> {
+    pub fn new(slice: &'pkt [u8]) -> Self {
+        Self {
+            walker: OptionWalker::new(slice),
+        }
+    }
+}
+
+impl<'pkt> Iterator for TcpOptions<'pkt> {
+    type Item = Result<TcpOption<'pkt>, OptionError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (kind, data) = match self.walker.next()? {
+            Ok(RawOption::Eol) => return Some(Ok(TcpOption::Eol)),
+            Ok(RawOption::Nop) => return Some(Ok(TcpOption::Nop)),
+            Ok(RawOption::Value(kind, data)) => (kind, data),
+            Err(e) => return Some(Err(e)),
+        };
+        let len = data.len() as u8 + 2;
+
+        let option = match (kind, len) {
+            (2, 4) => TcpOption::Mss(u16::from_be_bytes(*data.first_chunk::<2>().unwrap())),
+            (3, 3) => TcpOption::WindowScale(data[0]),
+            (4, 2) => TcpOption::SackPermitted,
+            (5, 10 | 18 | 26 | 34) => TcpOption::Sack(SackBlocks { slice: data }),
+            (8, 10) => TcpOption::Timestamps {
+                value: u32::from_be_bytes(*data[0..4].first_chunk::<4>().unwrap()),
+                echo_reply: u32::from_be_bytes(*data[4..8].first_chunk::<4>().unwrap()),
+            },
+            (2..=5 | 8, _) => {
+                self.walker.finish();
+                return Some(Err(OptionError::InvalidLength(kind, len)));
+            }
+            _ => TcpOption::Unknown(kind, data),
+        };
+
+        Some(Ok(option))
+    }
+}
+
+impl<P: AsRef<[u8]>> Tcp<P> {
+    pub fn options_iter(&self) -> TcpOptions<'_> {
+        TcpOptions::new(self.options())
+    }
+
+    /// Maximum segment size announced in the options, if any
+    pub fn mss(&self) -> Option<u16> {
+        self.options_iter().find_map(|o| match o {
+            Ok(TcpOption::Mss(mss)) => Some(mss),
+            _ => None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::transport::tcp::Tcp;
+    use crate::transport::tcpopt::{OptionError, TcpOption, TcpOptions};
+
+    #[test]
+    fn syn_options() {
+        // MSS 1460, SACK permitted, timestamps, NOP, window scale 7
+        let packet = [
+            0x30, 0x39, 0x00, 0x50, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0xA0, 0x02,
+            0xFF, 0xFF, 0x00, 0x00, 0x00, 0x00, 0x02, 0x04, 0x05, 0xB4, 0x04, 0x02, 0x08, 0x0A,
+            0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x01, 0x03, 0x03, 0x07,
+        ];
+        let (tcp, _) = Tcp::new(&packet).unwrap();
+        let mut options = tcp.options_iter();
+
+        assert_eq!(options.next(), Some(Ok(TcpOption::Mss(1460))));
+        assert_eq!(options.next(), Some(Ok(TcpOption::SackPermitted)));
+        assert_eq!(
+            options.next(),
+            Some(Ok(TcpOption::Timestamps {
+                value: 1,
+                echo_reply: 0
+            }))
+        );
+        assert_eq!(options.next(), Some(Ok(TcpOption::Nop)));
+        assert_eq!(options.next(), Some(Ok(TcpOption::WindowScale(7))));
+        assert_eq!(options.next(), None);
+        assert_eq!(tcp.mss(), Some(1460));
+    }
+
+    #[test]
+    fn sack_blocks() {
+        let options = [
+            0x01, 0x01, 0x05, 0x12, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00,
+            0x00, 0x03, 0x00, 0x00, 0x00, 0x04,
+        ];
+        let Some(Ok(TcpOption::Sack(mut blocks))) = TcpOptions::new(&options).nth(2) else {
+            panic!("expected a SACK option");
+        };
+
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks.next(), Some((1, 2)));
+        assert_eq!(blocks.next(), Some((3, 4)));
+        assert_eq!(blocks.next(), None);
+    }
+
+    #[test]
+    fn malformed() {
+        let mut options = TcpOptions::new(&[0x02, 0x05, 0x05, 0xB4, 0x00]);
+        assert_eq!(options.next(), Some(Err(OptionError::InvalidLength(2, 5))));
+        assert_eq!(options.next(), None);
+
+        let mut options = TcpOptions::new(&[0x01, 0x08, 0x0A, 0x00]);
+        assert_eq!(options.next(), Some(Ok(TcpOption::Nop)));
+        assert_eq!(options.next(), Some(Err(OptionError::Truncated(1))));
+
+        let mut options = TcpOptions::new(&[0x1E, 0x00]);
+        assert_eq!(options.next(), Some(Err(OptionError::InvalidLength(30, 0))));
+
+        let mut options = TcpOptions::new(&[0x00, 0x02, 0x04]);
+        assert_eq!(options.next(), Some(Ok(TcpOption::Eol)));
+        assert_eq!(options.next(), None);
+    }
+}
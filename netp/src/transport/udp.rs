@@ -1,58 +1,253 @@
-pub struct Udp<'pkt> {
-    slice: &'pkt mut [u8],
+use core::fmt;
+
+#[derive(Debug)]
+pub struct Udp<P = ()> {
+    slice: P,
 }
 
+#[derive(Debug)]
 pub enum Error {
     InvalidLength(usize),
+    /// The length field is smaller than the header or larger than the available bytes
+    InvalidLengthField(u16, usize),
+}
+
+impl Udp<()> {
+    pub const MIN_LEN: usize = 8;
 }
 
-impl<'pkt> Udp<'pkt> {
-    pub const SIZE: usize = 8;
-    pub fn new(slice: &'pkt mut [u8]) -> Result<(Self, &'pkt mut [u8]), Error> {
-        if slice.len() < Self::SIZE {
+impl<'pkt> Udp<&'pkt [u8]> {
+    /// The returned payload is truncated to the length field, trailing bytes such as Ethernet
+    /// padding are left out
+    pub fn new(slice: &'pkt [u8]) -> Result<(Self, &'pkt [u8]), Error> {
+        if slice.len() < Udp::MIN_LEN {
+            return Err(Error::InvalidLength(slice.len()));
+        }
+
+        let length = u16::from_be_bytes(*slice[4..6].first_chunk::<2>().unwrap());
+        if (length as usize) < Udp::MIN_LEN || length as usize > slice.len() {
+            return Err(Error::InvalidLengthField(length, slice.len()));
+        }
+
+        let (slice, rem) = slice[..length as usize].split_at(Udp::MIN_LEN);
+
+        Ok((Self { slice }, rem))
+    }
+
+    /// Header in the first fragment of a datagram. The length field covers the whole datagram so
+    /// it is not checked, the returned payload is the rest of the fragment
+    pub fn new_fragment(slice: &'pkt [u8]) -> Result<(Self, &'pkt [u8]), Error> {
+        if slice.len() < Udp::MIN_LEN {
             return Err(Error::InvalidLength(slice.len()));
         }
 
-        let (slice, rem) = slice.split_at_mut(Self::SIZE);
+        let (slice, rem) = slice.split_at(Udp::MIN_LEN);
 
         Ok((Self { slice }, rem))
     }
 }
 
-impl Udp<'_> {
+impl<'pkt> Udp<&'pkt mut [u8]> {
+    /// The returned payload is truncated to the length field, trailing bytes such as Ethernet
+    /// padding are left out
+    pub fn new_mut(slice: &'pkt mut [u8]) -> Result<(Self, &'pkt mut [u8]), Error> {
+        if slice.len() < Udp::MIN_LEN {
+            return Err(Error::InvalidLength(slice.len()));
+        }
+
+        let length = u16::from_be_bytes(*slice[4..6].first_chunk::<2>().unwrap());
+        if (length as usize) < Udp::MIN_LEN || length as usize > slice.len() {
+            return Err(Error::InvalidLengthField(length, slice.len()));
+        }
+
+        let (slice, rem) = slice[..length as usize].split_at_mut(Udp::MIN_LEN);
+
+        Ok((Self { slice }, rem))
+    }
+}
+
+impl<P: AsRef<[u8]>> Udp<P> {
+    pub fn slice(&self) -> &[u8] {
+        self.slice.as_ref()
+    }
+
     pub fn source(&self) -> u16 {
-        u16::from_be_bytes(*self.slice[0..2].first_chunk::<2>().unwrap())
+        u16::from_be_bytes(*self.slice.as_ref()[0..2].first_chunk::<2>().unwrap())
     }
 
     pub fn destination(&self) -> u16 {
-        u16::from_be_bytes(*self.slice[2..4].first_chunk::<2>().unwrap())
+        u16::from_be_bytes(*self.slice.as_ref()[2..4].first_chunk::<2>().unwrap())
     }
 
     pub fn length(&self) -> u16 {
-        u16::from_be_bytes(*self.slice[4..6].first_chunk::<2>().unwrap())
+        u16::from_be_bytes(*self.slice.as_ref()[4..6].first_chunk::<2>().unwrap())
     }
 
     pub fn checksum(&self) -> &[u8; 2] {
-        self.slice[6..8].first_chunk::<2>().unwrap()
+        self.slice.as_ref()[6..8].first_chunk::<2>().unwrap()
+    }
+
+    /// Checksum over the IPv4 pseudo-header, this header and `payload`
+    pub fn calc_csum_ipv4(&self, source: &[u8; 4], destination: &[u8; 4], payload: &[u8]) -> u16 {
+        let csum = etherparse::checksum::Sum16BitWords::new()
+            .add_4bytes(*source)
+            .add_4bytes(*destination)
+            .add_2bytes([0, 17])
+            .add_2bytes(self.length().to_be_bytes())
+            .add_slice(&self.slice.as_ref()[0..6])
+            .add_slice(payload)
+            .ones_complement()
+            .to_be();
+
+        // A zero checksum means "no checksum" for UDP over IPv4
+        if csum == 0 {
+            0xFFFF
+        } else {
+            csum
+        }
+    }
+
+    /// Checksum over the IPv6 pseudo-header, this header and `payload`
+    pub fn calc_csum_ipv6(&self, source: &[u8; 16], destination: &[u8; 16], payload: &[u8]) -> u16 {
+        let csum = etherparse::checksum::Sum16BitWords::new()
+            .add_16bytes(*source)
+            .add_16bytes(*destination)
+            .add_4bytes((self.length() as u32).to_be_bytes())
+            .add_4bytes([0, 0, 0, 17])
+            .add_slice(&self.slice.as_ref()[0..6])
+            .add_slice(payload)
+            .ones_complement()
+            .to_be();
+
+        if csum == 0 {
+            0xFFFF
+        } else {
+            csum
+        }
+    }
+
+    /// Payload length declared by the length field. For a header read with `new_fragment` it
+    /// covers the whole datagram rather than the bytes at hand, and a field smaller than the
+    /// header gives 0
+    pub fn payload_len(&self) -> usize {
+        (self.length() as usize).saturating_sub(Udp::MIN_LEN)
+    }
+
+    pub fn size_usize(&self) -> usize {
+        Udp::MIN_LEN
+    }
+}
+
+impl<P: AsMut<[u8]> + AsRef<[u8]>> Udp<P> {
+    pub fn slice_mut(&mut self) -> &mut [u8] {
+        self.slice.as_mut()
     }
 
     pub fn set_source(&mut self, source: u16) {
-        self.slice[0..2].copy_from_slice(&source.to_be_bytes())
+        self.slice.as_mut()[0..2].copy_from_slice(&source.to_be_bytes())
     }
 
     pub fn set_destination(&mut self, destination: u16) {
-        self.slice[2..4].copy_from_slice(&destination.to_be_bytes())
+        self.slice.as_mut()[2..4].copy_from_slice(&destination.to_be_bytes())
     }
 
     pub fn set_length(&mut self, length: u16) {
-        self.slice[4..6].copy_from_slice(&length.to_be_bytes())
+        self.slice.as_mut()[4..6].copy_from_slice(&length.to_be_bytes())
     }
 
     pub fn set_checksum(&mut self, checksum: u16) {
-        self.slice[6..8].copy_from_slice(&checksum.to_be_bytes())
+        self.slice.as_mut()[6..8].copy_from_slice(&checksum.to_be_bytes())
     }
 
     pub fn set_checksum_zero(&mut self) {
         self.set_checksum(0);
     }
+
+    pub fn update_csum_ipv4(&mut self, source: &[u8; 4], destination: &[u8; 4], payload: &[u8]) {
+        self.set_checksum(self.calc_csum_ipv4(source, destination, payload))
+    }
+
+    pub fn update_csum_ipv6(&mut self, source: &[u8; 16], destination: &[u8; 16], payload: &[u8]) {
+        self.set_checksum(self.calc_csum_ipv6(source, destination, payload))
+    }
+}
+
+impl<P: AsRef<[u8]>> Udp<P> {
+    /// Everything but the ports, `UDP, length 2`
+    pub(crate) fn fmt_details(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "UDP, length {}", self.payload_len())
+    }
+}
+
+/// `5353 > 53: UDP, length 2`
+impl<P: AsRef<[u8]>> fmt::Display for Udp<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} > {}: ", self.source(), self.destination())?;
+        self.fmt_details(f)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<P: AsRef<[u8]>> serde::Serialize for Udp<P> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("Udp", 4)?;
+        state.serialize_field("source", &self.source())?;
+        state.serialize_field("destination", &self.destination())?;
+        state.serialize_field("length", &self.length())?;
+        state.serialize_field("checksum", &u16::from_be_bytes(*self.checksum()))?;
+        state.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::transport::udp::Udp;
+
+    #[test]
+    fn create_ref() {
+        let packet = [
+            0x30, 0x39, 0x00, 0x35, 0x00, 0x0A, 0x00, 0x00, 0xAA, 0xBB, 0x00, 0x00,
+        ];
+        let (udp, payload) = Udp::new(&packet).unwrap();
+
+        assert_eq!(udp.source(), 12345);
+        assert_eq!(udp.destination(), 53);
+        assert_eq!(udp.length(), 10);
+        assert_eq!(payload, &[0xAA, 0xBB]);
+    }
+
+    #[test]
+    fn create_mut() {
+        let mut packet = [0x30, 0x39, 0x00, 0x35, 0x00, 0x08, 0x12, 0x34];
+        let (mut udp, payload) = Udp::new_mut(&mut packet).unwrap();
+
+        assert_eq!(payload.len(), 0);
+
+        udp.set_destination(123);
+        udp.set_checksum_zero();
+
+        assert_eq!(udp.destination(), 123);
+        assert_eq!(udp.checksum(), &[0, 0]);
+    }
+
+    #[test]
+    fn inconsistent_length() {
+        let packet = [0x30, 0x39, 0x00, 0x35, 0x00, 0x10, 0x00, 0x00];
+        assert!(Udp::new(&packet).is_err());
+
+        let packet = [0x30, 0x39, 0x00, 0x35, 0x00, 0x04, 0x00, 0x00];
+        assert!(Udp::new(&packet).is_err());
+    }
+
+    #[test]
+    fn fragment_length() {
+        let packet = [0x30, 0x39, 0x00, 0x35, 0x00, 0x00, 0x00, 0x00, 0xAA];
+        let (udp, payload) = Udp::new_fragment(&packet).unwrap();
+
+        assert_eq!(udp.length(), 0);
+        assert_eq!(udp.payload_len(), 0);
+        assert_eq!(payload, &[0xAA]);
+    }
 }
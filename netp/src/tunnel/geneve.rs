@@ -0,0 +1,289 @@
+use core::fmt;
+
+use crate::link::EtherType;
+
+const FLAG_OAM: u8 = 0x80;
+const FLAG_CRITICAL: u8 = 0x40;
+
+/// Geneve header as described by RFC 8926, followed by its variable length options
+#[derive(Debug)]
+pub struct Geneve<P = ()> {
+    slice: P,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    InvalidSize(usize),
+    /// The options announced by the header do not fit, holds the available bytes and the size of
+    /// the header
+    InvalidSizeForOptions(usize, usize),
+    UnsupportedVersion(u8),
+}
+
+/// Raised by the options iterator when an option runs past the end of the header
+#[derive(Debug)]
+pub struct OptionError;
+
+impl Geneve<()> {
+    pub const MIN_LEN: usize = 8;
+    /// UDP destination port assigned by IANA
+    pub const PORT: u16 = 6081;
+}
+
+fn header_size(slice: &[u8]) -> Result<usize, Error> {
+    if slice.len() < Geneve::MIN_LEN {
+        return Err(Error::InvalidSize(slice.len()));
+    }
+
+    let version = slice[0] >> 6;
+    if version != 0 {
+        return Err(Error::UnsupportedVersion(version));
+    }
+
+    let size = Geneve::MIN_LEN + (slice[0] & 0x3F) as usize * 4;
+    if slice.len() < size {
+        return Err(Error::InvalidSizeForOptions(slice.len(), size));
+    }
+
+    Ok(size)
+}
+
+impl<'pkt> Geneve<&'pkt [u8]> {
+    pub fn new(slice: &'pkt [u8]) -> Result<(Self, &'pkt [u8]), Error> {
+        let size = header_size(slice)?;
+        let (slice, rem) = slice.split_at(size);
+
+        Ok((Self { slice }, rem))
+    }
+}
+
+impl<'pkt> Geneve<&'pkt mut [u8]> {
+    pub fn new_mut(slice: &'pkt mut [u8]) -> Result<(Self, &'pkt mut [u8]), Error> {
+        let size = header_size(slice)?;
+        let (slice, rem) = slice.split_at_mut(size);
+
+        Ok((Self { slice }, rem))
+    }
+}
+
+impl<P: AsRef<[u8]>> Geneve<P> {
+    pub fn slice(&self) -> &[u8] {
+        self.slice.as_ref()
+    }
+
+    pub fn version(&self) -> u8 {
+        self.slice.as_ref()[0] >> 6
+    }
+
+    /// The packet carries a control message instead of user data
+    pub fn is_oam(&self) -> bool {
+        self.slice.as_ref()[1] & FLAG_OAM != 0
+    }
+
+    /// At least one option has its critical bit set, endpoints that do not understand it must
+    /// drop the packet
+    pub fn has_critical_options(&self) -> bool {
+        self.slice.as_ref()[1] & FLAG_CRITICAL != 0
+    }
+
+    pub fn protocol_type(&self) -> EtherType {
+        self.protocol_type_u16().into()
+    }
+
+    pub fn protocol_type_u16(&self) -> u16 {
+        u16::from_be_bytes(*self.slice.as_ref()[2..4].first_chunk::<2>().unwrap())
+    }
+
+    /// 24-bit virtual network identifier
+    pub fn vni(&self) -> u32 {
+        u32::from_be_bytes(*self.slice.as_ref()[4..8].first_chunk::<4>().unwrap()) >> 8
+    }
+
+    pub fn options(&self) -> GeneveOptions<'_> {
+        GeneveOptions {
+            slice: &self.slice.as_ref()[Geneve::MIN_LEN..],
+        }
+    }
+
+    pub fn size_usize(&self) -> usize {
+        self.slice.as_ref().len()
+    }
+}
+
+impl<P: AsMut<[u8]> + AsRef<[u8]>> Geneve<P> {
+    pub fn slice_mut(&mut self) -> &mut [u8] {
+        self.slice.as_mut()
+    }
+
+    /// Only the lower 24 bits are kept
+    pub fn set_vni(&mut self, vni: u32) {
+        self.slice.as_mut()[4..7].copy_from_slice(&vni.to_be_bytes()[1..]);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GeneveOption<'pkt> {
+    pub class: u16,
+    pub kind: u8,
+    pub data: &'pkt [u8],
+}
+
+impl GeneveOption<'_> {
+    /// The high bit of the type marks options the receiver must understand
+    pub fn is_critical(&self) -> bool {
+        self.kind & 0x80 != 0
+    }
+}
+
+/// Stops after the first malformed option
+#[derive(Clone)]
+pub struct GeneveOptions<'pkt> {
+    slice: &'pkt [u8],
+}
+
+impl<'pkt> Iterator for GeneveOptions<'pkt> {
+    type Item = Result<GeneveOption<'pkt>, OptionError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.slice.is_empty() {
+            return None;
+        }
+
+        let Some(header) = self.slice.first_chunk::<4>() else {
+            self.slice = &[];
+            return Some(Err(OptionError));
+        };
+
+        let len = 4 + (header[3] & 0x1F) as usize * 4;
+        if self.slice.len() < len {
+            self.slice = &[];
+            return Some(Err(OptionError));
+        }
+
+        let option = GeneveOption {
+            class: u16::from_be_bytes([header[0], header[1]]),
+            kind: header[2],
+            data: &self.slice[4..len],
+        };
+        self.slice = &self.slice[len..];
+
+        Some(Ok(option))
+    }
+}
+
+/// `Geneve, Flags [C], vni 0x2a, proto TEB (0x6558), options [12 bytes]`
+impl<P: AsRef<[u8]>> fmt::Display for Geneve<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let flags = match (self.is_oam(), self.has_critical_options()) {
+            (true, true) => "OC",
+            (true, false) => "O",
+            (false, true) => "C",
+            (false, false) => "none",
+        };
+
+        write!(
+            f,
+            "Geneve, Flags [{flags}], vni {:#x}, proto {}, options [{} bytes]",
+            self.vni(),
+            self.protocol_type(),
+            self.size_usize() - Geneve::MIN_LEN
+        )
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for GeneveOption<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("GeneveOption", 3)?;
+        state.serialize_field("class", &self.class)?;
+        state.serialize_field("type", &self.kind)?;
+        state.serialize_field("length", &self.data.len())?;
+        state.end()
+    }
+}
+
+/// Serialized as a sequence of the options up to the first malformed one
+#[cfg(feature = "serde")]
+struct Options<'pkt>(GeneveOptions<'pkt>);
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Options<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.0.clone().map_while(Result::ok))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<P: AsRef<[u8]>> serde::Serialize for Geneve<P> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("Geneve", 5)?;
+        state.serialize_field("oam", &self.is_oam())?;
+        state.serialize_field("critical", &self.has_critical_options())?;
+        state.serialize_field("protocol_type", &self.protocol_type())?;
+        state.serialize_field("vni", &self.vni())?;
+        state.serialize_field("options", &Options(self.options()))?;
+        state.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::link::EtherType;
+    use crate::tunnel::geneve::{Error, Geneve, GeneveOption};
+
+    #[test]
+    fn options() {
+        let packet = [
+            0x03, 0x40, 0x65, 0x58, 0x00, 0x00, 0x2A, 0x00, // Header with 12 bytes of options
+            0x01, 0x02, 0x80, 0x01, 0xDE, 0xAD, 0xBE, 0xEF, // Critical option with 4 bytes
+            0xFF, 0xFF, 0x01, 0x00, // Empty option
+            0xAA,
+        ];
+        let (geneve, rem) = Geneve::new(&packet).unwrap();
+
+        assert_eq!(geneve.vni(), 42);
+        assert_eq!(
+            geneve.protocol_type(),
+            EtherType::TransparentEthernetBridging
+        );
+        assert!(geneve.has_critical_options());
+        assert!(!geneve.is_oam());
+        assert_eq!(geneve.size_usize(), 20);
+        assert_eq!(rem, &[0xAA]);
+
+        let mut options = geneve.options();
+        let option = options.next().unwrap().unwrap();
+        assert_eq!(
+            option,
+            GeneveOption {
+                class: 0x0102,
+                kind: 0x80,
+                data: &[0xDE, 0xAD, 0xBE, 0xEF]
+            }
+        );
+        assert!(option.is_critical());
+        assert_eq!(options.next().unwrap().unwrap().class, 0xFFFF);
+        assert!(options.next().is_none());
+    }
+
+    #[test]
+    fn truncated_option() {
+        let packet = [
+            0x01, 0x00, 0x65, 0x58, 0x00, 0x00, 0x01, 0x00, 0x01, 0x02, 0x03, 0x01,
+        ];
+        let (geneve, _) = Geneve::new(&packet).unwrap();
+
+        let mut options = geneve.options();
+        assert!(options.next().unwrap().is_err());
+        assert!(options.next().is_none());
+
+        assert!(matches!(
+            Geneve::new(&packet[..8]),
+            Err(Error::InvalidSizeForOptions(8, 12))
+        ));
+    }
+}
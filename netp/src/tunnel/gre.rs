@@ -0,0 +1,249 @@
+use core::fmt;
+
+use crate::link::EtherType;
+
+const FLAG_CHECKSUM: u8 = 0x80;
+const FLAG_KEY: u8 = 0x20;
+const FLAG_SEQUENCE: u8 = 0x10;
+
+/// GRE header as described by RFC 2784 with the key and sequence number extensions of RFC 2890
+#[derive(Debug)]
+pub struct Gre<P = ()> {
+    slice: P,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    InvalidSize(usize),
+    /// The optional fields announced by the flags do not fit, holds the available bytes and the
+    /// size of the header
+    InvalidSizeForFlags(usize, usize),
+    /// Only version 0 is supported, version 1 is the enhanced GRE header of PPTP
+    UnsupportedVersion(u8),
+}
+
+impl Gre<()> {
+    pub const MIN_LEN: usize = 4;
+    pub const MAX_LEN: usize = 16;
+}
+
+fn header_size(slice: &[u8]) -> Result<usize, Error> {
+    if slice.len() < Gre::MIN_LEN {
+        return Err(Error::InvalidSize(slice.len()));
+    }
+
+    let version = slice[1] & 0x07;
+    if version != 0 {
+        return Err(Error::UnsupportedVersion(version));
+    }
+
+    let flags = slice[0];
+    let mut size = Gre::MIN_LEN;
+
+    // The checksum is followed by a reserved field, both take 4 bytes
+    for flag in [FLAG_CHECKSUM, FLAG_KEY, FLAG_SEQUENCE] {
+        if flags & flag != 0 {
+            size += 4;
+        }
+    }
+
+    if slice.len() < size {
+        return Err(Error::InvalidSizeForFlags(slice.len(), size));
+    }
+
+    Ok(size)
+}
+
+impl<'pkt> Gre<&'pkt [u8]> {
+    pub fn new(slice: &'pkt [u8]) -> Result<(Self, &'pkt [u8]), Error> {
+        let size = header_size(slice)?;
+        let (slice, rem) = slice.split_at(size);
+
+        Ok((Self { slice }, rem))
+    }
+}
+
+impl<'pkt> Gre<&'pkt mut [u8]> {
+    pub fn new_mut(slice: &'pkt mut [u8]) -> Result<(Self, &'pkt mut [u8]), Error> {
+        let size = header_size(slice)?;
+        let (slice, rem) = slice.split_at_mut(size);
+
+        Ok((Self { slice }, rem))
+    }
+}
+
+impl<P: AsRef<[u8]>> Gre<P> {
+    pub fn slice(&self) -> &[u8] {
+        self.slice.as_ref()
+    }
+
+    pub fn has_checksum(&self) -> bool {
+        self.slice.as_ref()[0] & FLAG_CHECKSUM != 0
+    }
+
+    pub fn has_key(&self) -> bool {
+        self.slice.as_ref()[0] & FLAG_KEY != 0
+    }
+
+    pub fn has_sequence(&self) -> bool {
+        self.slice.as_ref()[0] & FLAG_SEQUENCE != 0
+    }
+
+    pub fn version(&self) -> u8 {
+        self.slice.as_ref()[1] & 0x07
+    }
+
+    pub fn protocol_type(&self) -> EtherType {
+        self.protocol_type_u16().into()
+    }
+
+    pub fn protocol_type_u16(&self) -> u16 {
+        u16::from_be_bytes(*self.slice.as_ref()[2..4].first_chunk::<2>().unwrap())
+    }
+
+    /// Offset of an optional field, the fields that are present come in checksum, key, sequence
+    /// order
+    fn field_offset(&self, flag: u8) -> Option<usize> {
+        let flags = self.slice.as_ref()[0];
+        if flags & flag == 0 {
+            return None;
+        }
+
+        let preceding = [FLAG_CHECKSUM, FLAG_KEY]
+            .into_iter()
+            .filter(|f| *f > flag && flags & f != 0)
+            .count();
+
+        Some(Gre::MIN_LEN + 4 * preceding)
+    }
+
+    fn field(&self, flag: u8) -> Option<u32> {
+        let start = self.field_offset(flag)?;
+        let field = self.slice.as_ref()[start..start + 4]
+            .first_chunk::<4>()
+            .unwrap();
+
+        Some(u32::from_be_bytes(*field))
+    }
+
+    pub fn checksum(&self) -> Option<u16> {
+        self.field(FLAG_CHECKSUM).map(|field| (field >> 16) as u16)
+    }
+
+    /// Commonly used to tell tunnels apart, e.g. as the virtual subnet ID of NVGRE
+    pub fn key(&self) -> Option<u32> {
+        self.field(FLAG_KEY)
+    }
+
+    pub fn sequence(&self) -> Option<u32> {
+        self.field(FLAG_SEQUENCE)
+    }
+
+    pub fn size_usize(&self) -> usize {
+        self.slice.as_ref().len()
+    }
+}
+
+impl<P: AsMut<[u8]> + AsRef<[u8]>> Gre<P> {
+    pub fn slice_mut(&mut self) -> &mut [u8] {
+        self.slice.as_mut()
+    }
+
+    pub fn set_protocol_type(&mut self, protocol_type: EtherType) {
+        let protocol_type: u16 = protocol_type.into();
+        self.slice.as_mut()[2..4].copy_from_slice(&protocol_type.to_be_bytes());
+    }
+
+    /// Does nothing when the header has no key field
+    pub fn set_key(&mut self, key: u32) {
+        let Some(start) = self.field_offset(FLAG_KEY) else {
+            return;
+        };
+
+        self.slice.as_mut()[start..start + 4].copy_from_slice(&key.to_be_bytes());
+    }
+}
+
+/// `GREv0, key=0x4d2, seq 7, proto TEB (0x6558)`
+impl<P: AsRef<[u8]>> fmt::Display for Gre<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "GREv{}", self.version())?;
+
+        if let Some(key) = self.key() {
+            write!(f, ", key={key:#x}")?;
+        }
+
+        if let Some(sequence) = self.sequence() {
+            write!(f, ", seq {sequence}")?;
+        }
+
+        write!(f, ", proto {}", self.protocol_type())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<P: AsRef<[u8]>> serde::Serialize for Gre<P> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("Gre", 4)?;
+        state.serialize_field("protocol_type", &self.protocol_type())?;
+        state.serialize_field("checksum", &self.checksum())?;
+        state.serialize_field("key", &self.key())?;
+        state.serialize_field("sequence", &self.sequence())?;
+        state.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::link::EtherType;
+    use crate::tunnel::gre::{Error, Gre};
+
+    #[test]
+    fn key_and_sequence() {
+        let packet = [
+            0x30, 0x00, 0x65, 0x58, 0x00, 0x00, 0x04, 0xD2, 0x00, 0x00, 0x00, 0x07, 0xAA, 0xBB,
+        ];
+        let (gre, rem) = Gre::new(&packet).unwrap();
+
+        assert!(!gre.has_checksum());
+        assert_eq!(gre.protocol_type(), EtherType::TransparentEthernetBridging);
+        assert_eq!(gre.checksum(), None);
+        assert_eq!(gre.key(), Some(1234));
+        assert_eq!(gre.sequence(), Some(7));
+        assert_eq!(gre.size_usize(), 12);
+        assert_eq!(rem, &[0xAA, 0xBB]);
+    }
+
+    #[test]
+    fn checksum_and_key() {
+        let mut packet = [
+            0xA0, 0x00, 0x08, 0x00, 0x12, 0x34, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01,
+        ];
+        let (mut gre, rem) = Gre::new_mut(&mut packet).unwrap();
+
+        assert!(rem.is_empty());
+        assert_eq!(gre.checksum(), Some(0x1234));
+        assert_eq!(gre.key(), Some(1));
+        assert_eq!(gre.sequence(), None);
+
+        gre.set_key(42);
+        assert_eq!(gre.key(), Some(42));
+    }
+
+    #[test]
+    fn invalid() {
+        let packet = [0x30, 0x00, 0x65, 0x58, 0x00, 0x00, 0x04, 0xD2];
+        assert!(matches!(
+            Gre::new(&packet),
+            Err(Error::InvalidSizeForFlags(8, 12))
+        ));
+
+        let packet = [0x30, 0x01, 0x88, 0x0B];
+        assert!(matches!(
+            Gre::new(&packet),
+            Err(Error::UnsupportedVersion(1))
+        ));
+    }
+}
@@ -0,0 +1,11 @@
+//! Encapsulation headers. Each parser returns the encapsulated frame as its remainder, hand it to
+//! `Packet::new` or `Packet::new_ip` depending on `protocol_type()`, or use
+//! `Packet::decapsulate`.
+
+pub mod geneve;
+pub mod gre;
+pub mod vxlan;
+
+pub use geneve::Geneve;
+pub use gre::Gre;
+pub use vxlan::Vxlan;
@@ -0,0 +1,133 @@
+use core::fmt;
+
+const FLAG_VNI: u8 = 0x08;
+
+/// VXLAN header as described by RFC 7348, always followed by an Ethernet frame
+#[derive(Debug)]
+pub struct Vxlan<P = ()> {
+    slice: P,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    InvalidSize(usize),
+    /// The I flag is not set, there is no valid VNI
+    MissingVni,
+}
+
+impl Vxlan<()> {
+    pub const LEN: usize = 8;
+    /// UDP destination port assigned by IANA
+    pub const PORT: u16 = 4789;
+}
+
+fn validate(slice: &[u8]) -> Result<(), Error> {
+    if slice.len() < Vxlan::LEN {
+        return Err(Error::InvalidSize(slice.len()));
+    }
+
+    if slice[0] & FLAG_VNI == 0 {
+        return Err(Error::MissingVni);
+    }
+
+    Ok(())
+}
+
+impl<'pkt> Vxlan<&'pkt [u8]> {
+    pub fn new(slice: &'pkt [u8]) -> Result<(Self, &'pkt [u8]), Error> {
+        validate(slice)?;
+        let (slice, rem) = slice.split_at(Vxlan::LEN);
+
+        Ok((Self { slice }, rem))
+    }
+}
+
+impl<'pkt> Vxlan<&'pkt mut [u8]> {
+    pub fn new_mut(slice: &'pkt mut [u8]) -> Result<(Self, &'pkt mut [u8]), Error> {
+        validate(slice)?;
+        let (slice, rem) = slice.split_at_mut(Vxlan::LEN);
+
+        Ok((Self { slice }, rem))
+    }
+}
+
+impl<P: AsRef<[u8]>> Vxlan<P> {
+    pub fn slice(&self) -> &[u8] {
+        self.slice.as_ref()
+    }
+
+    pub fn flags(&self) -> u8 {
+        self.slice.as_ref()[0]
+    }
+
+    /// 24-bit VXLAN network identifier
+    pub fn vni(&self) -> u32 {
+        u32::from_be_bytes(*self.slice.as_ref()[4..8].first_chunk::<4>().unwrap()) >> 8
+    }
+
+    pub fn size_usize(&self) -> usize {
+        Vxlan::LEN
+    }
+}
+
+impl<P: AsMut<[u8]> + AsRef<[u8]>> Vxlan<P> {
+    pub fn slice_mut(&mut self) -> &mut [u8] {
+        self.slice.as_mut()
+    }
+
+    /// Only the lower 24 bits are kept
+    pub fn set_vni(&mut self, vni: u32) {
+        self.slice.as_mut()[4..7].copy_from_slice(&vni.to_be_bytes()[1..]);
+    }
+}
+
+/// `VXLAN, flags [I] (0x08), vni 100`
+impl<P: AsRef<[u8]>> fmt::Display for Vxlan<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "VXLAN, flags [I] ({:#04x}), vni {}",
+            self.flags(),
+            self.vni()
+        )
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<P: AsRef<[u8]>> serde::Serialize for Vxlan<P> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("Vxlan", 2)?;
+        state.serialize_field("flags", &self.flags())?;
+        state.serialize_field("vni", &self.vni())?;
+        state.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tunnel::vxlan::{Error, Vxlan};
+
+    #[test]
+    fn create() {
+        let mut packet = [0x08, 0x00, 0x00, 0x00, 0x01, 0x02, 0x03, 0x00, 0xAA];
+        let (mut vxlan, rem) = Vxlan::new_mut(&mut packet).unwrap();
+
+        assert_eq!(vxlan.vni(), 0x010203);
+        assert_eq!(rem, &[0xAA]);
+
+        vxlan.set_vni(0xFF123456);
+        assert_eq!(vxlan.vni(), 0x123456);
+        assert_eq!(vxlan.slice()[7], 0);
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(matches!(
+            Vxlan::new(&[0x08, 0x00]),
+            Err(Error::InvalidSize(2))
+        ));
+        assert!(matches!(Vxlan::new(&[0; 8]), Err(Error::MissingVni)));
+    }
+}